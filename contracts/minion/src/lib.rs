@@ -0,0 +1,631 @@
+extern crate near_sdk;
+extern crate serde;
+
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::collections::LookupMap;
+use near_sdk::json_types::{Base64VecU8, U128, U64};
+use near_sdk::{
+    env, ext_contract, near_bindgen, setup_alloc, AccountId, PanicOnDefault, Promise, PromiseResult,
+};
+
+use serde::{Deserialize, Serialize};
+
+/// Gas for the get_proposal_status view on moloch
+const GAS_FOR_PROPOSAL_STATUS: u64 = 10_000_000_000_000;
+/// Gas for each of the minion's own callbacks
+const GAS_FOR_CALLBACK: u64 = 10_000_000_000_000;
+
+setup_alloc!();
+
+#[ext_contract(ext_moloch)]
+pub trait Moloch {
+    fn get_proposal_status(&self, proposal_index: U64) -> ProposalStatus;
+}
+
+#[ext_contract(ext_self)]
+pub trait MinionCallbacks {
+    fn on_propose_action(
+        &mut self,
+        proposal_index: U64,
+        action: Action,
+        storage_deposit: U128,
+    ) -> U128;
+    fn on_action_stored(&mut self, proposer: AccountId, storage_deposit: U128) -> U128;
+    fn on_proposal_status(&mut self, proposal_index: U64) -> Promise;
+    fn on_execute(&mut self, proposal_index: U64) -> bool;
+}
+
+/// Where a moloch proposal stands, see Moloch's get_proposal_status
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct ProposalStatus {
+    proposer: AccountId,
+    processed: bool,
+    did_pass: bool,
+    aborted: bool,
+    starting_period: U64,
+    current_period: U64,
+    details: String,
+}
+
+/// A call the minion makes once its proposal has passed
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct Action {
+    /// The member who submitted the proposal the action belongs to
+    proposer: AccountId,
+    /// The contract the minion calls
+    receiver_id: AccountId,
+    method_name: String,
+    args: Base64VecU8,
+    /// NEAR the minion attaches to the call
+    deposit: U128,
+    gas: U64,
+    /// true once the call has been made, reset if the call fails
+    executed: bool,
+}
+
+impl Action {
+    /// Hex encoded sha256 of the call, which the proposal's details have to contain
+    fn hash(&self) -> String {
+        let call = (
+            self.receiver_id.clone(),
+            self.method_name.clone(),
+            self.args.0.clone(),
+            u128::from(self.deposit),
+            u64::from(self.gas),
+        );
+        env::sha256(&call.try_to_vec().unwrap())
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+}
+
+// Minion
+//
+// Holds assets on behalf of a moloch DAO and makes arbitrary calls, each tied
+// to a moloch proposal. A call is only made once moloch reports its proposal
+// as processed and passed, so the guild bank never has to release funds for
+// it and rage quit is unaffected.
+#[near_bindgen]
+#[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
+pub struct Minion {
+    /// The moloch whose proposals approve actions
+    moloch_id: AccountId,
+    /// The action of each proposal, by proposal index
+    actions: LookupMap<u64, Action>,
+}
+
+#[near_bindgen]
+impl Minion {
+    #[init]
+    pub fn new(moloch_id: AccountId) -> Self {
+        assert!(!env::state_exists(), "Already initialized");
+        assert!(
+            env::is_valid_account_id(moloch_id.as_bytes()),
+            "Moloch must be a valid account"
+        );
+        Self {
+            moloch_id: moloch_id,
+            actions: LookupMap::new(b"actions".to_vec()),
+        }
+    }
+
+    /// The proposer of a moloch proposal attaches the action the minion takes if it passes,
+    /// before voting on the proposal starts. The proposal's details have to contain the action
+    /// hash, see get_action_hash, so the proposal commits to the call members vote on. The
+    /// attached deposit pays for the action's storage, the rest is refunded.
+    #[payable]
+    pub fn propose_action(
+        &mut self,
+        proposal_index: U64,
+        receiver_id: AccountId,
+        method_name: String,
+        args: Base64VecU8,
+        deposit: U128,
+        gas: U64,
+    ) -> Promise {
+        assert!(
+            env::is_valid_account_id(receiver_id.as_bytes()),
+            "Receiver must be a valid account"
+        );
+        assert!(!method_name.is_empty(), "Method name cannot be empty");
+        assert!(u64::from(gas) > 0, "Gas cannot be 0");
+        assert!(
+            !self.actions.contains_key(&proposal_index.into()),
+            "Proposal {} already has an action",
+            u64::from(proposal_index)
+        );
+        let action = Action {
+            proposer: env::predecessor_account_id(),
+            receiver_id: receiver_id,
+            method_name: method_name,
+            args: args,
+            deposit: deposit,
+            gas: gas,
+            executed: false,
+        };
+        ext_moloch::get_proposal_status(proposal_index, &self.moloch_id, 0, GAS_FOR_PROPOSAL_STATUS)
+            .then(ext_self::on_propose_action(
+                proposal_index,
+                action,
+                env::attached_deposit().into(),
+                &env::current_account_id(),
+                0,
+                GAS_FOR_CALLBACK,
+            ))
+            .then(ext_self::on_action_stored(
+                env::predecessor_account_id(),
+                env::attached_deposit().into(),
+                &env::current_account_id(),
+                0,
+                GAS_FOR_CALLBACK,
+            ))
+    }
+
+    /// Adds the action if its proposer submitted the proposal, voting on the proposal hasn't
+    /// started and its details contain the action hash. Returns the storage cost of the action.
+    #[private]
+    pub fn on_propose_action(
+        &mut self,
+        proposal_index: U64,
+        action: Action,
+        storage_deposit: U128,
+    ) -> U128 {
+        let initial_storage_usage = env::storage_usage();
+        let status = self.proposal_status();
+        let index = u64::from(proposal_index);
+        assert!(
+            !status.processed,
+            "Actions can only be added to unprocessed proposals"
+        );
+        assert!(
+            u64::from(status.starting_period) > u64::from(status.current_period),
+            "Actions can only be added before voting on proposal {} starts",
+            index
+        );
+        assert!(
+            status.proposer == action.proposer,
+            "Only the proposer of proposal {} can add its action",
+            index
+        );
+        let hash = action.hash();
+        assert!(
+            status.details.contains(&hash),
+            "The details of proposal {} must contain the action hash {}",
+            index,
+            hash
+        );
+        assert!(
+            !self.actions.contains_key(&index),
+            "Proposal {} already has an action",
+            index
+        );
+        self.actions.insert(&index, &action);
+        let storage_cost =
+            u128::from(env::storage_usage() - initial_storage_usage) * env::storage_byte_cost();
+        assert!(
+            u128::from(storage_deposit) >= storage_cost,
+            "Attach at least {} yoctoNEAR to cover the action's storage",
+            storage_cost
+        );
+        env::log(
+            format!(
+                "Action proposed! proposal_index: {}, receiver: {}, method: {}, hash: {}",
+                index, action.receiver_id, action.method_name, hash
+            )
+            .as_bytes(),
+        );
+        storage_cost.into()
+    }
+
+    /// Refunds what the action's storage did not use of the deposit, all of it if the action
+    /// was not added. Returns the refund.
+    #[private]
+    pub fn on_action_stored(&mut self, proposer: AccountId, storage_deposit: U128) -> U128 {
+        let storage_cost = match env::promise_result(0) {
+            PromiseResult::Successful(result) => {
+                near_sdk::serde_json::from_slice::<U128>(&result).map_or(0, u128::from)
+            }
+            _ => 0,
+        };
+        let refund = u128::from(storage_deposit).saturating_sub(storage_cost);
+        if refund > 0 {
+            Promise::new(proposer).transfer(refund);
+        }
+        refund.into()
+    }
+
+    /// Anyone can execute the action of a passed proposal. The minion checks the proposal with
+    /// moloch before making the call.
+    pub fn execute_action(&mut self, proposal_index: U64) -> Promise {
+        let action = self
+            .get_action(proposal_index)
+            .expect("No action for proposal");
+        assert!(!action.executed, "Action already executed");
+        let required_gas = u64::from(action.gas)
+            .saturating_add(GAS_FOR_PROPOSAL_STATUS)
+            .saturating_add(3 * GAS_FOR_CALLBACK);
+        assert!(
+            env::prepaid_gas() >= required_gas,
+            "Attach at least {} gas",
+            required_gas
+        );
+        ext_moloch::get_proposal_status(proposal_index, &self.moloch_id, 0, GAS_FOR_PROPOSAL_STATUS)
+            .then(ext_self::on_proposal_status(
+                proposal_index,
+                &env::current_account_id(),
+                0,
+                u64::from(action.gas).saturating_add(GAS_FOR_CALLBACK * 2),
+            ))
+    }
+
+    /// Makes the action's call if moloch reports its proposal as processed and passed
+    #[private]
+    pub fn on_proposal_status(&mut self, proposal_index: U64) -> Promise {
+        let status = self.proposal_status();
+        let index = u64::from(proposal_index);
+        assert!(
+            status.processed && status.did_pass && !status.aborted,
+            "Proposal {} has not passed",
+            index
+        );
+        let mut action = self.actions.get(&index).expect("No action for proposal");
+        assert!(!action.executed, "Action already executed");
+        action.executed = true;
+        self.actions.insert(&index, &action);
+        Promise::new(action.receiver_id)
+            .function_call(
+                action.method_name.into_bytes(),
+                action.args.into(),
+                action.deposit.into(),
+                action.gas.into(),
+            )
+            .then(ext_self::on_execute(
+                proposal_index,
+                &env::current_account_id(),
+                0,
+                GAS_FOR_CALLBACK,
+            ))
+    }
+
+    /// Lets a failed action be executed again
+    #[private]
+    pub fn on_execute(&mut self, proposal_index: U64) -> bool {
+        let index = u64::from(proposal_index);
+        match env::promise_result(0) {
+            PromiseResult::Successful(_) => {
+                env::log(format!("Action executed! proposal_index: {}", index).as_bytes());
+                true
+            }
+            _ => {
+                let mut action = self.actions.get(&index).unwrap();
+                action.executed = false;
+                self.actions.insert(&index, &action);
+                env::log(format!("Action failed! proposal_index: {}", index).as_bytes());
+                false
+            }
+        }
+    }
+
+    pub fn get_moloch(&self) -> AccountId {
+        self.moloch_id.clone()
+    }
+
+    pub fn get_action(&self, proposal_index: U64) -> Option<Action> {
+        self.actions.get(&proposal_index.into())
+    }
+
+    /// Returns the hash a proposal's details have to contain to add this action
+    pub fn get_action_hash(
+        &self,
+        receiver_id: AccountId,
+        method_name: String,
+        args: Base64VecU8,
+        deposit: U128,
+        gas: U64,
+    ) -> String {
+        Action {
+            proposer: env::predecessor_account_id(),
+            receiver_id: receiver_id,
+            method_name: method_name,
+            args: args,
+            deposit: deposit,
+            gas: gas,
+            executed: false,
+        }
+        .hash()
+    }
+}
+
+impl Minion {
+    /// The proposal status returned by moloch to a callback
+    fn proposal_status(&self) -> ProposalStatus {
+        assert_eq!(
+            env::promise_results_count(),
+            1,
+            "Expected one promise result"
+        );
+        match env::promise_result(0) {
+            PromiseResult::Successful(result) => near_sdk::serde_json::from_slice(&result)
+                .expect("Moloch returned an invalid proposal status"),
+            _ => panic!("Could not get the proposal status from moloch"),
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::{testing_env, MockedBlockchain, RuntimeFeesConfig, VMConfig, VMContext};
+    use std::collections::HashMap;
+    use std::convert::TryInto;
+
+    fn bob() -> AccountId {
+        "bob.near".to_string()
+    }
+
+    fn alice() -> AccountId {
+        "alice.testnet".to_string()
+    }
+
+    fn moloch() -> AccountId {
+        "moloch.near".to_string()
+    }
+
+    fn get_context(predecessor: AccountId) -> VMContext {
+        VMContextBuilder::new()
+            .current_account_id("minion.near".try_into().unwrap())
+            .predecessor_account_id(predecessor.try_into().unwrap())
+            .prepaid_gas(300_000_000_000_000)
+            .build()
+    }
+
+    fn with_result(result: PromiseResult) {
+        testing_env!(
+            get_context("minion.near".to_string()),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            HashMap::default(),
+            vec![result]
+        );
+    }
+
+    fn status_result(proposer: AccountId, processed: bool, did_pass: bool) -> PromiseResult {
+        let status = ProposalStatus {
+            proposer: proposer,
+            processed: processed,
+            did_pass: did_pass,
+            aborted: false,
+            starting_period: 1.into(),
+            current_period: 0.into(),
+            details: format!("Ping alice, action: {}", action().hash()),
+        };
+        PromiseResult::Successful(near_sdk::serde_json::to_vec(&status).unwrap())
+    }
+
+    fn storage_deposit() -> U128 {
+        10u128.pow(24).into()
+    }
+
+    fn action() -> Action {
+        Action {
+            proposer: bob(),
+            receiver_id: alice(),
+            method_name: "ping".to_string(),
+            args: vec![].into(),
+            deposit: 10.into(),
+            gas: 20_000_000_000_000.into(),
+            executed: false,
+        }
+    }
+
+    fn minion_with_action() -> Minion {
+        testing_env!(get_context(bob()));
+        let mut minion = Minion::new(moloch());
+        with_result(status_result(bob(), false, false));
+        minion.on_propose_action(0.into(), action(), storage_deposit());
+        minion
+    }
+
+    #[test]
+    fn propose_action() {
+        testing_env!(get_context(bob()));
+        let mut minion = Minion::new(moloch());
+        minion.propose_action(
+            0.into(),
+            alice(),
+            "ping".to_string(),
+            vec![].into(),
+            10.into(),
+            20_000_000_000_000.into(),
+        );
+        assert_eq!(minion.get_action(0.into()), None);
+    }
+
+    #[test]
+    fn on_propose_action() {
+        let minion = minion_with_action();
+        assert_eq!(minion.get_action(0.into()), Some(action()));
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Only the proposer of proposal 0 can add its action"#)]
+    fn on_propose_action_not_proposer() {
+        testing_env!(get_context(bob()));
+        let mut minion = Minion::new(moloch());
+        with_result(status_result(alice(), false, false));
+        minion.on_propose_action(0.into(), action(), storage_deposit());
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Actions can only be added to unprocessed proposals"#)]
+    fn on_propose_action_processed() {
+        testing_env!(get_context(bob()));
+        let mut minion = Minion::new(moloch());
+        with_result(status_result(bob(), true, true));
+        minion.on_propose_action(0.into(), action(), storage_deposit());
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Actions can only be added before voting on proposal 0 starts"#)]
+    fn on_propose_action_voting_started() {
+        testing_env!(get_context(bob()));
+        let mut minion = Minion::new(moloch());
+        let status = ProposalStatus {
+            proposer: bob(),
+            processed: false,
+            did_pass: false,
+            aborted: false,
+            starting_period: 1.into(),
+            current_period: 1.into(),
+            details: action().hash(),
+        };
+        with_result(PromiseResult::Successful(
+            near_sdk::serde_json::to_vec(&status).unwrap(),
+        ));
+        minion.on_propose_action(0.into(), action(), storage_deposit());
+    }
+
+    #[test]
+    #[should_panic(expected = r#"The details of proposal 0 must contain the action hash"#)]
+    fn on_propose_action_not_in_details() {
+        testing_env!(get_context(bob()));
+        let mut minion = Minion::new(moloch());
+        let mut other_action = action();
+        other_action.method_name = "pong".to_string();
+        with_result(status_result(bob(), false, false));
+        minion.on_propose_action(0.into(), other_action, storage_deposit());
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Attach at least"#)]
+    fn on_propose_action_storage_not_covered() {
+        testing_env!(get_context(bob()));
+        let mut minion = Minion::new(moloch());
+        with_result(status_result(bob(), false, false));
+        minion.on_propose_action(0.into(), action(), 1.into());
+    }
+
+    #[test]
+    fn on_action_stored_refunds_unused_deposit() {
+        testing_env!(get_context(bob()));
+        let mut minion = Minion::new(moloch());
+        with_result(PromiseResult::Successful(
+            near_sdk::serde_json::to_vec(&U128(100)).unwrap(),
+        ));
+        assert_eq!(
+            minion.on_action_stored(bob(), 1_000.into()),
+            U128(900),
+            "Unused deposit was not refunded"
+        );
+    }
+
+    #[test]
+    fn on_action_stored_failed_refunds_deposit() {
+        testing_env!(get_context(bob()));
+        let mut minion = Minion::new(moloch());
+        with_result(PromiseResult::Failed);
+        assert_eq!(
+            minion.on_action_stored(bob(), 1_000.into()),
+            U128(1_000),
+            "Deposit was not refunded"
+        );
+    }
+
+    #[test]
+    fn get_action_hash() {
+        testing_env!(get_context(bob()));
+        let minion = Minion::new(moloch());
+        let hash = minion.get_action_hash(
+            alice(),
+            "ping".to_string(),
+            vec![].into(),
+            10.into(),
+            20_000_000_000_000.into(),
+        );
+        assert_eq!(hash, action().hash());
+        assert_eq!(hash.len(), 64);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Proposal 0 already has an action"#)]
+    fn propose_action_twice() {
+        let mut minion = minion_with_action();
+        testing_env!(get_context(bob()));
+        minion.propose_action(
+            0.into(),
+            alice(),
+            "ping".to_string(),
+            vec![].into(),
+            10.into(),
+            20_000_000_000_000.into(),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Could not get the proposal status from moloch"#)]
+    fn on_propose_action_failed_status() {
+        testing_env!(get_context(bob()));
+        let mut minion = Minion::new(moloch());
+        with_result(PromiseResult::Failed);
+        minion.on_propose_action(0.into(), action(), storage_deposit());
+    }
+
+    #[test]
+    fn execute_action() {
+        let mut minion = minion_with_action();
+        testing_env!(get_context(alice()));
+        minion.execute_action(0.into());
+        with_result(status_result(bob(), true, true));
+        minion.on_proposal_status(0.into());
+        assert!(minion.get_action(0.into()).unwrap().executed);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Attach at least"#)]
+    fn execute_action_not_enough_gas() {
+        let mut minion = minion_with_action();
+        let mut context = get_context(alice());
+        context.prepaid_gas = 20_000_000_000_000;
+        testing_env!(context);
+        minion.execute_action(0.into());
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Proposal 0 has not passed"#)]
+    fn on_proposal_status_failed_proposal() {
+        let mut minion = minion_with_action();
+        with_result(status_result(bob(), true, false));
+        minion.on_proposal_status(0.into());
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Proposal 0 has not passed"#)]
+    fn on_proposal_status_unprocessed_proposal() {
+        let mut minion = minion_with_action();
+        with_result(status_result(bob(), false, false));
+        minion.on_proposal_status(0.into());
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Action already executed"#)]
+    fn execute_action_twice() {
+        let mut minion = minion_with_action();
+        with_result(status_result(bob(), true, true));
+        minion.on_proposal_status(0.into());
+        testing_env!(get_context(alice()));
+        minion.execute_action(0.into());
+    }
+
+    #[test]
+    fn on_execute_failed_allows_retry() {
+        let mut minion = minion_with_action();
+        with_result(status_result(bob(), true, true));
+        minion.on_proposal_status(0.into());
+        with_result(PromiseResult::Failed);
+        assert!(!minion.on_execute(0.into()));
+        assert!(!minion.get_action(0.into()).unwrap().executed);
+    }
+}
@@ -0,0 +1,677 @@
+extern crate near_sdk;
+extern crate serde;
+
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::collections::{LookupMap, UnorderedMap, Vector};
+use near_sdk::json_types::{Base64VecU8, U128, U64};
+use near_sdk::{
+    env, ext_contract, near_bindgen, setup_alloc, AccountId, PanicOnDefault, Promise, PromiseResult,
+};
+
+use serde::{Deserialize, Serialize};
+
+use std::cmp::min;
+
+/// Bytes of state a new DAO needs on top of its code, paid for by the summoner
+const DAO_STATE_BYTES: u128 = 10_000;
+/// Maximum founders of a new DAO, which bounds the registry reserve
+const MAX_FOUNDERS: usize = 100;
+/// Bytes the factory's registry needs for a new DAO, held back from the summoner's deposit
+const REGISTRY_BASE_BYTES: u128 = 1_000;
+/// Registry bytes needed for each founder of a new DAO
+const REGISTRY_BYTES_PER_FOUNDER: u128 = 400;
+
+setup_alloc!();
+
+#[ext_contract(ext_self)]
+pub trait MolochFactoryCallbacks {
+    fn on_create(
+        &mut self,
+        dao_id: AccountId,
+        summoner: AccountId,
+        args: SummonArgs,
+        code_version: U64,
+        deposit: U128,
+        registry_reserve: U128,
+    ) -> bool;
+}
+
+/// A founding member of a new DAO, serialized the way Moloch::new expects it
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Founder {
+    account_id: AccountId,
+    shares: U128,
+    delegate_key: Option<AccountId>,
+}
+
+/// What a new DAO measures periods in, see Moloch's PeriodClock
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Copy)]
+pub enum PeriodClock {
+    Timestamp,
+    BlockHeight,
+}
+
+/// The arguments of Moloch::new
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SummonArgs {
+    founders: Vec<Founder>,
+    approved_token: AccountId,
+    period_duration: U64,
+    period_clock: PeriodClock,
+    voting_period_length: U64,
+    grace_period_length: U64,
+    abort_window: U64,
+    proposal_deposit: U128,
+    dilution_bound: U128,
+    processing_reward: U128,
+    abort_penalty: U128,
+    guardian: Option<AccountId>,
+}
+
+impl SummonArgs {
+    /// The deposit held back to pay for registering the DAO in the factory
+    fn registry_reserve(&self) -> u128 {
+        (REGISTRY_BASE_BYTES + REGISTRY_BYTES_PER_FOUNDER * self.founders.len() as u128)
+            * env::storage_byte_cost()
+    }
+
+    fn summoners(&self) -> Vec<AccountId> {
+        self.founders
+            .iter()
+            .map(|founder| founder.account_id.clone())
+            .collect()
+    }
+}
+
+/// A DAO the factory created
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct DaoInfo {
+    /// The founders the DAO was summoned with
+    summoners: Vec<AccountId>,
+    /// The DAO's approved token
+    approved_token: AccountId,
+    /// The code version the DAO runs
+    code_version: U64,
+    created_at: U64,
+}
+
+/// A stored version of the Moloch wasm
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct CodeVersion {
+    /// Hex encoded sha256 of the code
+    hash: String,
+    /// Size of the code in bytes
+    size: U64,
+    created_at: U64,
+}
+
+#[near_bindgen]
+#[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
+pub struct MolochFactory {
+    /// The account allowed to store new code versions
+    owner_id: AccountId,
+    /// Moloch wasm by version, the latest version is used for new DAOs by default
+    code: Vector<Vec<u8>>,
+    /// Details of each stored code version
+    code_versions: Vector<CodeVersion>,
+    /// DAOs the factory created
+    daos: UnorderedMap<AccountId, DaoInfo>,
+    /// DAOs each account was a summoner of
+    daos_by_summoner: LookupMap<AccountId, Vec<AccountId>>,
+}
+
+#[near_bindgen]
+impl MolochFactory {
+    #[init]
+    pub fn new(owner_id: AccountId) -> Self {
+        assert!(!env::state_exists(), "Already initialized");
+        assert!(
+            env::is_valid_account_id(owner_id.as_bytes()),
+            "Owner must be a valid account"
+        );
+        Self {
+            owner_id: owner_id,
+            code: Vector::new(b"code".to_vec()),
+            code_versions: Vector::new(b"code_versions".to_vec()),
+            daos: UnorderedMap::new(b"daos".to_vec()),
+            daos_by_summoner: LookupMap::new(b"daos_by_summoner".to_vec()),
+        }
+    }
+
+    /// The owner stores a new version of the Moloch wasm, passed as the raw input of the call.
+    /// The attached deposit has to cover the storage of the code, the rest is refunded.
+    #[payable]
+    pub fn store_code(&mut self) -> U64 {
+        let code = env::input().expect("Expected the Moloch wasm as input");
+        self.add_code_version(code).into()
+    }
+
+    /// Creates `<name>.<factory>`, deploys a code version to it, the latest by default, and
+    /// summons the DAO with args in one transaction. The attached deposit pays for the DAO's
+    /// storage and its entry in the factory's registry, and is refunded if the DAO could not
+    /// be created.
+    #[payable]
+    pub fn create(&mut self, name: String, args: SummonArgs, code_version: Option<U64>) -> Promise {
+        let dao_id = format!("{}.{}", name, env::current_account_id());
+        assert!(
+            !name.is_empty() && !name.contains('.') && env::is_valid_account_id(dao_id.as_bytes()),
+            "Invalid DAO name {}",
+            name
+        );
+        assert!(
+            self.daos.get(&dao_id).is_none(),
+            "DAO {} already exists",
+            dao_id
+        );
+        let code_version = match code_version {
+            Some(code_version) => u64::from(code_version),
+            None => {
+                assert!(!self.code.is_empty(), "No code stored");
+                self.code.len() - 1
+            }
+        };
+        let code = self.code.get(code_version).expect("Unknown code version");
+        // Moloch::new validates the rest of args, on_create refunds the deposit if it fails
+        assert!(
+            args.founders.len() <= MAX_FOUNDERS,
+            "Too many founders: greater than max founders"
+        );
+
+        let deposit = env::attached_deposit();
+        let registry_reserve = args.registry_reserve();
+        let required_deposit =
+            (code.len() as u128 + DAO_STATE_BYTES) * env::storage_byte_cost() + registry_reserve;
+        assert!(
+            deposit >= required_deposit,
+            "Attach at least {} yoctoNEAR to cover the DAO's storage",
+            required_deposit
+        );
+
+        let prepaid_gas = env::prepaid_gas();
+        Promise::new(dao_id.clone())
+            .create_account()
+            .transfer(deposit - registry_reserve)
+            .deploy_contract(code)
+            .function_call(
+                b"new".to_vec(),
+                near_sdk::serde_json::to_vec(&args).unwrap(),
+                0,
+                prepaid_gas / 3,
+            )
+            .then(ext_self::on_create(
+                dao_id,
+                env::predecessor_account_id(),
+                args,
+                code_version.into(),
+                deposit.into(),
+                registry_reserve.into(),
+                &env::current_account_id(),
+                0,
+                prepaid_gas / 3,
+            ))
+    }
+
+    /// Registers a created DAO and refunds what the registry did not use of the reserve, or
+    /// refunds the whole deposit if creating the DAO failed
+    #[private]
+    pub fn on_create(
+        &mut self,
+        dao_id: AccountId,
+        summoner: AccountId,
+        args: SummonArgs,
+        code_version: U64,
+        deposit: U128,
+        registry_reserve: U128,
+    ) -> bool {
+        assert_eq!(
+            env::promise_results_count(),
+            1,
+            "Expected one promise result"
+        );
+        match env::promise_result(0) {
+            PromiseResult::Successful(_) => {
+                let initial_storage_usage = env::storage_usage();
+                let summoners = args.summoners();
+                for account_id in summoners.iter() {
+                    let mut daos = self.get_daos_by_summoner(account_id.to_string());
+                    daos.push(dao_id.clone());
+                    self.daos_by_summoner.insert(account_id, &daos);
+                }
+                self.daos.insert(
+                    &dao_id,
+                    &DaoInfo {
+                        summoners: summoners,
+                        approved_token: args.approved_token,
+                        code_version: code_version,
+                        created_at: env::block_timestamp().into(),
+                    },
+                );
+                let registry_cost = u128::from(env::storage_usage() - initial_storage_usage)
+                    * env::storage_byte_cost();
+                let refund = u128::from(registry_reserve).saturating_sub(registry_cost);
+                if refund > 0 {
+                    Promise::new(summoner.clone()).transfer(refund);
+                }
+                env::log(
+                    format!(
+                        "DAO created! dao: {}, summoner: {}, code_version: {}, registry_cost: {}",
+                        dao_id,
+                        summoner,
+                        u64::from(code_version),
+                        registry_cost
+                    )
+                    .as_bytes(),
+                );
+                true
+            }
+            _ => {
+                Promise::new(summoner.clone()).transfer(deposit.into());
+                env::log(
+                    format!(
+                        "DAO creation failed! dao: {}, summoner: {}, refund: {}",
+                        dao_id,
+                        summoner,
+                        u128::from(deposit)
+                    )
+                    .as_bytes(),
+                );
+                false
+            }
+        }
+    }
+
+    /// A DAO opts into a stored code version. When an upgrade proposal passes the DAO fetches
+    /// the code with get_code, deploys it to itself and then records the version it runs here.
+    pub fn update_dao_code_version(&mut self, code_version: U64) {
+        let dao_id = env::predecessor_account_id();
+        let mut dao = self
+            .daos
+            .get(&dao_id)
+            .expect("Only DAOs created by this factory can update their code version");
+        assert!(
+            u64::from(code_version) < self.code.len(),
+            "Unknown code version"
+        );
+        dao.code_version = code_version;
+        self.daos.insert(&dao_id, &dao);
+        env::log(
+            format!(
+                "DAO code version updated! dao: {}, code_version: {}",
+                dao_id,
+                u64::from(code_version)
+            )
+            .as_bytes(),
+        );
+    }
+
+    pub fn get_owner(&self) -> AccountId {
+        self.owner_id.clone()
+    }
+
+    /// Returns the latest code version, None before any code is stored
+    pub fn get_latest_code_version(&self) -> Option<U64> {
+        match self.code.len() {
+            0 => None,
+            len => Some((len - 1).into()),
+        }
+    }
+
+    pub fn get_code_versions(&self) -> Vec<CodeVersion> {
+        self.code_versions.to_vec()
+    }
+
+    /// Returns the wasm of a code version
+    pub fn get_code(&self, code_version: U64) -> Base64VecU8 {
+        self.code
+            .get(code_version.into())
+            .expect("Unknown code version")
+            .into()
+    }
+
+    pub fn get_dao(&self, dao_id: AccountId) -> Option<DaoInfo> {
+        self.daos.get(&dao_id)
+    }
+
+    /// Returns limit DAOs in the order they were created starting at from
+    pub fn get_daos(&self, from: U64, limit: U64) -> Vec<(AccountId, DaoInfo)> {
+        let keys = self.daos.keys_as_vector();
+        let values = self.daos.values_as_vector();
+        let from = u64::from(from);
+        let to = min(from.saturating_add(limit.into()), keys.len());
+        (from..to)
+            .map(|index| (keys.get(index).unwrap(), values.get(index).unwrap()))
+            .collect()
+    }
+
+    pub fn get_daos_by_summoner(&self, summoner: AccountId) -> Vec<AccountId> {
+        match self.daos_by_summoner.get(&summoner) {
+            Some(daos) => daos,
+            None => vec![],
+        }
+    }
+}
+
+impl MolochFactory {
+    fn add_code_version(&mut self, code: Vec<u8>) -> u64 {
+        let initial_storage_usage = env::storage_usage();
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.owner_id,
+            "Only the owner can store code"
+        );
+        assert!(!code.is_empty(), "Code cannot be empty");
+        let hash: String = env::sha256(&code)
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        self.code_versions.push(&CodeVersion {
+            hash: hash.clone(),
+            size: (code.len() as u64).into(),
+            created_at: env::block_timestamp().into(),
+        });
+        self.code.push(&code);
+        let storage_cost =
+            u128::from(env::storage_usage() - initial_storage_usage) * env::storage_byte_cost();
+        assert!(
+            env::attached_deposit() >= storage_cost,
+            "Attach at least {} yoctoNEAR to cover the code's storage",
+            storage_cost
+        );
+        let refund = env::attached_deposit() - storage_cost;
+        if refund > 0 {
+            Promise::new(env::predecessor_account_id()).transfer(refund);
+        }
+        let code_version = self.code.len() - 1;
+        env::log(
+            format!(
+                "Code stored! code_version: {}, hash: {}, refund: {}",
+                code_version, hash, refund
+            )
+            .as_bytes(),
+        );
+        code_version
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::{get_logs, VMContextBuilder};
+    use near_sdk::{testing_env, MockedBlockchain, RuntimeFeesConfig, VMConfig, VMContext};
+    use std::collections::HashMap;
+    use std::convert::TryInto;
+
+    fn owner() -> AccountId {
+        "owner.near".to_string()
+    }
+
+    fn bob() -> AccountId {
+        "bob.near".to_string()
+    }
+
+    fn fdai() -> AccountId {
+        "fdai.testnet".to_string()
+    }
+
+    fn code() -> Vec<u8> {
+        vec![0, 97, 115, 109]
+    }
+
+    fn get_context(predecessor: AccountId, attached_deposit: u128) -> VMContext {
+        VMContextBuilder::new()
+            .current_account_id("factory.near".try_into().unwrap())
+            .predecessor_account_id(predecessor.try_into().unwrap())
+            .attached_deposit(attached_deposit)
+            .build()
+    }
+
+    fn summon_args() -> SummonArgs {
+        SummonArgs {
+            founders: vec![Founder {
+                account_id: bob(),
+                shares: 1.into(),
+                delegate_key: None,
+            }],
+            approved_token: fdai(),
+            period_duration: 10u64.pow(9).into(),
+            period_clock: PeriodClock::Timestamp,
+            voting_period_length: 3.into(),
+            grace_period_length: 1.into(),
+            abort_window: 1.into(),
+            proposal_deposit: 10.into(),
+            dilution_bound: 1.into(),
+            processing_reward: 1.into(),
+            abort_penalty: 1.into(),
+            guardian: None,
+        }
+    }
+
+    fn factory_with_code() -> MolochFactory {
+        testing_env!(get_context(owner(), 10u128.pow(24)));
+        let mut factory = MolochFactory::new(owner());
+        factory.add_code_version(code());
+        factory
+    }
+
+    #[test]
+    fn store_code() {
+        let factory = factory_with_code();
+        assert_eq!(factory.get_latest_code_version(), Some(0.into()));
+        assert_eq!(Vec::<u8>::from(factory.get_code(0.into())), code());
+        let versions = factory.get_code_versions();
+        assert_eq!(versions.len(), 1);
+        assert_eq!(u64::from(versions[0].size), 4);
+        assert_eq!(versions[0].hash.len(), 64);
+    }
+
+    #[test]
+    fn store_code_refunds_excess_deposit() {
+        testing_env!(get_context(owner(), 10u128.pow(24)));
+        let mut factory = MolochFactory::new(owner());
+        let initial_storage_usage = env::storage_usage();
+        factory.add_code_version(code());
+        let storage_cost =
+            u128::from(env::storage_usage() - initial_storage_usage) * env::storage_byte_cost();
+        testing_env!(get_context(owner(), storage_cost + 10));
+        factory.add_code_version(code());
+        let log = get_logs().pop().unwrap();
+        assert!(
+            log.ends_with("refund: 10"),
+            "Excess deposit was not refunded"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Only the owner can store code"#)]
+    fn store_code_not_owner() {
+        testing_env!(get_context(bob(), 10u128.pow(24)));
+        let mut factory = MolochFactory::new(owner());
+        factory.add_code_version(code());
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Attach at least"#)]
+    fn store_code_storage_not_covered() {
+        testing_env!(get_context(owner(), 0));
+        let mut factory = MolochFactory::new(owner());
+        factory.add_code_version(code());
+    }
+
+    #[test]
+    fn create() {
+        let mut factory = factory_with_code();
+        testing_env!(get_context(bob(), 10u128.pow(24)));
+        factory.create("dao".to_string(), summon_args(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Invalid DAO name dao.sub"#)]
+    fn create_invalid_name() {
+        let mut factory = factory_with_code();
+        testing_env!(get_context(bob(), 10u128.pow(24)));
+        factory.create("dao.sub".to_string(), summon_args(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"No code stored"#)]
+    fn create_without_code() {
+        testing_env!(get_context(bob(), 10u128.pow(24)));
+        let mut factory = MolochFactory::new(owner());
+        factory.create("dao".to_string(), summon_args(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Unknown code version"#)]
+    fn create_unknown_code_version() {
+        let mut factory = factory_with_code();
+        testing_env!(get_context(bob(), 10u128.pow(24)));
+        factory.create("dao".to_string(), summon_args(), Some(1.into()));
+    }
+
+    // Moloch::new rejects invalid args and on_create refunds the deposit, see on_create_failed
+    #[test]
+    fn create_invalid_args() {
+        let mut factory = factory_with_code();
+        testing_env!(get_context(bob(), 10u128.pow(24)));
+        let mut args = summon_args();
+        args.abort_window = 4.into();
+        args.founders.push(args.founders[0].clone());
+        factory.create("dao".to_string(), args, None);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Too many founders: greater than max founders"#)]
+    fn create_too_many_founders() {
+        let mut factory = factory_with_code();
+        testing_env!(get_context(bob(), 10u128.pow(24)));
+        let mut args = summon_args();
+        args.founders = (0..=MAX_FOUNDERS)
+            .map(|index| Founder {
+                account_id: format!("founder{}.near", index),
+                shares: 1.into(),
+                delegate_key: None,
+            })
+            .collect();
+        factory.create("dao".to_string(), args, None);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Attach at least"#)]
+    fn create_registry_not_covered() {
+        let mut factory = factory_with_code();
+        let dao_storage = (code().len() as u128 + DAO_STATE_BYTES) * env::storage_byte_cost();
+        testing_env!(get_context(bob(), dao_storage));
+        factory.create("dao".to_string(), summon_args(), None);
+    }
+
+    #[test]
+    fn registry_reserve_covers_max_founders() {
+        let mut factory = factory_with_code();
+        let mut args = summon_args();
+        args.founders = (0..MAX_FOUNDERS)
+            .map(|index| Founder {
+                account_id: format!("founder-with-a-long-account-name-{:03}.near", index),
+                shares: 1.into(),
+                delegate_key: None,
+            })
+            .collect();
+        testing_env!(
+            get_context("factory.near".to_string(), 0),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            HashMap::default(),
+            vec![PromiseResult::Successful(vec![])]
+        );
+        let initial_storage_usage = env::storage_usage();
+        factory.on_create(
+            "dao-with-a-long-account-name.factory.near".to_string(),
+            bob(),
+            args.clone(),
+            0.into(),
+            10u128.pow(24).into(),
+            args.registry_reserve().into(),
+        );
+        let registry_cost =
+            u128::from(env::storage_usage() - initial_storage_usage) * env::storage_byte_cost();
+        assert!(registry_cost <= args.registry_reserve());
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Attach at least"#)]
+    fn create_storage_not_covered() {
+        let mut factory = factory_with_code();
+        testing_env!(get_context(bob(), 1));
+        factory.create("dao".to_string(), summon_args(), None);
+    }
+
+    fn on_create(factory: &mut MolochFactory, result: PromiseResult) -> bool {
+        testing_env!(
+            get_context("factory.near".to_string(), 0),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            HashMap::default(),
+            vec![result]
+        );
+        factory.on_create(
+            "dao.factory.near".to_string(),
+            bob(),
+            summon_args(),
+            0.into(),
+            10u128.pow(24).into(),
+            summon_args().registry_reserve().into(),
+        )
+    }
+
+    #[test]
+    fn on_create_registers_dao() {
+        let mut factory = factory_with_code();
+        assert!(on_create(&mut factory, PromiseResult::Successful(vec![])));
+        let dao = factory.get_dao("dao.factory.near".to_string()).unwrap();
+        assert_eq!(dao.summoners, vec![bob()]);
+        assert_eq!(dao.approved_token, fdai());
+        assert_eq!(dao.code_version, 0.into());
+        assert_eq!(
+            factory.get_daos_by_summoner(bob()),
+            vec!["dao.factory.near".to_string()]
+        );
+        assert_eq!(factory.get_daos(0.into(), 10.into()).len(), 1);
+    }
+
+    #[test]
+    fn on_create_failed() {
+        let mut factory = factory_with_code();
+        assert!(!on_create(&mut factory, PromiseResult::Failed));
+        assert_eq!(factory.get_dao("dao.factory.near".to_string()), None);
+        assert_eq!(factory.get_daos_by_summoner(bob()), Vec::<AccountId>::new());
+    }
+
+    #[test]
+    #[should_panic(expected = r#"DAO dao.factory.near already exists"#)]
+    fn create_existing_dao() {
+        let mut factory = factory_with_code();
+        on_create(&mut factory, PromiseResult::Successful(vec![]));
+        testing_env!(get_context(bob(), 10u128.pow(24)));
+        factory.create("dao".to_string(), summon_args(), None);
+    }
+
+    #[test]
+    fn update_dao_code_version() {
+        let mut factory = factory_with_code();
+        on_create(&mut factory, PromiseResult::Successful(vec![]));
+        testing_env!(get_context(owner(), 10u128.pow(24)));
+        factory.add_code_version(vec![0, 97, 115, 109, 1]);
+        testing_env!(get_context("dao.factory.near".to_string(), 0));
+        factory.update_dao_code_version(1.into());
+        let dao = factory.get_dao("dao.factory.near".to_string()).unwrap();
+        assert_eq!(dao.code_version, 1.into());
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Only DAOs created by this factory can update their code version"#)]
+    fn update_dao_code_version_not_a_dao() {
+        let mut factory = factory_with_code();
+        testing_env!(get_context(bob(), 0));
+        factory.update_dao_code_version(0.into());
+    }
+}
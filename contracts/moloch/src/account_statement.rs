@@ -0,0 +1,131 @@
+extern crate near_sdk;
+
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::collections::LookupMap;
+use near_sdk::json_types::{U128, U64};
+use near_sdk::{env, near_bindgen, AccountId, PanicOnDefault};
+
+use crate::{StatementEntry, StatementKind};
+
+/// Entries kept per account, the oldest entry is dropped to make room for a new one
+pub const MAX_STATEMENT_ENTRIES: usize = 20;
+
+// Account statements
+//
+// The most recent movements of each account's escrow and withdrawable
+// balances, so an account can see where its tokens went.
+#[near_bindgen]
+#[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
+pub struct AccountStatements {
+    entries: LookupMap<AccountId, Vec<StatementEntry>>,
+}
+
+impl AccountStatements {
+    pub fn new() -> Self {
+        Self {
+            entries: LookupMap::new(b"account_statements".to_vec()),
+        }
+    }
+
+    pub fn record(
+        &mut self,
+        account_id: &AccountId,
+        kind: StatementKind,
+        token_id: AccountId,
+        amount: u128,
+        proposal_index: Option<u64>,
+    ) {
+        let mut entries = self.statement(account_id);
+        if entries.len() >= MAX_STATEMENT_ENTRIES {
+            entries.remove(0);
+        };
+        entries.push(StatementEntry {
+            kind: kind,
+            token_id: token_id,
+            amount: amount.into(),
+            proposal_index: proposal_index.map(U64::from),
+            timestamp: env::block_timestamp().into(),
+        });
+        self.entries.insert(account_id, &entries);
+    }
+
+    /// Bytes an entry for token_id takes up, the most recording it can add to storage
+    pub fn entry_bytes(&self, token_id: &AccountId, proposal_index: Option<u64>) -> u64 {
+        let entry = StatementEntry {
+            kind: StatementKind::Refund,
+            token_id: token_id.to_string(),
+            amount: U128::from(0),
+            proposal_index: proposal_index.map(U64::from),
+            timestamp: U64::from(0),
+        };
+        entry.try_to_vec().unwrap().len() as u64
+    }
+
+    /// The account's entries, oldest first
+    pub fn statement(&self, account_id: &AccountId) -> Vec<StatementEntry> {
+        return match self.entries.get(account_id) {
+            Some(entries) => entries,
+            None => vec![],
+        };
+    }
+
+    pub fn clear(&mut self, account_id: &AccountId) {
+        self.entries.remove(account_id);
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mocks::{bob, fdai, get_context};
+    use near_sdk::{testing_env, MockedBlockchain};
+
+    #[test]
+    fn record() {
+        let context = get_context(false);
+        testing_env!(context);
+
+        let mut statements = AccountStatements::new();
+        statements.record(&bob(), StatementKind::Deposit, fdai(), 10, None);
+        statements.record(&bob(), StatementKind::DepositLock, fdai(), 5, Some(0));
+        let statement = statements.statement(&bob());
+        assert_eq!(statement.len(), 2);
+        assert_eq!(statement[0].kind, StatementKind::Deposit);
+        assert_eq!(u128::from(statement[1].amount), 5);
+        assert_eq!(statement[1].proposal_index, Some(0.into()));
+    }
+
+    #[test]
+    fn record_drops_oldest() {
+        let context = get_context(false);
+        testing_env!(context);
+
+        let mut statements = AccountStatements::new();
+        for amount in 0..(MAX_STATEMENT_ENTRIES as u128 + 2) {
+            statements.record(&bob(), StatementKind::Deposit, fdai(), amount, None);
+        }
+        let statement = statements.statement(&bob());
+        assert_eq!(statement.len(), MAX_STATEMENT_ENTRIES);
+        assert_eq!(
+            u128::from(statement[0].amount),
+            2,
+            "Oldest entries were not dropped"
+        );
+        assert_eq!(
+            u128::from(statement[MAX_STATEMENT_ENTRIES - 1].amount),
+            MAX_STATEMENT_ENTRIES as u128 + 1
+        );
+    }
+
+    #[test]
+    fn clear() {
+        let context = get_context(false);
+        testing_env!(context);
+
+        let mut statements = AccountStatements::new();
+        statements.record(&bob(), StatementKind::Deposit, fdai(), 10, None);
+        statements.clear(&bob());
+        assert_eq!(statements.statement(&bob()), vec![]);
+    }
+}
@@ -3,25 +3,121 @@ use crate::*;
 use near_contract_standards::fungible_token::receiver::FungibleTokenReceiver;
 use near_sdk::json_types::{ValidAccountId, U128};
 use near_sdk::near_bindgen;
-use near_sdk::{env, PromiseOrValue};
+use near_sdk::{env, Balance, PromiseOrValue};
+
+/// msg that sends the transfer straight to the guild bank
+const DONATE_MSG: &str = "donate";
 
 #[near_bindgen]
 impl FungibleTokenReceiver for Moloch {
     /// Deposit a transfer into the guild bank escrow
     /// As long as the sent token matches the approved token
+    ///
+    /// An empty msg credits the sender's escrow balance. Otherwise msg
+    /// is the account id of a beneficiary whose escrow balance is credited
+    /// instead, which lets a sponsor fund tribute for an applicant. The
+    /// beneficiary must have registered storage with moloch.
+    ///
+    /// The sender pays for any new escrow entry out of their storage balance. When funding a
+    /// beneficiary the sender is refunded once the beneficiary takes the storage over.
+    /// If the sender is not registered or cannot cover the entry the full
+    /// amount is returned.
+    ///
+    /// A msg of "donate" credits the guild bank directly. No shares are
+    /// issued and no storage is needed for a donation.
     fn ft_on_transfer(
         &mut self,
         sender_id: ValidAccountId,
         amount: U128,
         msg: String,
     ) -> PromiseOrValue<U128> {
-        // TODO: Do we need a storage check here
+        let initial_storage_usage = env::storage_usage();
+        self.assert_active();
+        self.assert_not_paused();
         let token_id = env::predecessor_account_id();
-        if token_id == self.token_id {
-            self.escrow.deposit(sender_id.into(), u128::from(amount));
+        if token_id != self.token_id {
+            return PromiseOrValue::Value(amount);
+        };
+        if msg == DONATE_MSG {
+            self.bank.deposit(u128::from(amount));
+            env::log(
+                format!(
+                    "Donation! donor: {}, amount: {}",
+                    sender_id,
+                    u128::from(amount)
+                )
+                .as_bytes(),
+            );
             return PromiseOrValue::Value(U128(0));
         };
-        PromiseOrValue::Value(amount)
+        let sender_storage = match self.user_storage_accounts.get(&sender_id.to_string()) {
+            Some(sender_storage) => sender_storage,
+            None => {
+                env::log(
+                    format!(
+                        "Sender {} has not registered storage, refunding the deposit",
+                        sender_id
+                    )
+                    .as_bytes(),
+                );
+                return PromiseOrValue::Value(amount);
+            }
+        };
+        let beneficiary_id = match msg.is_empty() {
+            true => sender_id.to_string(),
+            false => msg,
+        };
+        if !env::is_valid_account_id(beneficiary_id.as_bytes())
+            || !self.user_storage_accounts.contains_key(&beneficiary_id)
+        {
+            env::log(
+                format!(
+                    "Beneficiary {} has not registered storage, refunding the deposit",
+                    beneficiary_id
+                )
+                .as_bytes(),
+            );
+            return PromiseOrValue::Value(amount);
+        };
+        // A new escrow entry is at most min_account_storage_usage bytes
+        let required_storage = match self.escrow.user_balance(beneficiary_id.to_string()) {
+            0 => Balance::from(self.min_account_storage_usage) * env::storage_byte_cost(),
+            _ => 0,
+        };
+        if sender_storage.available < required_storage {
+            env::log(
+                format!(
+                    "Sender {} has insufficient storage for an escrow entry, refunding the deposit",
+                    sender_id
+                )
+                .as_bytes(),
+            );
+            return PromiseOrValue::Value(amount);
+        };
+        self.escrow
+            .deposit(beneficiary_id.to_string(), u128::from(amount));
+        self.record_statement(
+            &beneficiary_id,
+            StatementKind::Deposit,
+            &token_id,
+            u128::from(amount),
+            None,
+        );
+        self.sponsor_storage(
+            sender_id.to_string(),
+            &beneficiary_id,
+            initial_storage_usage,
+        );
+        env::log(
+            format!(
+                "Escrow deposit! sender: {}, beneficiary: {}, amount: {}",
+                sender_id,
+                beneficiary_id,
+                u128::from(amount)
+            )
+            .as_bytes(),
+        );
+        PromiseOrValue::Value(U128(0))
     }
 }
 
@@ -29,10 +125,18 @@ impl FungibleTokenReceiver for Moloch {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::mocks::{bob, fdai, get_context_builder, robert, storage_deposit, MockMoloch};
     use near_sdk::test_utils::VMContextBuilder;
     use near_sdk::{testing_env, MockedBlockchain};
     use std::convert::TryInto;
 
+    fn returned_amount(promise: PromiseOrValue<U128>) -> u128 {
+        match promise {
+            PromiseOrValue::Promise(_) => 0,
+            PromiseOrValue::Value(t) => t.into(),
+        }
+    }
+
     #[test]
     fn ft_on_transfer() {
         let context = VMContextBuilder::new()
@@ -41,16 +145,23 @@ mod tests {
             .build();
         testing_env!(context);
         let mut contract = Moloch::new(
-            "bob.near".to_string(),
+            vec![Founder::new("bob.near".to_string(), 1.into(), None)],
             "fdau.near".to_string(),
             10.into(),
+            PeriodClock::Timestamp,
             10.into(),
             10.into(),
             10.into(),
             10.into(),
             10.into(),
             10.into(),
+            0.into(),
+            None,
         );
+        contract.token_info = Some(TokenInfo {
+            symbol: "FDAU".to_string(),
+            decimals: 18,
+        });
 
         let promise = contract.ft_on_transfer(
             "bob.near".to_string().try_into().unwrap(),
@@ -64,4 +175,174 @@ mod tests {
 
         assert_eq!(returned_amount, 10, "Returned amount is incorrect");
     }
+
+    #[test]
+    fn ft_on_transfer_sender() {
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder.build());
+        let mut contract = MockMoloch::new()
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .build();
+        testing_env!(context_builder
+            .predecessor_account_id(fdai().try_into().unwrap())
+            .build());
+
+        let promise = contract.ft_on_transfer(bob().try_into().unwrap(), 10.into(), "".to_string());
+
+        assert_eq!(returned_amount(promise), 0, "Returned amount is incorrect");
+        assert_eq!(
+            contract.escrow.user_balance(bob()),
+            10,
+            "Sender escrow balance is incorrect"
+        );
+    }
+
+    #[test]
+    fn ft_on_transfer_beneficiary() {
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder.build());
+        let mut contract = MockMoloch::new()
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .register_user(robert(), storage_deposit(), storage_deposit())
+            .build();
+        testing_env!(context_builder
+            .predecessor_account_id(fdai().try_into().unwrap())
+            .build());
+
+        let promise = contract.ft_on_transfer(bob().try_into().unwrap(), 10.into(), robert());
+
+        assert_eq!(returned_amount(promise), 0, "Returned amount is incorrect");
+        assert_eq!(
+            contract.escrow.user_balance(robert()),
+            10,
+            "Beneficiary escrow balance is incorrect"
+        );
+        assert_eq!(
+            contract.escrow.user_balance(bob()),
+            0,
+            "Sender escrow balance is incorrect"
+        );
+    }
+
+    #[test]
+    fn ft_on_transfer_beneficiary_not_registered() {
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder.build());
+        let mut contract = MockMoloch::new()
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .build();
+        testing_env!(context_builder
+            .predecessor_account_id(fdai().try_into().unwrap())
+            .build());
+
+        let promise = contract.ft_on_transfer(bob().try_into().unwrap(), 10.into(), robert());
+
+        assert_eq!(returned_amount(promise), 10, "Returned amount is incorrect");
+        assert_eq!(
+            contract.escrow.user_balance(robert()),
+            0,
+            "Beneficiary escrow balance is incorrect"
+        );
+    }
+
+    #[test]
+    fn ft_on_transfer_charges_sender_storage() {
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder.build());
+        let mut contract = MockMoloch::new()
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .register_user(robert(), storage_deposit(), storage_deposit())
+            .build();
+        testing_env!(context_builder
+            .predecessor_account_id(fdai().try_into().unwrap())
+            .build());
+
+        contract.ft_on_transfer(bob().try_into().unwrap(), 10.into(), robert());
+
+        let bob_storage = contract.user_storage_accounts.get(&bob()).unwrap();
+        assert!(
+            bob_storage.available < storage_deposit(),
+            "Sender was not charged for the escrow entry"
+        );
+        let robert_storage = contract.user_storage_accounts.get(&robert()).unwrap();
+        assert_eq!(
+            robert_storage.available,
+            storage_deposit(),
+            "Beneficiary was charged for the escrow entry"
+        );
+    }
+
+    #[test]
+    fn ft_on_transfer_sender_not_registered() {
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder.build());
+        let mut contract = MockMoloch::new()
+            .register_user(robert(), storage_deposit(), storage_deposit())
+            .build();
+        testing_env!(context_builder
+            .predecessor_account_id(fdai().try_into().unwrap())
+            .build());
+
+        let promise = contract.ft_on_transfer(bob().try_into().unwrap(), 10.into(), robert());
+
+        assert_eq!(returned_amount(promise), 10, "Returned amount is incorrect");
+        assert_eq!(
+            contract.escrow.user_balance(robert()),
+            0,
+            "Beneficiary escrow balance is incorrect"
+        );
+    }
+
+    #[test]
+    fn ft_on_transfer_sender_insufficient_storage() {
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder.build());
+        let mut contract = MockMoloch::new()
+            .register_user(bob(), storage_deposit(), 0)
+            .build();
+        testing_env!(context_builder
+            .predecessor_account_id(fdai().try_into().unwrap())
+            .build());
+
+        let promise = contract.ft_on_transfer(bob().try_into().unwrap(), 10.into(), "".to_string());
+
+        assert_eq!(returned_amount(promise), 10, "Returned amount is incorrect");
+        assert_eq!(
+            contract.escrow.user_balance(bob()),
+            0,
+            "Sender escrow balance is incorrect"
+        );
+    }
+
+    #[test]
+    fn ft_on_transfer_donate() {
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder.build());
+        let mut contract = MockMoloch::new().build();
+        testing_env!(context_builder
+            .predecessor_account_id(fdai().try_into().unwrap())
+            .build());
+
+        let promise = contract.ft_on_transfer(
+            robert().try_into().unwrap(),
+            10.into(),
+            "donate".to_string(),
+        );
+
+        assert_eq!(returned_amount(promise), 0, "Returned amount is incorrect");
+        assert_eq!(
+            contract.bank.get_balance(),
+            10,
+            "Donation was not credited to the guild bank"
+        );
+        assert_eq!(
+            contract.escrow.user_balance(robert()),
+            0,
+            "Donor escrow balance is incorrect"
+        );
+        assert_eq!(
+            contract.total_shares, 1,
+            "Shares were issued for a donation"
+        );
+    }
 }
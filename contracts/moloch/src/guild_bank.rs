@@ -1,16 +1,27 @@
 extern crate near_sdk;
 
-use near_contract_standards::fungible_token::core_impl::ext_fungible_token;
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::json_types::U128;
-use near_sdk::{env, near_bindgen, AccountId, PanicOnDefault, Promise};
+use near_sdk::collections::{LookupMap, Vector};
+use near_sdk::{env, near_bindgen, AccountId, PanicOnDefault};
+
+use std::cmp::min;
+
+use crate::Nft;
 
 // Guild bank
 #[near_bindgen]
-#[derive(BorshDeserialize, BorshSerialize, PanicOnDefault, Debug)]
+#[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
 pub struct GuildBank {
     token_id: AccountId,
     balance: u128,
+    /// Native NEAR held by the guild, this never includes storage deposits
+    near_balance: u128,
+    /// NFTs accepted as tribute, these can't be split so rage quit grants claims on them
+    nfts: Vector<Nft>,
+    /// Number of vault NFTs each former member can claim
+    nft_claims: LookupMap<AccountId, u64>,
+    /// Vault NFTs reserved by unclaimed claims
+    total_nft_claims: u64,
 }
 
 impl GuildBank {
@@ -18,30 +29,33 @@ impl GuildBank {
         Self {
             token_id: approved_token,
             balance: 0,
+            near_balance: 0,
+            nfts: Vector::new(b"guild_bank_nfts".to_vec()),
+            nft_claims: LookupMap::new(b"guild_bank_nft_claims".to_vec()),
+            total_nft_claims: 0,
         }
     }
 
-    pub fn withdraw(&mut self, receiver: AccountId, shares: u128, total_shares: u128) -> Promise {
-        let amount = match self
-            .balance
-            .saturating_mul(shares)
-            .checked_div(total_shares)
-        {
-            Some(amount) => amount,
-            None => panic!("Total shares is 0 a withdrawl cannot be calculated"),
-        };
+    /// Removes the receiver's proportional share of the approved token and of
+    /// the guild's NEAR, returning the (token, NEAR) amounts owed to them
+    pub fn withdraw(
+        &mut self,
+        receiver: AccountId,
+        shares: u128,
+        total_shares: u128,
+    ) -> (u128, u128) {
+        let amount = proportional_amount(self.balance, shares, total_shares);
+        let near_amount = proportional_amount(self.near_balance, shares, total_shares);
         self.balance -= amount;
-        env::log(format!("Withdraw: receiver: {}, amount: {}", receiver, amount).as_bytes());
-        let prepaid_gas = env::prepaid_gas();
-        ext_fungible_token::ft_transfer(
-            receiver,
-            U128::from(amount),
-            Some("Withdraw from guild bank".to_string()),
-            &self.token_id,
-            1,
-            // Change to a fixed gas amount
-            prepaid_gas / 2,
-        )
+        self.near_balance -= near_amount;
+        env::log(
+            format!(
+                "Withdraw: receiver: {}, amount: {}, near_amount: {}",
+                receiver, amount, near_amount
+            )
+            .as_bytes(),
+        );
+        (amount, near_amount)
     }
 
     pub fn deposit(&mut self, amount: u128) -> u128 {
@@ -52,6 +66,85 @@ impl GuildBank {
     pub fn get_balance(&self) -> u128 {
         self.balance
     }
+
+    pub fn deposit_near(&mut self, amount: u128) -> u128 {
+        self.near_balance += amount;
+        return self.near_balance;
+    }
+
+    pub fn get_near_balance(&self) -> u128 {
+        self.near_balance
+    }
+
+    pub fn deposit_nft(&mut self, nft: Nft) {
+        self.nfts.push(&nft);
+    }
+
+    /// Returns limit vault NFTs starting at from_index
+    pub fn get_nfts(&self, from_index: u64, limit: u64) -> Vec<Nft> {
+        let to_index = min(from_index.saturating_add(limit), self.nfts.len());
+        (from_index..to_index)
+            .map(|index| self.nfts.get(index).unwrap())
+            .collect()
+    }
+
+    pub fn nft_count(&self) -> u64 {
+        self.nfts.len()
+    }
+
+    /// Grants the receiver claims on their proportional share of the vault NFTs not already
+    /// claimed, rounded down, and returns the number of claims granted
+    pub fn grant_nft_claims(
+        &mut self,
+        receiver: AccountId,
+        shares: u128,
+        total_shares: u128,
+    ) -> u64 {
+        let unclaimed = self.nfts.len().saturating_sub(self.total_nft_claims);
+        let claims = proportional_amount(unclaimed as u128, shares, total_shares) as u64;
+        if claims > 0 {
+            self.nft_claims
+                .insert(&receiver, &(self.nft_claims(&receiver) + claims));
+            self.total_nft_claims += claims;
+        };
+        claims
+    }
+
+    pub fn nft_claims(&self, account_id: &AccountId) -> u64 {
+        match self.nft_claims.get(account_id) {
+            Some(claims) => claims,
+            None => 0,
+        }
+    }
+
+    /// Uses one of the account's claims to take the vault NFT at index
+    pub fn claim_nft(&mut self, account_id: &AccountId, index: u64) -> Nft {
+        let claims = self.nft_claims(account_id);
+        assert!(claims > 0, "{} has no NFT claims", account_id);
+        assert!(index < self.nfts.len(), "NFT does not exist");
+        if claims == 1 {
+            self.nft_claims.remove(account_id);
+        } else {
+            self.nft_claims.insert(account_id, &(claims - 1));
+        };
+        self.total_nft_claims -= 1;
+        self.nfts.swap_remove(index)
+    }
+
+    /// Puts back an NFT whose claim_nft transfer failed, together with the claim it used
+    pub fn return_nft_claim(&mut self, account_id: &AccountId, nft: Nft) {
+        self.nfts.push(&nft);
+        self.nft_claims
+            .insert(account_id, &(self.nft_claims(account_id) + 1));
+        self.total_nft_claims += 1;
+    }
+}
+
+fn proportional_amount(balance: u128, shares: u128, total_shares: u128) -> u128 {
+    match balance.saturating_mul(shares).checked_div(total_shares) {
+        Some(amount) => amount,
+        None => panic!("Total shares is 0 a withdrawl cannot be calculated"),
+    }
 }
 
 #[cfg(not(target_arch = "wasm32"))]
@@ -84,7 +177,9 @@ mod tests {
         testing_env!(context);
         let mut contract = GuildBank::new(fdai());
         contract.balance = 77;
-        contract.withdraw(robert(), 10, 100);
+        let (amount, near_amount) = contract.withdraw(robert(), 10, 100);
+        assert_eq!(amount, 7, "Withdrawn amount is incorrect");
+        assert_eq!(near_amount, 0, "Withdrawn NEAR amount is incorrect");
         assert_eq!(
             contract.balance, 70,
             "Balance did not have the correct amount withdrawn"
@@ -100,4 +195,82 @@ mod tests {
         contract.balance = 77;
         contract.withdraw(robert(), 10, 0);
     }
+
+    #[test]
+    fn withdraw_near() {
+        let context = get_context(false);
+        testing_env!(context);
+        let mut contract = GuildBank::new(fdai());
+        contract.balance = 77;
+        contract.deposit_near(200);
+        contract.withdraw(robert(), 10, 100);
+        assert_eq!(
+            contract.balance, 70,
+            "Balance did not have the correct amount withdrawn"
+        );
+        assert_eq!(
+            contract.near_balance, 180,
+            "NEAR balance did not have the correct amount withdrawn"
+        )
+    }
+
+    fn nft(token_id: &str) -> Nft {
+        Nft::new("nft.near".to_string(), token_id.to_string())
+    }
+
+    #[test]
+    fn get_nfts() {
+        let context = get_context(false);
+        testing_env!(context);
+        let mut contract = GuildBank::new(fdai());
+        for token_id in ["1", "2", "3"].iter() {
+            contract.deposit_nft(nft(token_id));
+        }
+        assert_eq!(contract.get_nfts(1, 5), vec![nft("2"), nft("3")]);
+        assert_eq!(contract.get_nfts(0, 1), vec![nft("1")]);
+        assert_eq!(contract.get_nfts(3, 1), vec![]);
+    }
+
+    #[test]
+    fn grant_nft_claims() {
+        let context = get_context(false);
+        testing_env!(context);
+        let mut contract = GuildBank::new(fdai());
+        for token_id in ["1", "2", "3", "4"].iter() {
+            contract.deposit_nft(nft(token_id));
+        }
+        // Half of 4 unclaimed NFTs
+        assert_eq!(contract.grant_nft_claims(robert(), 5, 10), 2);
+        // A third of the 2 left is rounded down
+        assert_eq!(contract.grant_nft_claims(fdai(), 1, 3), 0);
+        // The last shares take what is left
+        assert_eq!(contract.grant_nft_claims(fdai(), 2, 2), 2);
+        assert_eq!(contract.nft_claims(&robert()), 2);
+        assert_eq!(contract.total_nft_claims, 4);
+    }
+
+    #[test]
+    fn claim_nft() {
+        let context = get_context(false);
+        testing_env!(context);
+        let mut contract = GuildBank::new(fdai());
+        contract.deposit_nft(nft("1"));
+        contract.deposit_nft(nft("2"));
+        contract.grant_nft_claims(robert(), 1, 1);
+        assert_eq!(contract.claim_nft(&robert(), 1), nft("2"));
+        assert_eq!(contract.claim_nft(&robert(), 0), nft("1"));
+        assert_eq!(contract.nft_claims(&robert()), 0);
+        assert_eq!(contract.total_nft_claims, 0);
+        assert_eq!(contract.nft_count(), 0, "Vault was not emptied");
+    }
+
+    #[test]
+    #[should_panic(expected = r#"robert.testnet has no NFT claims"#)]
+    fn claim_nft_without_claims() {
+        let context = get_context(false);
+        testing_env!(context);
+        let mut contract = GuildBank::new(fdai());
+        contract.deposit_nft(nft("1"));
+        contract.claim_nft(&robert(), 0);
+    }
 }
@@ -3,39 +3,88 @@ extern crate near_sdk;
 extern crate serde;
 
 use near_contract_standards::fungible_token::core_impl::ext_fungible_token;
+use near_contract_standards::fungible_token::metadata::{FungibleTokenMetadata, FT_METADATA_SPEC};
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
 use near_sdk::collections::{LookupMap, UnorderedMap, Vector};
-use near_sdk::json_types::{U128, U64};
-use near_sdk::{env, near_bindgen, setup_alloc, AccountId, Balance, PanicOnDefault, Promise};
+use near_sdk::json_types::{Base64VecU8, U128, U64};
+use near_sdk::{
+    assert_one_yocto, env, ext_contract, near_bindgen, setup_alloc, AccountId, Balance,
+    PanicOnDefault, Promise, PromiseResult,
+};
 
 use serde::{Deserialize, Serialize};
 
-use std::cmp::max;
-use std::collections::HashMap;
+use std::cmp::{max, min};
+use std::collections::{HashMap, HashSet};
 
+mod account_statement;
 mod ft_callbacks;
 mod guild_bank;
+mod nft_callbacks;
 mod proposal_escrow;
 mod storage_impl;
+mod token_ledger;
 
 const MAX_VOTING_PERIOD_LENGTH: u64 = 10_000_000_000_000_000_000; // maximum length of voting period;
 const MAX_GRACE_PERIOD_LENGTH: u64 = 10_000_000_000_000_000_000; // maximum length of grace period
 const MAX_DILUTION_BOUND: u128 = 10_000_000_000_000_000_000; // maximum dilution bound
 const MAX_NUMBER_OF_SHARES: u128 = 10_000_000_000_000_000_000; // maximum dilution bound
+const MAX_SCOPED_KEYS: usize = 10; // maximum scoped delegate keys per member
+const MAX_PROFILE_NAME_LENGTH: usize = 64; // maximum length of a profile display name
+const MAX_PROFILE_URL_LENGTH: usize = 256; // maximum length of a profile avatar url or link
+const MAX_PROFILE_LINKS: usize = 5; // maximum number of profile links
+const SURPLUS_SWEEP_DELAY: u64 = 1; // periods between recording a surplus and sweeping it
+/// Token id used for native NEAR in the token ledger. Account ids are lowercase, so this can
+/// never collide with the approved token or any other NEP-141 contract.
+const NEAR_TOKEN_ID: &str = "NEAR";
+/// Symbol shares are shown with in NEP-148 metadata
+const SHARES_SYMBOL: &str = "SHARES";
 
 setup_alloc!();
 
+#[ext_contract(ext_self)]
+pub trait MolochCallbacks {
+    fn on_withdraw_balance(
+        &mut self,
+        account_id: AccountId,
+        token_id: AccountId,
+        amount: U128,
+        reserved_bytes: U64,
+    );
+    fn on_ft_metadata(&mut self) -> bool;
+    fn on_reconcile(&mut self, sweep: bool, withdrawal_nonce: U64) -> Reconciliation;
+    fn on_upgrade_code(&mut self, factory_id: AccountId, code_version: U64) -> bool;
+    fn on_escrow_withdraw_nft(&mut self, account_id: AccountId, nft: Nft, freed_bytes: U64)
+        -> bool;
+    fn on_claim_nft(&mut self, account_id: AccountId, nft: Nft, freed_bytes: U64) -> bool;
+}
+
+#[ext_contract(ext_ft_metadata)]
+pub trait FungibleTokenMetadataProvider {
+    fn ft_metadata(&self) -> FungibleTokenMetadata;
+}
+
+#[ext_contract(ext_factory)]
+pub trait MolochFactory {
+    fn get_code(&self, code_version: U64) -> Base64VecU8;
+    fn update_dao_code_version(&mut self, code_version: U64);
+}
+
 #[derive(BorshDeserialize, BorshSerialize, Default, PartialEq, Debug, Serialize, Deserialize)]
 pub struct UserStorageBalance {
     total: u128,
     available: u128,
+    /// Bytes the account has paid for out of total, refunds never exceed these
+    used_bytes: u64,
 }
 
 #[near_bindgen]
 #[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
 pub struct Moloch {
-    /// The length of period in non_leap nanoseconds
+    /// The length of period in non_leap nanoseconds, or in blocks with the block height clock
     period_duration: u64,
+    /// What periods are measured in
+    period_clock: PeriodClock,
     /// The number of periods in to vote on a proposal
     voting_period_length: u64,
     /// The number of periods until a proposal is processed
@@ -48,10 +97,17 @@ pub struct Moloch {
     dilution_bound: u128,
     /// Amount to give to whoever processes a proposal
     processing_reward: u128,
+    /// Amount of an aborted proposal's deposit the proposer forfeits to the guild bank
+    abort_penalty: u128,
     /// time used to determine the current period
     summoning_time: u64,
+    /// block height used to determine the current period with the block height clock
+    summoning_block_height: u64,
     /// Approved token to use payment
     token_id: AccountId,
+    /// Symbol and decimals of the approved token, None until ft_metadata confirms the token.
+    /// The DAO is inactive until then.
+    token_info: Option<TokenInfo>,
     /// Members in the DAO
     members: UnorderedMap<AccountId, Member>,
     /// Members of the DAO related to their delegate key
@@ -60,16 +116,92 @@ pub struct Moloch {
     user_storage_accounts: LookupMap<AccountId, UserStorageBalance>,
     /// The mininum amount of storgage needed to interact with this contract
     min_account_storage_usage: u64,
+    /// NEAR that pays for processing proposals when neither proposer nor applicant can
+    storage_pool: u128,
+    /// What each account has put into the storage pool and can still take out of it
+    storage_pool_funders: LookupMap<AccountId, u128>,
     /// Total shares across all members
     total_shares: u128,
     /// A data structure that handles the guild balance and member withdraws
     bank: guild_bank::GuildBank,
     /// An escrow to hold fungible token deposits for proposals
     escrow: proposal_escrow::ProposalEscrow,
+    /// Balances accounts can withdraw with withdraw_balance
+    ledger: token_ledger::TokenLedger,
+    /// Recent escrow and withdrawable balance movements of registered accounts
+    statements: account_statement::AccountStatements,
+    /// Approved token debited by withdraw_balance whose transfer has not resolved yet
+    pending_withdrawals: u128,
+    /// Number of approved token withdrawals started, tells a reconciliation if one started
+    /// while it was in flight
+    withdrawal_nonce: u64,
+    /// Surplus a reconciliation found and the period it was found in, swept by a later one
+    recorded_surplus: Option<(u128, u64)>,
     /// Total shares that have been requested in unprocessed proposals
     total_shares_requested: u128,
     /// Array of proposals in the order they were submitted
     proposal_queue: Vector<Proposal>,
+    /// Proposals waiting for the applicant to pledge their tribute, they join the proposal_queue
+    /// with pledge_tribute
+    pending_proposals: LookupMap<u64, Proposal>,
+    /// Index the next pending proposal gets
+    pending_proposal_count: u64,
+    /// Who paid for each queued proposal's storage, refunded when processing frees it
+    proposal_storage_receipts: LookupMap<u64, Vec<StorageReceipt>>,
+    /// Who paid for each pending proposal's storage
+    pending_storage_receipts: LookupMap<u64, Vec<StorageReceipt>>,
+    /// Who paid for the storage of an account's escrow, ledger and statement entries when
+    /// someone else did, refunded when the account takes the storage over or frees it
+    sponsor_storage_receipts: LookupMap<AccountId, Vec<StorageReceipt>>,
+    /// Tribute applicants allow a proposer to pledge for them, keyed by (applicant, proposer)
+    tribute_authorizations: LookupMap<(AccountId, AccountId), TributeAuthorization>,
+    /// Account allowed to pause and unpause the DAO, replaceable by a guardian proposal
+    guardian: Option<AccountId>,
+    /// While paused only rage_quit and withdrawals are open
+    paused: bool,
+    /// The period the current pause started in
+    paused_at_period: u64,
+}
+
+/// Where a proposal stands, returned by get_proposal_status for contracts acting on outcomes
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct ProposalStatus {
+    proposer: AccountId,
+    processed: bool,
+    did_pass: bool,
+    aborted: bool,
+    /// The period voting on the proposal starts in, 0 while it waits for its tribute
+    starting_period: U64,
+    /// The DAO's current period, to compare starting_period against
+    current_period: U64,
+    details: String,
+}
+
+/// The approved token balance moloch holds against what it owes, returned by on_reconcile
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct Reconciliation {
+    /// Balance of moloch according to the approved token contract
+    balance: U128,
+    /// Held by the guild bank
+    guild_bank: U128,
+    /// Held in escrow for accounts
+    escrow: U128,
+    /// Withdrawable with withdraw_balance
+    withdrawable: U128,
+    /// Locked in escrow for unprocessed proposals
+    locked: U128,
+    /// Withdrawals whose transfer had not resolved
+    pending_withdrawals: U128,
+    /// Balance beyond what is owed
+    surplus: U128,
+    /// What is owed beyond the balance
+    deficit: U128,
+    /// Surplus waiting to be swept
+    recorded_surplus: U128,
+    /// First period the recorded surplus can be swept in
+    sweepable_from_period: Option<U64>,
+    /// Surplus moved into the guild bank
+    swept: U128,
 }
 
 #[derive(BorshDeserialize, BorshSerialize, Default, PartialEq)]
@@ -82,6 +214,98 @@ pub struct Member {
     exists: bool,
     /// Highest proposal index number on which the member voted yes
     highest_index_yes_vote: u64,
+    /// The member this member has delegated their voting weight to
+    vote_delegatee: Option<AccountId>,
+    /// Members who have delegated their voting weight to this member
+    vote_delegators: Vec<AccountId>,
+    /// The member's shares at the end of each period in which they changed, oldest first
+    share_checkpoints: Vec<ShareCheckpoint>,
+    /// Additional delegate keys limited to voting or proposing
+    scoped_keys: Vec<ScopedKey>,
+    /// Name, avatar and links the member shows to others
+    profile: Option<MemberProfile>,
+}
+
+/// Profile metadata a member sets for DAO interfaces
+#[derive(BorshDeserialize, BorshSerialize, PartialEq, Debug, Serialize, Deserialize, Clone)]
+pub struct MemberProfile {
+    display_name: String,
+    avatar_url: Option<String>,
+    links: Vec<String>,
+}
+
+/// A member as returned by get_member
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct MemberInfo {
+    delegate_key: AccountId,
+    shares: U128,
+    highest_index_yes_vote: U64,
+    profile: Option<MemberProfile>,
+}
+
+/// What a delegate key can do for its member
+#[derive(
+    BorshDeserialize, BorshSerialize, PartialEq, Debug, Serialize, Deserialize, Clone, Copy,
+)]
+pub enum KeyScope {
+    Vote,
+    Propose,
+    Both,
+}
+
+impl KeyScope {
+    fn allows(&self, scope: KeyScope) -> bool {
+        *self == KeyScope::Both || *self == scope
+    }
+}
+
+/// A delegate key in addition to the member's delegate_key
+#[derive(BorshDeserialize, BorshSerialize, PartialEq, Debug, Serialize, Deserialize, Clone)]
+pub struct ScopedKey {
+    account_id: AccountId,
+    scope: KeyScope,
+    /// The key can't be used from this period on
+    expires_at_period: Option<u64>,
+}
+
+impl Member {
+    /// Records the member's current shares for the period
+    fn checkpoint_shares(&mut self, period: u64) {
+        if let Some(checkpoint) = self.share_checkpoints.last_mut() {
+            if checkpoint.period == period {
+                checkpoint.shares = self.shares;
+                return;
+            };
+        };
+        self.share_checkpoints.push(ShareCheckpoint {
+            period: period,
+            shares: self.shares,
+        });
+    }
+
+    /// The voting weight of the member on a proposal starting in starting_period. These are the
+    /// shares the member held at the end of the previous period, so shares gained while a
+    /// proposal is in voting don't count towards it. Shares burned since then don't count either.
+    fn shares_at(&self, starting_period: u64) -> u128 {
+        let snapshot = match self
+            .share_checkpoints
+            .iter()
+            .rev()
+            .find(|checkpoint| checkpoint.period < starting_period)
+        {
+            Some(checkpoint) => checkpoint.shares,
+            None => 0,
+        };
+        min(snapshot, self.shares)
+    }
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Default, PartialEq, Debug)]
+pub struct ShareCheckpoint {
+    /// The period the shares changed in
+    period: u64,
+    /// The member's shares at the end of the period
+    shares: u128,
 }
 
 #[derive(BorshDeserialize, BorshSerialize, Default, PartialEq, Debug, Serialize, Deserialize)]
@@ -106,12 +330,203 @@ pub struct Proposal {
     aborted: bool,
     /// Amount of tokens offered as tribute
     token_tribute: u128,
+    /// Amount of NEAR offered as tribute
+    near_tribute: u128,
+    /// NFTs offered as tribute
+    nft_tribute: Vec<Nft>,
     /// The proposal details - could be an IPFS hash, plaintext, or JSON
     details: String,
     /// The maximum number of total shares encountered at a yes vote on this proposal
     max_total_shares_at_yes_vote: u128,
     /// Mapping of votes for each member
     votes_by_member: HashMap<AccountId, Vote>,
+    /// What the proposal does when it passes
+    kind: ProposalKind,
+    /// true once the applicant has consented and the tribute is held by the proposal
+    tribute_pledged: bool,
+}
+
+/// Storage bytes an account paid for a proposal, or for another account's entries
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct StorageReceipt {
+    payer: AccountId,
+    bytes: u64,
+}
+
+/// Tribute an applicant allows a proposer to pledge from their escrow
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct TributeAuthorization {
+    token_tribute: u128,
+    near_tribute: u128,
+}
+
+/// The action a proposal takes when it passes
+#[derive(BorshDeserialize, BorshSerialize, PartialEq, Debug, Serialize, Deserialize, Clone)]
+pub enum ProposalKind {
+    /// Grants the applicant shares in exchange for tribute
+    Membership,
+    /// Replaces the guardian, None removes it
+    UpdateGuardian(Option<AccountId>),
+    /// Burns all of a member's shares and credits them their share of the guild bank, as if
+    /// they had rage quit
+    GuildKick(AccountId),
+    /// Deploys a code version stored in a factory to the DAO. The new code has to read the
+    /// DAO's current state, there is no migration step.
+    Upgrade(AccountId, u64),
+}
+
+impl Default for ProposalKind {
+    fn default() -> Self {
+        ProposalKind::Membership
+    }
+}
+
+/// Why escrowed funds are locked for a proposal
+#[derive(
+    BorshDeserialize, BorshSerialize, PartialEq, Debug, Serialize, Deserialize, Clone, Copy,
+)]
+pub enum LockPurpose {
+    /// The proposer's proposal deposit
+    Deposit,
+    /// The proposer's proposal deposit while the proposal waits for the applicant's pledge,
+    /// keyed by the pending index
+    PendingDeposit,
+    /// The applicant's pledged tribute
+    Tribute,
+}
+
+/// Escrowed funds an account can't use until the proposal they are locked for releases them
+#[derive(BorshDeserialize, BorshSerialize, PartialEq, Debug, Clone)]
+pub struct EscrowLock {
+    /// The proposal the funds are locked for
+    proposal_index: u64,
+    /// The account the funds are refunded to
+    owner: AccountId,
+    /// Amount of the approved token
+    amount: u128,
+    /// Amount of native NEAR
+    near_amount: u128,
+    purpose: LockPurpose,
+}
+
+/// An escrow lock as returned by get_escrow_locks
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct EscrowLockInfo {
+    proposal_index: U64,
+    owner: AccountId,
+    amount: U128,
+    near_amount: U128,
+    purpose: LockPurpose,
+}
+
+impl From<EscrowLock> for EscrowLockInfo {
+    fn from(lock: EscrowLock) -> Self {
+        EscrowLockInfo {
+            proposal_index: lock.proposal_index.into(),
+            owner: lock.owner,
+            amount: lock.amount.into(),
+            near_amount: lock.near_amount.into(),
+            purpose: lock.purpose,
+        }
+    }
+}
+
+/// What moved an account's escrow or withdrawable balance
+#[derive(
+    BorshDeserialize, BorshSerialize, PartialEq, Debug, Serialize, Deserialize, Clone, Copy,
+)]
+pub enum StatementKind {
+    /// Tokens or NEAR deposited into escrow
+    Deposit,
+    /// Escrow locked as a proposal deposit
+    DepositLock,
+    /// Escrow locked as proposal tribute
+    TributeLock,
+    /// A lock returned to escrow, or a failed withdrawal returned to the withdrawable balance
+    Refund,
+    /// Locked tribute paid into the guild bank when the proposal passed
+    Tribute,
+    /// Withdrawable reward for processing a proposal
+    ProcessingReward,
+    /// Withdrawable share of the guild bank from a rage quit
+    RageQuit,
+    /// Withdrawable share of the guild bank from a guild kick
+    GuildKick,
+    /// Escrow moved to the withdrawable balance
+    EscrowWithdrawal,
+    /// Withdrawable balance paid out of moloch
+    Withdrawal,
+}
+
+/// An entry in an account's statement
+#[derive(BorshDeserialize, BorshSerialize, PartialEq, Debug, Serialize, Deserialize, Clone)]
+pub struct StatementEntry {
+    kind: StatementKind,
+    /// The approved token or "NEAR"
+    token_id: AccountId,
+    amount: U128,
+    /// The proposal behind the movement, if any
+    proposal_index: Option<U64>,
+    /// Block timestamp of the movement
+    timestamp: U64,
+}
+
+/// A NEP-171 token held by moloch
+#[derive(BorshDeserialize, BorshSerialize, PartialEq, Debug, Serialize, Deserialize, Clone)]
+pub struct Nft {
+    /// The NFT contract the token belongs to
+    contract_id: AccountId,
+    /// The token id on the NFT contract
+    token_id: String,
+}
+
+impl Nft {
+    pub fn new(contract_id: AccountId, token_id: String) -> Self {
+        Self {
+            contract_id: contract_id,
+            token_id: token_id,
+        }
+    }
+}
+
+/// Display details of the approved token, taken from its NEP-148 metadata
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct TokenInfo {
+    symbol: String,
+    decimals: u8,
+}
+
+/// What the DAO measures periods in
+#[derive(
+    BorshDeserialize, BorshSerialize, Serialize, Deserialize, PartialEq, Debug, Clone, Copy,
+)]
+pub enum PeriodClock {
+    /// Periods of period_duration nanoseconds since the summoning time
+    Timestamp,
+    /// Periods of period_duration blocks since the summoning block, immune to timestamp
+    /// manipulation
+    BlockHeight,
+}
+
+/// A founding member created at summon
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Founder {
+    /// The founder's account, used for rage quits and withdrawals
+    account_id: AccountId,
+    /// The shares the founder starts with
+    shares: U128,
+    /// The key the founder votes and submits proposals with, defaults to account_id
+    delegate_key: Option<AccountId>,
+}
+
+impl Founder {
+    pub fn new(account_id: AccountId, shares: U128, delegate_key: Option<AccountId>) -> Self {
+        Self {
+            account_id: account_id,
+            shares: shares,
+            delegate_key: delegate_key,
+        }
+    }
 }
 
 // Needs to be changed to an AccountId
@@ -138,19 +553,38 @@ impl Vote {
     }
 }
 
+/// Logs a NEP-297 ft_mint or ft_burn event so indexers can follow share balances
+fn log_share_event(event: &str, owner_id: &str, amount: u128, memo: &str) {
+    if amount == 0 {
+        return;
+    };
+    env::log(
+        format!(
+            r#"EVENT_JSON:{{"standard":"nep141","version":"1.0.0","event":"{}","data":[{{"owner_id":"{}","amount":"{}","memo":"{}"}}]}}"#,
+            event, owner_id, amount, memo
+        )
+        .as_bytes(),
+    );
+}
+
 #[near_bindgen]
 impl Moloch {
+    /// Summons the DAO with its founding members. Each founder starts with their shares and votes
+    /// with their delegate key, or with their account when no key is given.
     #[init]
     pub fn new(
-        summoner: AccountId,
+        founders: Vec<Founder>,
         approved_token: AccountId,
         period_duration: U64,
+        period_clock: PeriodClock,
         voting_period_length: U64,
         grace_period_length: U64,
         abort_window: U64,
         proposal_deposit: U128,
         dilution_bound: U128,
         processing_reward: U128,
+        abort_penalty: U128,
+        guardian: Option<AccountId>,
     ) -> Self {
         let _period_duration = u64::from(period_duration);
         let _voting_period_length = u64::from(voting_period_length);
@@ -158,13 +592,11 @@ impl Moloch {
         let _proposal_deposit = u128::from(proposal_deposit);
         let _dilution_bound = u128::from(dilution_bound);
         let _processing_reward = u128::from(processing_reward);
+        let _abort_penalty = u128::from(abort_penalty);
         let _abort_window = u64::from(abort_window);
 
-        assert!(
-            env::is_valid_account_id(summoner.as_bytes()),
-            "Summoner must be a valid account"
-        );
-        // TODO: Make sure token is valid FungibleToken
+        assert!(!founders.is_empty(), "Moloch needs at least one founder");
+        // The token is confirmed by on_ft_metadata, see check_approved_token
         assert!(
             env::is_valid_account_id(approved_token.as_bytes()),
             "Approved token must have a valid address"
@@ -200,64 +632,275 @@ impl Moloch {
             _proposal_deposit >= _processing_reward,
             "proposal_deposit cannot be smaller than processing reward"
         );
+        assert!(
+            _proposal_deposit >= _processing_reward.saturating_add(_abort_penalty),
+            "proposal_deposit cannot be smaller than processing reward plus abort penalty"
+        );
+        if let Some(guardian) = &guardian {
+            assert!(
+                env::is_valid_account_id(guardian.as_bytes()),
+                "Guardian must be a valid account"
+            );
+        };
 
         let bank = guild_bank::GuildBank::new(approved_token.clone());
         let escrow = proposal_escrow::ProposalEscrow::new();
-
+        let ledger = token_ledger::TokenLedger::new(approved_token.clone());
+
+        let founder_ids: HashSet<AccountId> = founders
+            .iter()
+            .map(|founder| founder.account_id.clone())
+            .collect();
+        let mut seen_founders = HashSet::new();
+        let mut delegate_keys = HashSet::new();
         let mut members = UnorderedMap::new(b"members".to_vec());
-        members.insert(
-            &summoner,
-            &Member {
-                delegate_key: summoner.clone(),
-                shares: 1,
+        let mut members_by_delegate_key = UnorderedMap::new(b"members_by_delegate_key".to_vec());
+        let mut total_shares: u128 = 0;
+        for founder in founders.iter() {
+            let shares = u128::from(founder.shares);
+            let delegate_key = match &founder.delegate_key {
+                Some(delegate_key) => delegate_key.clone(),
+                None => founder.account_id.clone(),
+            };
+            assert!(
+                env::is_valid_account_id(founder.account_id.as_bytes()),
+                "Founder {} must be a valid account",
+                founder.account_id
+            );
+            assert!(
+                env::is_valid_account_id(delegate_key.as_bytes()),
+                "Delegate key {} must be a valid account",
+                delegate_key
+            );
+            assert!(
+                seen_founders.insert(founder.account_id.clone()),
+                "Duplicate founder {}",
+                founder.account_id
+            );
+            assert!(
+                delegate_keys.insert(delegate_key.clone()),
+                "Duplicate delegate key {}",
+                delegate_key
+            );
+            assert!(
+                delegate_key == founder.account_id || !founder_ids.contains(&delegate_key),
+                "Delegate key {} belongs to another founder",
+                delegate_key
+            );
+            assert!(
+                shares > 0,
+                "Founder {} must have at least 1 share",
+                founder.account_id
+            );
+            total_shares = total_shares.saturating_add(shares);
+            assert!(
+                total_shares <= MAX_NUMBER_OF_SHARES,
+                "Too many founder shares: greater than max shares"
+            );
+
+            let mut member = Member {
+                delegate_key: delegate_key.clone(),
+                shares: shares,
                 exists: true,
                 highest_index_yes_vote: 0,
-            },
-        );
-
-        let mut members_by_delegate_key = UnorderedMap::new(b"members_by_delegate_key".to_vec());
-        members_by_delegate_key.insert(&summoner, &summoner);
+                vote_delegatee: None,
+                vote_delegators: vec![],
+                share_checkpoints: vec![],
+                scoped_keys: vec![],
+                profile: None,
+            };
+            member.checkpoint_shares(0);
+            members.insert(&founder.account_id, &member);
+            members_by_delegate_key.insert(&delegate_key, &founder.account_id);
+            log_share_event("ft_mint", &founder.account_id, shares, "Summon");
+            env::log(
+                format!(
+                    "Founder added! account: {}, delegate_key: {}, shares: {}",
+                    founder.account_id, delegate_key, shares
+                )
+                .as_bytes(),
+            );
+        }
 
         // log summon
-        env::log(format!("Summon complete by {} with 1 share!", summoner).as_bytes());
+        env::log(
+            format!(
+                "Summon complete with {} founders and {} shares!",
+                founders.len(),
+                total_shares
+            )
+            .as_bytes(),
+        );
 
         let mut this = Self {
             period_duration: _period_duration,
+            period_clock: period_clock,
             voting_period_length: _voting_period_length,
             grace_period_length: _grace_period_length,
             proposal_deposit: _proposal_deposit,
             abort_window: _abort_window,
             dilution_bound: _dilution_bound,
             processing_reward: _processing_reward,
+            abort_penalty: _abort_penalty,
             token_id: approved_token,
+            token_info: None,
             summoning_time: env::block_timestamp(),
+            summoning_block_height: env::block_index(),
             members: members,
             members_by_delegate_key: members_by_delegate_key,
             user_storage_accounts: LookupMap::new(b"user_accounts_storage".to_vec()),
             min_account_storage_usage: 0,
-            total_shares: 1,
+            storage_pool: 0,
+            storage_pool_funders: LookupMap::new(b"storage_pool_funders".to_vec()),
+            total_shares: total_shares,
             bank: bank,
             escrow: escrow,
+            ledger: ledger,
+            statements: account_statement::AccountStatements::new(),
+            pending_withdrawals: 0,
+            withdrawal_nonce: 0,
+            recorded_surplus: None,
             total_shares_requested: 0,
             proposal_queue: Vector::new(b"proposal_queue".to_vec()),
+            pending_proposals: LookupMap::new(b"pending_proposals".to_vec()),
+            pending_proposal_count: 0,
+            proposal_storage_receipts: LookupMap::new(b"proposal_storage_receipts".to_vec()),
+            pending_storage_receipts: LookupMap::new(b"pending_storage_receipts".to_vec()),
+            sponsor_storage_receipts: LookupMap::new(b"sponsor_storage_receipts".to_vec()),
+            tribute_authorizations: LookupMap::new(b"tribute_authorizations".to_vec()),
+            guardian: guardian,
+            paused: false,
+            paused_at_period: 0,
         };
         this.measure_min_account_storage_usage();
+        this.check_approved_token();
         this
     }
 
+    /// Asks the approved token for its NEP-148 metadata. The DAO becomes active once
+    /// on_ft_metadata receives a valid response. Summoning calls this, and anyone can call it
+    /// again while the DAO is inactive, for example after the summon call ran out of gas.
+    pub fn check_approved_token(&mut self) -> Promise {
+        assert!(
+            self.token_info.is_none(),
+            "Approved token has already been confirmed"
+        );
+        let prepaid_gas = env::prepaid_gas();
+        ext_ft_metadata::ft_metadata(&self.token_id, 0, prepaid_gas / 3).then(
+            ext_self::on_ft_metadata(&env::current_account_id(), 0, prepaid_gas / 3),
+        )
+    }
+
+    /// Activates the DAO when the approved token returned valid NEP-148 metadata. Returns
+    /// whether the DAO is active.
+    #[private]
+    pub fn on_ft_metadata(&mut self) -> bool {
+        let metadata = match env::promise_result(0) {
+            PromiseResult::Successful(result) => {
+                near_sdk::serde_json::from_slice::<FungibleTokenMetadata>(&result).ok()
+            }
+            _ => None,
+        };
+        match metadata {
+            Some(metadata) if metadata.spec == FT_METADATA_SPEC && !metadata.symbol.is_empty() => {
+                env::log(
+                    format!(
+                        "Approved token confirmed! token: {}, symbol: {}, decimals: {}",
+                        self.token_id, metadata.symbol, metadata.decimals
+                    )
+                    .as_bytes(),
+                );
+                self.token_info = Some(TokenInfo {
+                    symbol: metadata.symbol,
+                    decimals: metadata.decimals,
+                });
+                true
+            }
+            _ => {
+                env::log(
+                    format!(
+                        "Approved token {} did not return valid ft_metadata, Moloch stays inactive",
+                        self.token_id
+                    )
+                    .as_bytes(),
+                );
+                false
+            }
+        }
+    }
+
+    /// Deploys the code a passed upgrade proposal fetched from the factory and then records
+    /// the code version in the factory. Returns whether the code was deployed.
+    #[private]
+    pub fn on_upgrade_code(&mut self, factory_id: AccountId, code_version: U64) -> bool {
+        let code = match env::promise_result(0) {
+            PromiseResult::Successful(result) => {
+                near_sdk::serde_json::from_slice::<Base64VecU8>(&result).ok()
+            }
+            _ => None,
+        };
+        match code {
+            Some(code) => {
+                let prepaid_gas = env::prepaid_gas();
+                Promise::new(env::current_account_id())
+                    .deploy_contract(code.into())
+                    .then(ext_factory::update_dao_code_version(
+                        code_version,
+                        &factory_id,
+                        0,
+                        prepaid_gas / 3,
+                    ));
+                env::log(
+                    format!(
+                        "Code deployed! factory: {}, code_version: {}",
+                        factory_id,
+                        u64::from(code_version)
+                    )
+                    .as_bytes(),
+                );
+                true
+            }
+            None => {
+                env::log(
+                    format!(
+                        "Upgrade failed! factory: {}, code_version: {}",
+                        factory_id,
+                        u64::from(code_version)
+                    )
+                    .as_bytes(),
+                );
+                false
+            }
+        }
+    }
+
     /// A function that determines the minimum storage
-    /// needed to accept fungible token transers.
-    // TODO: Determine if this is a suffcient minimum
+    /// needed to register and accept fungible token transers:
+    /// a storage account, an escrow entry and a statement entry
+    /// for the longest possible account id.
     #[private]
     fn measure_min_account_storage_usage(&mut self) {
         let initial_storage_usage = env::storage_usage();
-        // Register in escrow,
         let tmp_account_id = "a".repeat(64);
+        self.user_storage_accounts
+            .insert(&tmp_account_id, &UserStorageBalance::default());
         self.escrow.deposit(tmp_account_id.to_string(), 1u128);
+        self.statements.record(
+            &tmp_account_id,
+            StatementKind::Deposit,
+            tmp_account_id.to_string(),
+            1u128,
+            None,
+        );
         self.min_account_storage_usage = env::storage_usage() - initial_storage_usage;
+        self.statements.clear(&tmp_account_id);
         self.escrow.withdraw(tmp_account_id.to_string(), 1u128);
+        self.user_storage_accounts.remove(&tmp_account_id);
     }
 
+    /// Charges account_id for the bytes an action added, or refunds the bytes it freed. Freed
+    /// bytes the account didn't pay for go back to whoever sponsored its entries. When the
+    /// account acts itself it first takes over the storage sponsors paid for, if it can.
     #[private]
     fn update_available_storage(
         &mut self,
@@ -265,30 +908,251 @@ impl Moloch {
         initial_storage: u64,
         storage_used: u64,
     ) {
+        if account_id == env::predecessor_account_id() {
+            let added_bytes = storage_used.saturating_sub(initial_storage);
+            self.take_over_sponsored_storage(&account_id, added_bytes);
+        };
         let user_storage_opt = self.user_storage_accounts.get(&account_id);
         if user_storage_opt.is_none() {
             env::panic(format!("Account {} has not registered any storage", account_id).as_bytes());
         }
 
         let mut user_storage = user_storage_opt.unwrap();
+        let mut unpaid_bytes = 0;
         if storage_used > initial_storage {
+            let added_bytes = storage_used - initial_storage;
             user_storage.available = user_storage
                 .available
-                .checked_sub(
-                    env::storage_byte_cost() * Balance::from(storage_used - initial_storage),
-                )
+                .checked_sub(env::storage_byte_cost() * Balance::from(added_bytes))
                 .expect(format!("Insufficient deposit to pay for storage {}", account_id).as_str());
+            user_storage.used_bytes += added_bytes;
         } else {
-            let mut additional_storage = user_storage.available
-                + env::storage_byte_cost() * Balance::from(initial_storage - storage_used);
-            if (user_storage.available + additional_storage) > user_storage.total {
-                additional_storage = user_storage.total - user_storage.available;
-            }
-            user_storage.available += additional_storage
+            // Only bytes the account paid for are refunded
+            let freed_bytes = min(initial_storage - storage_used, user_storage.used_bytes);
+            unpaid_bytes = initial_storage - storage_used - freed_bytes;
+            user_storage.used_bytes -= freed_bytes;
+            user_storage.available += env::storage_byte_cost() * Balance::from(freed_bytes);
         };
 
         self.user_storage_accounts
             .insert(&account_id, &user_storage);
+        if unpaid_bytes > 0 {
+            if let Some(mut receipts) = self.sponsor_storage_receipts.get(&account_id) {
+                self.refund_storage_receipts(&mut receipts, unpaid_bytes);
+                if receipts.iter().all(|receipt| receipt.bytes == 0) {
+                    self.sponsor_storage_receipts.remove(&account_id);
+                } else {
+                    // The receipts keep their size, so this doesn't change the storage used
+                    self.sponsor_storage_receipts.insert(&account_id, &receipts);
+                };
+            };
+        };
+    }
+
+    /// Charges sponsor for the storage an action used since initial_storage on account_id's
+    /// entries. When the sponsor is someone else it gets a receipt, so the bytes go back to it
+    /// when account_id takes them over or frees them.
+    fn sponsor_storage(
+        &mut self,
+        sponsor: AccountId,
+        account_id: &AccountId,
+        initial_storage: u64,
+    ) {
+        if sponsor == *account_id {
+            self.update_available_storage(sponsor, initial_storage, env::storage_usage());
+            return;
+        };
+        let mut receipts = self
+            .sponsor_storage_receipts
+            .get(account_id)
+            .unwrap_or_default();
+        let position = match receipts.iter().position(|receipt| receipt.payer == sponsor) {
+            Some(position) => position,
+            None => {
+                receipts.push(StorageReceipt {
+                    payer: sponsor.to_string(),
+                    bytes: 0,
+                });
+                receipts.len() - 1
+            }
+        };
+        self.sponsor_storage_receipts.insert(account_id, &receipts);
+        // Updating the byte count below doesn't change the receipt's size
+        let storage_used = env::storage_usage();
+        if storage_used > initial_storage {
+            receipts[position].bytes += storage_used - initial_storage;
+            self.sponsor_storage_receipts.insert(account_id, &receipts);
+        };
+        self.update_available_storage(sponsor, initial_storage, storage_used);
+    }
+
+    /// Charges account_id for the storage sponsors paid for its entries and refunds the
+    /// sponsors. Nothing changes if account_id can't cover it on top of reserved_bytes.
+    fn take_over_sponsored_storage(&mut self, account_id: &AccountId, reserved_bytes: u64) {
+        let receipts = match self.sponsor_storage_receipts.get(account_id) {
+            Some(receipts) => receipts,
+            None => return,
+        };
+        let mut user_storage = match self.user_storage_accounts.get(account_id) {
+            Some(user_storage) => user_storage,
+            None => return,
+        };
+        let initial_storage_usage = env::storage_usage();
+        self.sponsor_storage_receipts.remove(account_id);
+        // The receipts themselves were paid for by the sponsors and are gone now
+        let receipt_bytes = initial_storage_usage - env::storage_usage();
+        let sponsored_bytes: u64 = receipts.iter().map(|receipt| receipt.bytes).sum();
+        let taken_over_bytes = sponsored_bytes.saturating_sub(receipt_bytes);
+        let cost = env::storage_byte_cost() * Balance::from(taken_over_bytes);
+        let reserved = env::storage_byte_cost() * Balance::from(reserved_bytes);
+        if user_storage.available < cost.saturating_add(reserved) {
+            self.sponsor_storage_receipts.insert(account_id, &receipts);
+            return;
+        };
+        user_storage.available -= cost;
+        user_storage.used_bytes += taken_over_bytes;
+        self.user_storage_accounts.insert(account_id, &user_storage);
+        for receipt in receipts.iter() {
+            if receipt.bytes > 0 && self.user_storage_accounts.contains_key(&receipt.payer) {
+                self.update_available_storage(receipt.payer.to_string(), receipt.bytes, 0);
+            };
+        }
+        env::log(
+            format!(
+                "Sponsored storage taken over! account: {}, bytes: {}",
+                account_id, taken_over_bytes
+            )
+            .as_bytes(),
+        );
+    }
+
+    /// Returns the bytes of account_id's entries other accounts are paying for
+    fn sponsored_bytes(&self, account_id: &AccountId) -> u64 {
+        self.sponsor_storage_receipts
+            .get(account_id)
+            .unwrap_or_default()
+            .iter()
+            .map(|receipt| receipt.bytes)
+            .sum()
+    }
+
+    /// Returns the storage receipts of pending proposals, or of queued ones
+    fn storage_receipts(&mut self, pending: bool) -> &mut LookupMap<u64, Vec<StorageReceipt>> {
+        match pending {
+            true => &mut self.pending_storage_receipts,
+            false => &mut self.proposal_storage_receipts,
+        }
+    }
+
+    /// Charges payer for the storage a proposal used since initial_storage and keeps a receipt,
+    /// so the bytes go back to payer when the proposal frees them. If the proposal shrank the
+    /// earlier payers are refunded instead.
+    fn add_storage_receipt(
+        &mut self,
+        pending: bool,
+        index: u64,
+        payer: AccountId,
+        initial_storage: u64,
+    ) {
+        let mut receipts = self
+            .storage_receipts(pending)
+            .get(&index)
+            .unwrap_or_default();
+        receipts.push(StorageReceipt {
+            payer: payer.to_string(),
+            bytes: 0,
+        });
+        self.storage_receipts(pending).insert(&index, &receipts);
+        // Updating the byte count below doesn't change the receipt's size
+        let storage_used = env::storage_usage();
+        if storage_used > initial_storage {
+            receipts.last_mut().unwrap().bytes = storage_used - initial_storage;
+            self.update_available_storage(payer, initial_storage, storage_used);
+        } else {
+            let unpaid_bytes =
+                self.refund_storage_receipts(&mut receipts, initial_storage - storage_used);
+            self.update_storage_pool(unpaid_bytes, 0);
+        };
+        self.storage_receipts(pending).insert(&index, &receipts);
+    }
+
+    /// Refunds freed bytes to the payers of the receipts, oldest first. Returns the bytes no
+    /// registered payer paid for.
+    fn refund_storage_receipts(
+        &mut self,
+        receipts: &mut Vec<StorageReceipt>,
+        freed_bytes: u64,
+    ) -> u64 {
+        let mut unpaid_bytes = freed_bytes;
+        for receipt in receipts.iter_mut() {
+            let refund = min(receipt.bytes, unpaid_bytes);
+            if refund == 0 || !self.user_storage_accounts.contains_key(&receipt.payer) {
+                continue;
+            };
+            receipt.bytes -= refund;
+            unpaid_bytes -= refund;
+            self.update_available_storage(receipt.payer.to_string(), refund, 0);
+        }
+        unpaid_bytes
+    }
+
+    /// Settles the storage of a proposal leaving the queue or the pending proposals, and drops
+    /// its receipts. Storage it freed goes back to whoever paid for it, and to the DAO storage
+    /// pool when no one did. Storage it used is charged to the proposer, or else the applicant,
+    /// or else the caller, whichever is registered and can cover it. Otherwise the DAO storage
+    /// pool pays, so a registered keeper can always process a proposal.
+    fn charge_proposal_storage(
+        &mut self,
+        pending: bool,
+        index: u64,
+        proposal: &Proposal,
+        initial_storage: u64,
+    ) {
+        let mut receipts = self
+            .storage_receipts(pending)
+            .remove(&index)
+            .unwrap_or_default();
+        let storage_used = env::storage_usage();
+        if storage_used <= initial_storage {
+            let unpaid_bytes =
+                self.refund_storage_receipts(&mut receipts, initial_storage - storage_used);
+            self.update_storage_pool(unpaid_bytes, 0);
+            return;
+        };
+        let cost = env::storage_byte_cost() * Balance::from(storage_used - initial_storage);
+        let payer = vec![
+            proposal.proposer.clone(),
+            proposal.applicant.clone(),
+            env::predecessor_account_id(),
+        ]
+        .into_iter()
+        .find(
+            |account_id| match self.user_storage_accounts.get(account_id) {
+                Some(user_storage) => user_storage.available >= cost,
+                None => false,
+            },
+        );
+        match payer {
+            Some(account_id) => {
+                self.update_available_storage(account_id, initial_storage, storage_used)
+            }
+            None => self.update_storage_pool(initial_storage, storage_used),
+        }
+    }
+
+    #[private]
+    fn update_storage_pool(&mut self, initial_storage: u64, storage_used: u64) {
+        if storage_used > initial_storage {
+            self.storage_pool = self
+                .storage_pool
+                .checked_sub(
+                    env::storage_byte_cost() * Balance::from(storage_used - initial_storage),
+                )
+                .expect("The DAO storage pool cannot cover the storage");
+        } else {
+            self.storage_pool +=
+                env::storage_byte_cost() * Balance::from(initial_storage - storage_used);
+        };
     }
 
     /// At any time members can submit a new proposal using their delegate_key
@@ -308,18 +1172,26 @@ impl Moloch {
     ///
     /// Existing members can earn additional voting shares through new proposals if they are listed
     /// as the applicant.
-    #[payable]
+    ///
+    /// Tribute can be offered in the approved token, in NEAR, as NFTs, or any mix of these. Each
+    /// is taken from the applicant's escrow for that asset.
     pub fn submit_proposal(
         &mut self,
         applicant: AccountId,
         token_tribute: U128,
+        near_tribute: U128,
+        nft_tribute: Vec<Nft>,
         shares_requested: U128,
         details: String,
     ) {
         let initial_storage_usage = env::storage_usage();
+        self.assert_active();
+        self.assert_not_paused();
         // 0. delegate check
         self.only_delegate();
+        self.assert_delegate_scope(KeyScope::Propose);
         let _token_tribute = u128::from(token_tribute);
+        let _near_tribute = u128::from(near_tribute);
         let _shares_requested = u128::from(shares_requested);
 
         // 1. A couple logic checks
@@ -351,67 +1223,309 @@ impl Moloch {
             .get(&env::predecessor_account_id())
             .unwrap();
 
-        self.escrow
-            .withdraw(member_id.to_string(), self.proposal_deposit);
-        self.escrow
-            .withdraw(applicant.to_string(), token_tribute.into());
-
-        // 4. Calculate starting period
-        let mut period_based_on_queue = 0;
-        let queue_len = self.proposal_queue.len();
-        if queue_len != 0 {
-            period_based_on_queue = match self.proposal_queue.get(queue_len.saturating_sub(1)) {
-                Some(proposal) => proposal.starting_period,
-                None => 0,
-            }
-        }
-        let starting_period =
-            max(u64::from(self.get_current_period()), period_based_on_queue).saturating_add(1);
+        // Applicants consent to their own proposals, other applicants through an authorization
+        // or by pledging later with pledge_tribute
+        let tribute_pledged = applicant == member_id
+            || self.use_tribute_authorization(
+                &applicant,
+                &member_id,
+                _token_tribute,
+                _near_tribute,
+                &nft_tribute,
+            );
+        // Proposals without a pledge wait outside the queue so they can't hold it up
+        let (proposal_index, deposit_purpose) = match tribute_pledged {
+            true => (self.proposal_queue.len(), LockPurpose::Deposit),
+            false => (self.pending_proposal_count, LockPurpose::PendingDeposit),
+        };
+        self.escrow.lock(
+            proposal_index,
+            member_id.to_string(),
+            self.proposal_deposit,
+            0,
+            deposit_purpose,
+        );
+        self.record_statement(
+            &member_id,
+            StatementKind::DepositLock,
+            &self.token_id.to_string(),
+            self.proposal_deposit,
+            match tribute_pledged {
+                true => Some(proposal_index),
+                false => None,
+            },
+        );
 
-        // 5. Add to queue
-        let proposal = Proposal {
+        // 4. Create the proposal, the starting period is set when it joins the queue
+        let mut proposal = Proposal {
             proposer: member_id,
             applicant: applicant,
             shares_requested: _shares_requested,
-            starting_period: starting_period,
+            starting_period: 0,
             yes_votes: 0,
             no_votes: 0,
             processed: false,
             did_pass: false,
             aborted: false,
             token_tribute: _token_tribute,
+            near_tribute: _near_tribute,
+            nft_tribute: nft_tribute,
             details: details,
             max_total_shares_at_yes_vote: 0,
             votes_by_member: HashMap::new(),
+            kind: ProposalKind::Membership,
+            tribute_pledged: tribute_pledged,
+        };
+        // 5. Add to queue, or to the pending proposals until the applicant pledges
+        let index_name = match tribute_pledged {
+            true => {
+                proposal.starting_period = self.next_starting_period();
+                self.pull_tribute(proposal_index, &proposal);
+                self.proposal_queue.push(&proposal);
+                "proposal_index"
+            }
+            false => {
+                self.pending_proposals.insert(&proposal_index, &proposal);
+                self.pending_proposal_count += 1;
+                "pending_index"
+            }
         };
-        self.proposal_queue.push(&proposal);
-        let proposal_index = self.proposal_queue.len().saturating_sub(1);
         // 6. Log
-        env::log(format!("Proposal submitted! proposal_index: {}, sender: {}, member_address: {}, applicant: {}, token_tribute: {}, shares_requested: {}", proposal_index, env::predecessor_account_id(), proposal.proposer, proposal.applicant, _token_tribute, _shares_requested).as_bytes());
+        env::log(format!("Proposal submitted! {}: {}, sender: {}, member_address: {}, applicant: {}, token_tribute: {}, near_tribute: {}, shares_requested: {}, tribute_pledged: {}", index_name, proposal_index, env::predecessor_account_id(), proposal.proposer, proposal.applicant, _token_tribute, _near_tribute, _shares_requested, tribute_pledged).as_bytes());
 
-        self.update_available_storage(
-            env::predecessor_account_id(),
+        self.add_storage_receipt(
+            !tribute_pledged,
+            proposal_index,
+            proposal.proposer,
             initial_storage_usage,
-            env::storage_usage(),
         );
     }
 
-    /// While a proposal is in its voting period, members can submit their vote using their
-    /// delegate_key.
-    ///
-    /// This function:
-    /// 1. Saves the vote on proposal by member address
-    /// 2. Based on the vote, adds the member's voting shares to the proposal yesVotes or noVote
+    /// Members can propose replacing the guardian, or removing it with None. The proposal takes
+    /// the proposal deposit like any other, requests no shares or tribute and goes through the
+    /// same queue, vote and grace period. The guardian is replaced when it is processed as passed.
+    pub fn submit_guardian_proposal(&mut self, new_guardian: Option<AccountId>, details: String) {
+        let initial_storage_usage = env::storage_usage();
+        self.assert_active();
+        self.assert_not_paused();
+        self.only_delegate();
+        self.assert_delegate_scope(KeyScope::Propose);
+        if let Some(new_guardian) = &new_guardian {
+            assert!(
+                env::is_valid_account_id(new_guardian.as_bytes()),
+                "Guardian must be a valid account"
+            );
+        };
+        let member_id = self
+            .members_by_delegate_key
+            .get(&env::predecessor_account_id())
+            .unwrap();
+        let proposal_index = self.proposal_queue.len();
+        self.escrow.lock(
+            proposal_index,
+            member_id.to_string(),
+            self.proposal_deposit,
+            0,
+            LockPurpose::Deposit,
+        );
+        self.record_statement(
+            &member_id,
+            StatementKind::DepositLock,
+            &self.token_id.to_string(),
+            self.proposal_deposit,
+            Some(proposal_index),
+        );
+
+        let proposal = Proposal {
+            proposer: member_id.to_string(),
+            applicant: member_id,
+            starting_period: self.next_starting_period(),
+            details: details,
+            kind: ProposalKind::UpdateGuardian(new_guardian.clone()),
+            tribute_pledged: true,
+            ..Proposal::default()
+        };
+        self.proposal_queue.push(&proposal);
+        env::log(
+            format!(
+                "Guardian proposal submitted! proposal_index: {}, sender: {}, member_address: {}, new_guardian: {:?}",
+                proposal_index,
+                env::predecessor_account_id(),
+                proposal.proposer,
+                new_guardian
+            )
+            .as_bytes(),
+        );
+
+        self.add_storage_receipt(
+            false,
+            proposal_index,
+            proposal.proposer,
+            initial_storage_usage,
+        );
+    }
+
+    /// Members can propose kicking a member out of the guild. When it passes the member's shares
+    /// are burned and they are credited their share of the guild bank and NFT claims, as if they
+    /// had rage quit everything. The proposal otherwise works like a guardian proposal.
+    pub fn submit_guild_kick_proposal(&mut self, member_to_kick: AccountId, details: String) {
+        let initial_storage_usage = env::storage_usage();
+        self.assert_active();
+        self.assert_not_paused();
+        self.only_delegate();
+        self.assert_delegate_scope(KeyScope::Propose);
+        let kicked_shares = match self.members.get(&member_to_kick) {
+            Some(member) => member.shares,
+            None => 0,
+        };
+        assert!(
+            kicked_shares > 0,
+            "{} is not a member with shares",
+            member_to_kick
+        );
+        let member_id = self
+            .members_by_delegate_key
+            .get(&env::predecessor_account_id())
+            .unwrap();
+        let proposal_index = self.proposal_queue.len();
+        self.escrow.lock(
+            proposal_index,
+            member_id.to_string(),
+            self.proposal_deposit,
+            0,
+            LockPurpose::Deposit,
+        );
+        self.record_statement(
+            &member_id,
+            StatementKind::DepositLock,
+            &self.token_id.to_string(),
+            self.proposal_deposit,
+            Some(proposal_index),
+        );
+
+        let proposal = Proposal {
+            proposer: member_id.to_string(),
+            applicant: member_id,
+            starting_period: self.next_starting_period(),
+            details: details,
+            kind: ProposalKind::GuildKick(member_to_kick.to_string()),
+            tribute_pledged: true,
+            ..Proposal::default()
+        };
+        self.proposal_queue.push(&proposal);
+        env::log(
+            format!(
+                "Guild kick proposal submitted! proposal_index: {}, sender: {}, member_address: {}, member_to_kick: {}",
+                proposal_index,
+                env::predecessor_account_id(),
+                proposal.proposer,
+                member_to_kick
+            )
+            .as_bytes(),
+        );
+
+        self.add_storage_receipt(
+            false,
+            proposal_index,
+            proposal.proposer,
+            initial_storage_usage,
+        );
+    }
+
+    /// Members can propose upgrading the DAO to a code version stored in a factory. When it
+    /// passes the DAO fetches the code with get_code, deploys it to itself and records the
+    /// version in the factory. process_proposal has to be called with enough gas for the
+    /// upgrade, the proposal otherwise works like a guardian proposal.
+    pub fn submit_upgrade_proposal(
+        &mut self,
+        factory_id: AccountId,
+        code_version: U64,
+        details: String,
+    ) {
+        let initial_storage_usage = env::storage_usage();
+        self.assert_active();
+        self.assert_not_paused();
+        self.only_delegate();
+        self.assert_delegate_scope(KeyScope::Propose);
+        assert!(
+            env::is_valid_account_id(factory_id.as_bytes()),
+            "Factory must be a valid account"
+        );
+        let member_id = self
+            .members_by_delegate_key
+            .get(&env::predecessor_account_id())
+            .unwrap();
+        let proposal_index = self.proposal_queue.len();
+        self.escrow.lock(
+            proposal_index,
+            member_id.to_string(),
+            self.proposal_deposit,
+            0,
+            LockPurpose::Deposit,
+        );
+        self.record_statement(
+            &member_id,
+            StatementKind::DepositLock,
+            &self.token_id.to_string(),
+            self.proposal_deposit,
+            Some(proposal_index),
+        );
+
+        let proposal = Proposal {
+            proposer: member_id.to_string(),
+            applicant: member_id,
+            starting_period: self.next_starting_period(),
+            details: details,
+            kind: ProposalKind::Upgrade(factory_id.clone(), code_version.into()),
+            tribute_pledged: true,
+            ..Proposal::default()
+        };
+        self.proposal_queue.push(&proposal);
+        env::log(
+            format!(
+                "Upgrade proposal submitted! proposal_index: {}, sender: {}, member_address: {}, factory: {}, code_version: {}",
+                proposal_index,
+                env::predecessor_account_id(),
+                proposal.proposer,
+                factory_id,
+                u64::from(code_version)
+            )
+            .as_bytes(),
+        );
+
+        self.add_storage_receipt(
+            false,
+            proposal_index,
+            proposal.proposer,
+            initial_storage_usage,
+        );
+    }
+
+    /// While a proposal is in its voting period, members can submit their vote using their
+    /// delegate_key.
+    ///
+    /// This function:
+    /// 1. Saves the vote on proposal by member address
+    /// 2. Based on the vote, adds the member's voting shares to the proposal yesVotes or noVote
     ///    tallies
     /// 3. If the member voted Yes and this is now the highest index proposal they voted yes on, it
     ///    updates their highest_index_yes_vote
     /// 4. If the member voted Yes and this is now the most total shares that the Guild had during
     ///    any Yes vote, update the proposal max_total_shares_at_yes_vote.
+    ///
+    /// The vote also carries the weight of every member who delegated to this member, directly or
+    /// through a chain of delegations, and who has not voted on the proposal yet. Their votes are
+    /// recorded as the same vote and a Yes updates each of their highest_index_yes_vote.
+    ///
+    /// Every member's weight is snapshotted at the proposal's starting_period, see
+    /// get_member_voting_shares.
     pub fn submit_vote(&mut self, proposal_index: U64, uint_vote: u8) {
         let initial_storage_usage = env::storage_usage();
+        self.assert_not_paused();
         let proposal_index = u64::from(proposal_index);
         // 0. delegate check
         self.only_delegate();
+        self.assert_delegate_scope(KeyScope::Vote);
         // 1. Get member
         let member_id = self
             .members_by_delegate_key
@@ -451,13 +1565,31 @@ impl Moloch {
         };
         assert!(!already_voted, "Member has already voted");
         assert!(!proposal.aborted, "Proposal has been aborted");
+        assert!(
+            proposal.tribute_pledged,
+            "Tribute has not been pledged by the applicant"
+        );
+        assert!(
+            member.vote_delegatee.is_none(),
+            "Member has delegated their votes"
+        );
 
-        // 5. Store vote
+        // 5. Store vote for the member and for everyone whose weight they carry
         proposal.votes_by_member.insert(member_id.clone(), vote);
+        let mut weight = member.shares_at(proposal.starting_period);
+        for delegator_id in self.delegators_without_vote(&member_id, &proposal) {
+            let mut delegator = self.members.get(&delegator_id).unwrap();
+            weight = weight.saturating_add(delegator.shares_at(proposal.starting_period));
+            if vote == Vote::Yes && proposal_index > delegator.highest_index_yes_vote {
+                delegator.highest_index_yes_vote = proposal_index;
+                self.members.insert(&delegator_id, &delegator);
+            };
+            proposal.votes_by_member.insert(delegator_id, vote);
+        }
         // 6. Add vote to count
         match vote {
             Vote::Yes => {
-                proposal.yes_votes = proposal.yes_votes.saturating_add(member.shares);
+                proposal.yes_votes = proposal.yes_votes.saturating_add(weight);
                 if proposal_index > member.highest_index_yes_vote {
                     member.highest_index_yes_vote = proposal_index;
                 };
@@ -466,7 +1598,7 @@ impl Moloch {
                 };
             }
             Vote::No => {
-                proposal.no_votes = proposal.no_votes.saturating_add(member.shares);
+                proposal.no_votes = proposal.no_votes.saturating_add(weight);
             }
             Vote::Null => {}
         }
@@ -484,16 +1616,12 @@ impl Moloch {
             )
             .as_bytes(),
         );
-        self.update_available_storage(
-            env::predecessor_account_id(),
-            initial_storage_usage,
-            env::storage_usage(),
-        );
+        self.update_available_storage(member_id, initial_storage_usage, env::storage_usage());
     }
 
     /// After a proposal has completed its grace period, anyone can call process_proposal to tally
     /// the votes and either accept or reject it. The caller will receive a reward for processing
-    /// the proposal.
+    /// the proposal, credited to their withdrawable balance.
     ///
     /// 1. Sets proposal.processsed = true to prevent duplicate processing
     /// 2. Update total_shares_requested to no longer have the shares requested in the processed
@@ -508,9 +1636,9 @@ impl Moloch {
     ///      4.2.1. For new members, if the member address is taken by an existing member's
     ///        delegate_key forcibly reset that member's delegate_key to their member address.
     ///    4.3. Update the total shares
-    ///    4.4  Transfer the tribute being held in escrow to the guild bank
+    ///    4.4  Transfer the token, NEAR and NFT tribute being held in escrow to the guild bank
     /// 5. Otherwise: return all the tribute being held in escrow to the applicant
-    /// 6. Send a processing reward to the address that called this function
+    /// 6. Credit a processing reward to the address that called this function
     /// 7. Send the proposal deposit minus the processing reward to the proposer
     ///
     /// The dilution_bound is a safety net mechanism designed to prevent a memeber from facing a
@@ -522,9 +1650,9 @@ impl Moloch {
     /// will fail. This means that members voting yes will only be obligated to contribute at most
     /// 3x what they were willing to contribute their share of the proposal cost, if 2/3 of the
     /// shares ragequit
-    #[payable]
-    pub fn process_proposal(&mut self, proposal_index: U64) -> Promise {
-        let initial_storage_usage = env::storage_usage();
+    pub fn process_proposal(&mut self, proposal_index: U64) {
+        let mut initial_storage_usage = env::storage_usage();
+        self.assert_not_paused();
         let _proposal_index = u64::from(proposal_index);
         assert!(
             _proposal_index < self.proposal_queue.len(),
@@ -564,6 +1692,7 @@ impl Moloch {
 
         // Set proposal processed to true
         proposal.processed = true;
+        let current_period = u64::from(self.get_current_period());
 
         // Calculate total shares requested
         self.total_shares_requested = self
@@ -572,68 +1701,161 @@ impl Moloch {
 
         // Check if proposal passed
         let mut passed = proposal.yes_votes > proposal.no_votes;
-        // Fail if dilution exceeeded
-        let max_total_shares = match self.total_shares_requested.checked_mul(self.dilution_bound) {
+        // Fail if dilution exceeeded, yes voters can't be diluted by more than the dilution
+        // bound by members rage quitting after the vote
+        let max_total_shares = match self.total_shares.checked_mul(self.dilution_bound) {
             Some(shares) => shares,
             None => u128::MAX,
         };
-        if max_total_shares > proposal.max_total_shares_at_yes_vote {
+        if max_total_shares < proposal.max_total_shares_at_yes_vote {
             passed = false
         };
 
         if passed == true && !proposal.aborted {
             proposal.did_pass = true;
-            let member_exists = match self.members.get(&proposal.applicant) {
-                Some(_) => true,
-                None => false,
-            };
-            if member_exists {
-                let mut member = self.members.get(&proposal.applicant).unwrap();
-                member.shares = member.shares.saturating_add(proposal.shares_requested);
-                self.members.insert(&proposal.applicant, &member);
-            } else {
-                let member_delegate_key =
-                    match self.members_by_delegate_key.get(&proposal.applicant) {
-                        Some(delegate_key) => delegate_key,
-                        None => "".to_string(),
+            match proposal.kind.clone() {
+                ProposalKind::Membership => {
+                    let member_exists = match self.members.get(&proposal.applicant) {
+                        Some(_) => true,
+                        None => false,
                     };
-                let member_exists = match self.members.get(&member_delegate_key) {
-                    Some(_) => true,
-                    None => false,
-                };
-                if member_exists {
-                    let mut member = self.members.get(&member_delegate_key).unwrap();
-                    self.members_by_delegate_key
-                        .insert(&member_delegate_key, &member_delegate_key);
-                    member.delegate_key = member_delegate_key.to_string();
-                    self.members.insert(&member_delegate_key, &member);
-                };
-
-                // Use applicant account id as delegate key by default
-                self.members.insert(
-                    &proposal.applicant,
-                    &Member {
-                        delegate_key: proposal.applicant.clone(),
-                        shares: proposal.shares_requested,
-                        exists: true,
-                        highest_index_yes_vote: 0,
-                    },
-                );
-                self.members_by_delegate_key
-                    .insert(&proposal.applicant, &proposal.applicant);
+                    if member_exists {
+                        let mut member = self.members.get(&proposal.applicant).unwrap();
+                        member.shares = member.shares.saturating_add(proposal.shares_requested);
+                        member.checkpoint_shares(current_period);
+                        self.members.insert(&proposal.applicant, &member);
+                    } else {
+                        let member_delegate_key =
+                            match self.members_by_delegate_key.get(&proposal.applicant) {
+                                Some(delegate_key) => delegate_key,
+                                None => "".to_string(),
+                            };
+                        let member_exists = match self.members.get(&member_delegate_key) {
+                            Some(_) => true,
+                            None => false,
+                        };
+                        if member_exists {
+                            let mut member = self.members.get(&member_delegate_key).unwrap();
+                            if member.delegate_key == proposal.applicant {
+                                self.members_by_delegate_key
+                                    .insert(&member_delegate_key, &member_delegate_key);
+                                member.delegate_key = member_delegate_key.to_string();
+                            } else {
+                                member
+                                    .scoped_keys
+                                    .retain(|key| key.account_id != proposal.applicant);
+                            };
+                            self.members.insert(&member_delegate_key, &member);
+                        };
+
+                        // Use applicant account id as delegate key by default
+                        let mut member = Member {
+                            delegate_key: proposal.applicant.clone(),
+                            shares: proposal.shares_requested,
+                            exists: true,
+                            highest_index_yes_vote: 0,
+                            vote_delegatee: None,
+                            vote_delegators: vec![],
+                            share_checkpoints: vec![],
+                            scoped_keys: vec![],
+                            profile: None,
+                        };
+                        member.checkpoint_shares(current_period);
+                        self.members.insert(&proposal.applicant, &member);
+                        self.members_by_delegate_key
+                            .insert(&proposal.applicant, &proposal.applicant);
+                    }
+                    self.total_shares = self.total_shares.saturating_add(proposal.shares_requested);
+                    log_share_event(
+                        "ft_mint",
+                        &proposal.applicant,
+                        proposal.shares_requested,
+                        &format!("Proposal {}", _proposal_index),
+                    );
+                    if let Some(tribute) =
+                        self.escrow.release(_proposal_index, LockPurpose::Tribute)
+                    {
+                        self.bank.deposit(tribute.amount);
+                        self.bank.deposit_near(tribute.near_amount);
+                        self.record_lock(&tribute, StatementKind::Tribute);
+                    };
+                    for nft in proposal.nft_tribute.iter() {
+                        self.bank.deposit_nft(nft.clone());
+                    }
+                }
+                ProposalKind::UpdateGuardian(guardian) => {
+                    env::log(
+                        format!(
+                            "Guardian updated! old_guardian: {:?}, new_guardian: {:?}",
+                            self.guardian, guardian
+                        )
+                        .as_bytes(),
+                    );
+                    self.guardian = guardian;
+                }
+                ProposalKind::GuildKick(member_id) => {
+                    let shares = match self.members.get(&member_id) {
+                        Some(member) => member.shares,
+                        None => 0,
+                    };
+                    // The member may have rage quit while the proposal was pending
+                    if shares > 0 {
+                        // The profile's storage is settled with the member, not the proposal
+                        initial_storage_usage -= self.remove_member_profile(&member_id);
+                        self.burn_shares(&member_id, shares, StatementKind::GuildKick);
+                    };
+                    env::log(
+                        format!(
+                            "Guild kick! member: {}, shares_burned: {}",
+                            member_id, shares
+                        )
+                        .as_bytes(),
+                    );
+                }
+                ProposalKind::Upgrade(factory_id, code_version) => {
+                    env::log(
+                        format!(
+                            "Upgrade started! factory: {}, code_version: {}",
+                            factory_id, code_version
+                        )
+                        .as_bytes(),
+                    );
+                    let prepaid_gas = env::prepaid_gas();
+                    ext_factory::get_code(code_version.into(), &factory_id, 0, prepaid_gas / 3)
+                        .then(ext_self::on_upgrade_code(
+                            factory_id,
+                            code_version.into(),
+                            &env::current_account_id(),
+                            0,
+                            prepaid_gas / 3,
+                        ));
+                }
             }
-            self.total_shares = self.total_shares.saturating_add(proposal.shares_requested);
-            self.bank.deposit(proposal.token_tribute);
-        } else {
-            self.escrow
-                .deposit(proposal.applicant.clone(), proposal.token_tribute);
+        } else if proposal.tribute_pledged {
+            self.return_tribute(_proposal_index, &proposal);
         }
 
-        // TODO: Are these rolled back if the transaction failed
-        self.escrow.deposit(
-            proposal.proposer.clone(),
-            self.proposal_deposit.saturating_sub(self.processing_reward),
+        // Return the deposit, less the abort penalty which goes to the guild bank
+        let abort_penalty = match proposal.aborted {
+            true => self.abort_penalty,
+            false => 0,
+        };
+        let deposit = match self.escrow.release(_proposal_index, LockPurpose::Deposit) {
+            Some(lock) => lock.amount,
+            None => 0,
+        };
+        let refund = deposit
+            .saturating_sub(self.processing_reward)
+            .saturating_sub(abort_penalty);
+        self.escrow.deposit(proposal.proposer.clone(), refund);
+        self.record_statement(
+            &proposal.proposer,
+            StatementKind::Refund,
+            &self.token_id.to_string(),
+            refund,
+            Some(_proposal_index),
         );
+        self.bank.deposit(abort_penalty);
 
         let message = format!(
                 "Proposal Processed! proposal_index: {}, proposal_applicant: {}, proposal_proposer: {}, proposal_token_tribute: {}, proposal_shares_requested: {}, passed: {}",
@@ -648,34 +1870,41 @@ impl Moloch {
             .replace(proposal_index.into(), &proposal);
         env::log(message.as_bytes());
 
-        self.update_available_storage(
+        // Credit processing reward
+        self.ledger.credit(
             env::predecessor_account_id(),
-            initial_storage_usage,
-            env::storage_usage(),
+            self.token_id.to_string(),
+            self.processing_reward,
+        );
+        self.record_statement(
+            &env::predecessor_account_id(),
+            StatementKind::ProcessingReward,
+            &self.token_id.to_string(),
+            self.processing_reward,
+            Some(_proposal_index),
         );
 
-        // Pay processing reward
-        let prepaid_gas = env::prepaid_gas();
-        ext_fungible_token::ft_transfer(
-            env::predecessor_account_id(),
-            U128::from(self.processing_reward),
-            Some("pay out processing reward for processing proposal".to_string()),
-            &self.token_id,
-            1,
-            prepaid_gas / 2,
-        )
+        // Keepers don't need to register, the proposal pays for its own processing
+        self.charge_proposal_storage(false, _proposal_index, &proposal, initial_storage_usage);
     }
 
     /// A member can ragequit at any time, so long as the member has not voted Yes on any proposal
     /// in the voting period or grace period, they can irreversibly destroy some of their shares
-    /// and receive a proportional sum of the approved token from the Guild Bank.
+    /// and receive a proportional sum of the approved token from the Guild Bank. While the DAO is
+    /// paused no proposal can be processed. Once a pause has lasted a voting and a grace period,
+    /// by when every proposal voted on before it could have been processed, the Yes vote check is
+    /// skipped to let every member exit.
     ///
     /// 1. Reduce the member's shares by the shares_to_burn being destroyed
     /// 2. Reduce the total shares by the shares_to_burn
-    /// 3. Instruct the guild bank to send the member their proportional amount of the approved
-    ///    token
-    #[payable]
-    pub fn rage_quit(&mut self, shares_to_burn: U128) -> Promise {
+    /// 3. Credit the member's withdrawable balance with their proportional amount of the approved
+    ///    token and of the guild's NEAR from the guild bank
+    ///
+    /// NFTs in the guild bank vault can't be split. Instead the member is granted claims on their
+    /// proportional share of the unclaimed vault NFTs, rounded down, and takes them one at a time
+    /// with claim_nft. The rounding remainder stays in the vault for the remaining members, so the
+    /// last member out at dissolution gets what is left in proportion to the shares they hold.
+    pub fn rage_quit(&mut self, shares_to_burn: U128) {
         let initial_storage_usage = env::storage_usage();
         let _shares_to_burn = u128::from(shares_to_burn);
 
@@ -683,23 +1912,25 @@ impl Moloch {
         self.only_member();
         // Check insuffcient shares
         let predecessor_account_id = env::predecessor_account_id();
-        let mut member = self.members.get(&predecessor_account_id).unwrap();
-        let initial_total_shares = self.total_shares;
+        let member = self.members.get(&predecessor_account_id).unwrap();
 
         assert!(
             member.shares >= _shares_to_burn,
             "Not enough shares to be burned"
         );
-        // Check can rage_quit
-        let can_rage_quit = self.can_rage_quit(member.highest_index_yes_vote.into());
-        assert!(
-            can_rage_quit,
-            "Can't rage quit until the highest index proposal member voted YES is processed",
+        // Check can rage_quit, unless a long pause keeps pending proposals from being processed
+        if !self.is_pause_exit_open() {
+            let can_rage_quit = self.can_rage_quit(member.highest_index_yes_vote.into());
+            assert!(
+                can_rage_quit,
+                "Can't rage quit until the highest index proposal member voted YES is processed",
+            );
+        }
+        self.burn_shares(
+            &predecessor_account_id,
+            _shares_to_burn,
+            StatementKind::RageQuit,
         );
-        // Burn shares
-        member.shares = member.shares.saturating_sub(_shares_to_burn);
-        self.total_shares = self.total_shares.saturating_sub(_shares_to_burn);
-        self.members.insert(&predecessor_account_id, &member);
 
         // log rage_quit
         env::log(
@@ -710,18 +1941,12 @@ impl Moloch {
             )
             .as_bytes(),
         );
+
         self.update_available_storage(
-            env::predecessor_account_id(),
+            predecessor_account_id,
             initial_storage_usage,
             env::storage_usage(),
         );
-
-        // TODO: Does the above get rolled back if this fails
-        self.bank.withdraw(
-            predecessor_account_id,
-            _shares_to_burn,
-            initial_total_shares,
-        )
     }
 
     /// This function exists to mitigate situations
@@ -730,10 +1955,13 @@ impl Moloch {
     /// expecting.
     ///
     /// In these situations the applicant can cancel the proposal
-    /// and immediately receive their money back. Aborting will not
-    /// immediately return the proposers deposit aas punishment.
+    /// and immediately receive their tribute back in escrow: tokens,
+    /// NEAR and NFTs. The proposer's deposit is returned when the
+    /// proposal is processed, less the processing reward and the
+    /// abort penalty, which goes to the guild bank.
     pub fn abort(&mut self, proposal_index: U64) {
         let initial_storage_usage = env::storage_usage();
+        self.assert_not_paused();
         let _proposal_index = u64::from(proposal_index);
         // Check if proposal index is within the length
         assert!(
@@ -742,6 +1970,12 @@ impl Moloch {
         );
         // Get the proposal
         let mut proposal = self.proposal_queue.get(_proposal_index).unwrap();
+        // Only applicants offering tribute can back out, the other kinds have no applicant of
+        // their own
+        assert!(
+            proposal.kind == ProposalKind::Membership,
+            "Only membership proposals can be aborted"
+        );
         // Check sender is the applicant
         assert!(
             env::predecessor_account_id() == proposal.applicant,
@@ -759,17 +1993,18 @@ impl Moloch {
         assert!(!proposal.aborted, "Proposal has already been aborted");
         // Reset proposal params for abort
         proposal.aborted = true;
+        if proposal.tribute_pledged {
+            self.return_tribute(_proposal_index, &proposal);
+        };
         proposal.token_tribute = 0;
+        proposal.near_tribute = 0;
+        proposal.nft_tribute.clear();
 
         self.proposal_queue.replace(_proposal_index, &proposal);
 
         // Log abort
         env::log(format!("Proposal was aborted by {}", env::predecessor_account_id(),).as_bytes());
 
-        // return deposit
-        self.escrow
-            .deposit(proposal.proposer.clone(), self.processing_reward);
-
         self.update_available_storage(
             env::predecessor_account_id(),
             initial_storage_usage,
@@ -777,6 +2012,161 @@ impl Moloch {
         );
     }
 
+    /// An applicant allows a proposer to pledge up to token_tribute and near_tribute from the
+    /// applicant's escrow in proposals for them. Each proposal that uses the authorization
+    /// reduces it. Setting both amounts replaces the previous authorization.
+    ///
+    /// NFT tribute can't be authorized in advance, the applicant pledges it with pledge_tribute.
+    pub fn authorize_tribute(
+        &mut self,
+        proposer: AccountId,
+        token_tribute: U128,
+        near_tribute: U128,
+    ) {
+        let initial_storage_usage = env::storage_usage();
+        self.assert_not_paused();
+        let applicant = env::predecessor_account_id();
+        assert!(
+            env::is_valid_account_id(proposer.as_bytes()),
+            "proposer must be a valid account id"
+        );
+        self.tribute_authorizations.insert(
+            &(applicant.to_string(), proposer.to_string()),
+            &TributeAuthorization {
+                token_tribute: token_tribute.into(),
+                near_tribute: near_tribute.into(),
+            },
+        );
+        env::log(
+            format!(
+                "Tribute authorized! applicant: {}, proposer: {}, token_tribute: {}, near_tribute: {}",
+                applicant,
+                proposer,
+                u128::from(token_tribute),
+                u128::from(near_tribute)
+            )
+            .as_bytes(),
+        );
+        self.update_available_storage(applicant, initial_storage_usage, env::storage_usage());
+    }
+
+    /// Removes the caller's tribute authorization for proposer
+    pub fn revoke_tribute_authorization(&mut self, proposer: AccountId) {
+        let initial_storage_usage = env::storage_usage();
+        let applicant = env::predecessor_account_id();
+        assert!(
+            self.tribute_authorizations
+                .remove(&(applicant.to_string(), proposer.to_string()))
+                .is_some(),
+            "No tribute authorization for {}",
+            proposer
+        );
+        env::log(
+            format!(
+                "Tribute authorization revoked! applicant: {}, proposer: {}",
+                applicant, proposer
+            )
+            .as_bytes(),
+        );
+        self.update_available_storage(applicant, initial_storage_usage, env::storage_usage());
+    }
+
+    /// The applicant consents to a pending proposal submitted for them. The tribute moves from
+    /// the applicant's escrow into the proposal, which joins the proposal_queue. Returns its
+    /// proposal index.
+    pub fn pledge_tribute(&mut self, pending_index: U64) -> U64 {
+        let initial_storage_usage = env::storage_usage();
+        self.assert_not_paused();
+        let pending_index = u64::from(pending_index);
+        let mut proposal = match self.pending_proposals.get(&pending_index) {
+            Some(proposal) => proposal,
+            None => panic!("Pending proposal does not exist"),
+        };
+        assert!(
+            env::predecessor_account_id() == proposal.applicant,
+            "Calling account is not the proposal applicant"
+        );
+        self.pending_proposals.remove(&pending_index);
+        let proposal_index = self.proposal_queue.len();
+        let receipts = self
+            .pending_storage_receipts
+            .remove(&pending_index)
+            .unwrap_or_default();
+        self.proposal_storage_receipts
+            .insert(&proposal_index, &receipts);
+        // The proposer's deposit is now held for the queued proposal
+        if let Some(deposit) = self
+            .escrow
+            .refund(pending_index, LockPurpose::PendingDeposit)
+        {
+            self.escrow.lock(
+                proposal_index,
+                deposit.owner,
+                deposit.amount,
+                0,
+                LockPurpose::Deposit,
+            );
+        };
+        proposal.starting_period = self.next_starting_period();
+        proposal.tribute_pledged = true;
+        self.pull_tribute(proposal_index, &proposal);
+        self.proposal_queue.push(&proposal);
+        env::log(
+            format!(
+                "Tribute pledged! pending_index: {}, proposal_index: {}, applicant: {}",
+                pending_index, proposal_index, proposal.applicant
+            )
+            .as_bytes(),
+        );
+        self.add_storage_receipt(
+            false,
+            proposal_index,
+            proposal.applicant,
+            initial_storage_usage,
+        );
+        proposal_index.into()
+    }
+
+    /// The proposer or the applicant drops a proposal still waiting for the applicant's pledge.
+    /// It never reached the queue, so the proposer gets the whole deposit back.
+    pub fn cancel_pending_proposal(&mut self, pending_index: U64) {
+        let initial_storage_usage = env::storage_usage();
+        let pending_index = u64::from(pending_index);
+        let proposal = match self.pending_proposals.get(&pending_index) {
+            Some(proposal) => proposal,
+            None => panic!("Pending proposal does not exist"),
+        };
+        let sender = env::predecessor_account_id();
+        assert!(
+            sender == proposal.proposer || sender == proposal.applicant,
+            "Only the proposer or the applicant can cancel a pending proposal"
+        );
+        self.pending_proposals.remove(&pending_index);
+        self.total_shares_requested = self
+            .total_shares_requested
+            .saturating_sub(proposal.shares_requested);
+        if let Some(deposit) = self
+            .escrow
+            .refund(pending_index, LockPurpose::PendingDeposit)
+        {
+            self.record_statement(
+                &deposit.owner,
+                StatementKind::Refund,
+                &self.token_id.to_string(),
+                deposit.amount,
+                None,
+            );
+        };
+        env::log(
+            format!(
+                "Pending proposal cancelled! pending_index: {}, sender: {}",
+                pending_index, sender
+            )
+            .as_bytes(),
+        );
+        self.charge_proposal_storage(true, pending_index, &proposal, initial_storage_usage);
+    }
+
     /// By default when a member is accepted their delegateKey is set to their member accountId. At
     /// any time, they can change it to be any accountId that is not in use, or back to their
     /// accountId.
@@ -787,6 +2177,7 @@ impl Moloch {
     /// 3. Updates the member delegate_key
     pub fn update_delegate_key(&mut self, new_delegate_key: AccountId) {
         let initial_storage_usage = env::storage_usage();
+        self.assert_not_paused();
         self.only_member();
         // Delegate key cannot be 0
         assert!(
@@ -843,39 +2234,631 @@ impl Moloch {
         );
     }
 
-    #[payable]
-    pub fn escrow_withdraw(&mut self, account_id: AccountId, amount: U128) -> Promise {
-        assert_eq!(
-            account_id,
-            env::predecessor_account_id(),
-            "Predecessor account id does not equal withdrawl account id"
+    /// Members can add delegate keys that only vote, only submit proposals or do both, for
+    /// example a hot key on a phone that can vote but not propose. A key can expire at a period,
+    /// from which on it can't be used. The key resolves to the member like their delegate_key
+    /// does and the member pays for its storage.
+    pub fn add_delegate_key(
+        &mut self,
+        delegate_key: AccountId,
+        scope: KeyScope,
+        expires_at_period: Option<U64>,
+    ) {
+        let initial_storage_usage = env::storage_usage();
+        self.assert_not_paused();
+        self.only_member();
+        assert!(
+            env::is_valid_account_id(delegate_key.as_bytes()),
+            "Delegate key must be a valid account"
         );
-        self.escrow.withdraw(account_id, amount.into());
-        let prepaid_gas = env::prepaid_gas();
-        ext_fungible_token::ft_transfer(
-            env::predecessor_account_id(),
-            amount,
-            Some("Withdrawing fungible tokens from Moloch escrow".to_string()),
-            &self.token_id,
-            1,
-            prepaid_gas / 2,
-        )
+        let sender = env::predecessor_account_id();
+        let mut member = self.members.get(&sender).unwrap();
+        assert!(
+            member.scoped_keys.len() < MAX_SCOPED_KEYS,
+            "A member can have at most {} scoped delegate keys",
+            MAX_SCOPED_KEYS
+        );
+        let key_member = match self.members_by_delegate_key.get(&delegate_key) {
+            Some(member_id) => member_id,
+            None => "".to_string(),
+        };
+        assert!(
+            key_member == "" && self.members.get(&delegate_key).is_none(),
+            "Can't overwrite existing delegate keys"
+        );
+        let expires_at_period = expires_at_period.map(u64::from);
+        if let Some(expires_at_period) = expires_at_period {
+            assert!(
+                expires_at_period > u64::from(self.get_current_period()),
+                "Expiry must be a future period"
+            );
+        };
+        member.scoped_keys.push(ScopedKey {
+            account_id: delegate_key.to_string(),
+            scope: scope,
+            expires_at_period: expires_at_period,
+        });
+        self.members.insert(&sender, &member);
+        self.members_by_delegate_key.insert(&delegate_key, &sender);
+        env::log(
+            format!(
+                "Added delegate key! sender: {}, delegate_key: {}, scope: {:?}, expires_at_period: {:?}",
+                sender, delegate_key, scope, expires_at_period
+            )
+            .as_bytes(),
+        );
+        self.update_available_storage(sender, initial_storage_usage, env::storage_usage());
     }
 
-    // Getter functions
+    /// Members remove a key added with add_delegate_key
+    pub fn remove_delegate_key(&mut self, delegate_key: AccountId) {
+        let initial_storage_usage = env::storage_usage();
+        self.assert_not_paused();
+        self.only_member();
+        let sender = env::predecessor_account_id();
+        let mut member = self.members.get(&sender).unwrap();
+        let keys = member.scoped_keys.len();
+        member
+            .scoped_keys
+            .retain(|key| key.account_id != delegate_key);
+        assert!(
+            member.scoped_keys.len() < keys,
+            "{} is not a scoped delegate key of {}",
+            delegate_key,
+            sender
+        );
+        self.members.insert(&sender, &member);
+        self.members_by_delegate_key.remove(&delegate_key);
+        env::log(
+            format!(
+                "Removed delegate key! sender: {}, delegate_key: {}",
+                sender, delegate_key
+            )
+            .as_bytes(),
+        );
+        self.update_available_storage(sender, initial_storage_usage, env::storage_usage());
+    }
+
+    /// Members with shares can set a profile with a display name, an avatar url and a few links
+    /// for DAO interfaces to show. The member pays for its storage and the profile is removed
+    /// when they rage quit all of their shares.
+    pub fn set_member_profile(
+        &mut self,
+        display_name: String,
+        avatar_url: Option<String>,
+        links: Vec<String>,
+    ) {
+        let initial_storage_usage = env::storage_usage();
+        self.assert_not_paused();
+        self.only_member();
+        let sender = env::predecessor_account_id();
+        let mut member = self.members.get(&sender).unwrap();
+        assert!(
+            member.shares > 0,
+            "Only members with shares can set a profile"
+        );
+        assert!(
+            !display_name.is_empty() && display_name.len() <= MAX_PROFILE_NAME_LENGTH,
+            "Display name must be between 1 and {} bytes",
+            MAX_PROFILE_NAME_LENGTH
+        );
+        assert!(
+            links.len() <= MAX_PROFILE_LINKS,
+            "A profile can have at most {} links",
+            MAX_PROFILE_LINKS
+        );
+        for url in avatar_url.iter().chain(links.iter()) {
+            assert!(
+                url.len() <= MAX_PROFILE_URL_LENGTH,
+                "Urls can be at most {} bytes",
+                MAX_PROFILE_URL_LENGTH
+            );
+        }
+        member.profile = Some(MemberProfile {
+            display_name: display_name,
+            avatar_url: avatar_url,
+            links: links,
+        });
+        self.members.insert(&sender, &member);
+        env::log(format!("Profile updated! member: {}", sender).as_bytes());
+        self.update_available_storage(sender, initial_storage_usage, env::storage_usage());
+    }
+
+    /// Members remove their profile and get its storage back
+    pub fn clear_member_profile(&mut self) {
+        let initial_storage_usage = env::storage_usage();
+        self.only_member();
+        let sender = env::predecessor_account_id();
+        let mut member = self.members.get(&sender).unwrap();
+        assert!(member.profile.is_some(), "Member has no profile");
+        member.profile = None;
+        self.members.insert(&sender, &member);
+        env::log(format!("Profile cleared! member: {}", sender).as_bytes());
+        self.update_available_storage(sender, initial_storage_usage, env::storage_usage());
+    }
+
+    /// A member can delegate their voting weight to another member. The weight is counted when
+    /// the delegatee, or whoever the delegatee delegates to, votes on a proposal the member has not
+    /// voted on. A member who has delegated can't vote until they revoke the delegation.
+    ///
+    /// Delegating again replaces the previous delegation and a delegation that would form a cycle
+    /// is rejected.
+    pub fn delegate_votes(&mut self, delegatee: AccountId) {
+        let initial_storage_usage = env::storage_usage();
+        self.assert_not_paused();
+        self.only_member();
+        let sender = env::predecessor_account_id();
+        assert!(sender != delegatee, "Can't delegate votes to yourself");
+        let delegatee_member = match self.members.get(&delegatee) {
+            Some(member) => member,
+            None => Member::default(),
+        };
+        assert!(delegatee_member.exists, "Delegatee is not a member");
+        // Following the delegatee's chain back to the sender means a cycle
+        let mut next_delegatee = delegatee_member.vote_delegatee;
+        while let Some(next_delegatee_id) = next_delegatee {
+            assert!(
+                next_delegatee_id != sender,
+                "Delegation would create a cycle"
+            );
+            next_delegatee = self.members.get(&next_delegatee_id).unwrap().vote_delegatee;
+        }
+
+        self.remove_vote_delegation(&sender);
+        let mut delegatee_member = self.members.get(&delegatee).unwrap();
+        delegatee_member.vote_delegators.push(sender.clone());
+        self.members.insert(&delegatee, &delegatee_member);
+        let mut member = self.members.get(&sender).unwrap();
+        member.vote_delegatee = Some(delegatee.clone());
+        self.members.insert(&sender, &member);
+
+        env::log(
+            format!(
+                "Delegated votes! sender: {}, delegatee: {}",
+                sender, delegatee
+            )
+            .as_bytes(),
+        );
+        self.update_available_storage(sender, initial_storage_usage, env::storage_usage());
+    }
+
+    /// Revokes the caller's vote delegation so they can vote again. Votes already cast with the
+    /// member's weight still count.
+    pub fn revoke_vote_delegation(&mut self) {
+        let initial_storage_usage = env::storage_usage();
+        self.assert_not_paused();
+        self.only_member();
+        let sender = env::predecessor_account_id();
+        assert!(
+            self.remove_vote_delegation(&sender),
+            "Member has not delegated their votes"
+        );
+        env::log(format!("Revoked vote delegation! sender: {}", sender).as_bytes());
+        self.update_available_storage(sender, initial_storage_usage, env::storage_usage());
+    }
+
+    /// Moves tokens out of the caller's escrow into their withdrawable balance, from where
+    /// withdraw_balance pays them out
+    #[payable]
+    pub fn escrow_withdraw(&mut self, account_id: AccountId, amount: U128) {
+        let initial_storage_usage = env::storage_usage();
+        assert_eq!(
+            account_id,
+            env::predecessor_account_id(),
+            "Predecessor account id does not equal withdrawl account id"
+        );
+        self.escrow.withdraw(account_id.to_string(), amount.into());
+        self.ledger.credit(
+            account_id.to_string(),
+            self.token_id.to_string(),
+            amount.into(),
+        );
+        self.record_statement(
+            &account_id,
+            StatementKind::EscrowWithdrawal,
+            &self.token_id.to_string(),
+            amount.into(),
+            None,
+        );
+        self.update_available_storage(account_id, initial_storage_usage, env::storage_usage());
+    }
+
+    /// Pays out the caller's withdrawable balance of a token. token_id is the approved token or
+    /// "NEAR" for native NEAR. If the transfer fails the amount is credited back.
+    ///
+    /// Registered accounts pay for the statement entry out of their storage balance, and for the
+    /// refund entry a failed payout records. What the refund entry doesn't use is given back once
+    /// the payout resolves.
+    #[payable]
+    pub fn withdraw_balance(&mut self, token_id: AccountId, amount: U128) -> Promise {
+        let initial_storage_usage = env::storage_usage();
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let _amount = u128::from(amount);
+        assert!(_amount > 0, "Amount must be greater than 0");
+        self.ledger
+            .debit(account_id.to_string(), token_id.to_string(), _amount);
+        env::log(
+            format!(
+                "Withdraw balance! account: {}, token: {}, amount: {}",
+                account_id, token_id, _amount
+            )
+            .as_bytes(),
+        );
+        self.record_statement(
+            &account_id,
+            StatementKind::Withdrawal,
+            &token_id,
+            _amount,
+            None,
+        );
+        let mut reserved_bytes = 0;
+        if self.user_storage_accounts.contains_key(&account_id) {
+            reserved_bytes = self.statements.entry_bytes(&token_id, None);
+            self.update_available_storage(
+                account_id.to_string(),
+                initial_storage_usage,
+                env::storage_usage() + reserved_bytes,
+            );
+        };
+        let prepaid_gas = env::prepaid_gas();
+        let transfer = if token_id == NEAR_TOKEN_ID {
+            Promise::new(account_id.to_string()).transfer(_amount)
+        } else {
+            assert!(token_id == self.token_id, "Unknown token {}", token_id);
+            self.pending_withdrawals = self.pending_withdrawals.saturating_add(_amount);
+            self.withdrawal_nonce += 1;
+            ext_fungible_token::ft_transfer(
+                account_id.to_string(),
+                amount,
+                Some("Withdrawing balance from Moloch".to_string()),
+                &self.token_id,
+                1,
+                prepaid_gas / 3,
+            )
+        };
+        transfer.then(ext_self::on_withdraw_balance(
+            account_id,
+            token_id,
+            amount,
+            reserved_bytes.into(),
+            &env::current_account_id(),
+            0,
+            prepaid_gas / 3,
+        ))
+    }
+
+    /// Credits a failed withdraw_balance payout back to the account. The refund entry is paid
+    /// from the reserved_bytes withdraw_balance charged, the rest is given back.
+    #[private]
+    pub fn on_withdraw_balance(
+        &mut self,
+        account_id: AccountId,
+        token_id: AccountId,
+        amount: U128,
+        reserved_bytes: U64,
+    ) {
+        let initial_storage_usage = env::storage_usage() + u64::from(reserved_bytes);
+        if token_id == self.token_id {
+            self.pending_withdrawals = self.pending_withdrawals.saturating_sub(amount.into());
+        };
+        match env::promise_result(0) {
+            PromiseResult::Successful(_) => {}
+            _ => {
+                self.ledger
+                    .credit(account_id.to_string(), token_id.to_string(), amount.into());
+                self.record_statement(
+                    &account_id,
+                    StatementKind::Refund,
+                    &token_id,
+                    amount.into(),
+                    None,
+                );
+                env::log(
+                    format!(
+                        "Withdraw balance failed, refunded! account: {}, token: {}, amount: {}",
+                        account_id,
+                        token_id,
+                        u128::from(amount)
+                    )
+                    .as_bytes(),
+                );
+            }
+        };
+        if u64::from(reserved_bytes) > 0 && self.user_storage_accounts.contains_key(&account_id) {
+            self.update_available_storage(account_id, initial_storage_usage, env::storage_usage());
+        };
+    }
+
+    /// Compares the approved token balance moloch holds on the token contract with what moloch
+    /// owes: the guild bank, escrow, withdrawable balances, unprocessed proposals and withdrawals
+    /// in flight. on_reconcile logs and returns the result.
+    ///
+    /// With sweep a member moves an unaccounted surplus, such as tokens sent with a plain
+    /// ft_transfer, into the guild bank in two steps. The first sweep records the surplus. Tokens
+    /// sent with ft_transfer_call while the balance is fetched show up as surplus, so a sweep at
+    /// least SURPLUS_SWEEP_DELAY periods later measures again, once those deposits have landed,
+    /// and moves at most the smaller of the two surpluses. No withdrawal may be in flight, and
+    /// the sweep is skipped if one starts before the balance comes back.
+    pub fn reconcile(&mut self, sweep: bool) -> Promise {
+        if sweep {
+            self.assert_not_paused();
+            self.only_member();
+            assert!(
+                self.pending_withdrawals == 0,
+                "Cannot sweep while withdrawals are in flight"
+            );
+        };
+        let prepaid_gas = env::prepaid_gas();
+        ext_fungible_token::ft_balance_of(
+            env::current_account_id(),
+            &self.token_id,
+            0,
+            prepaid_gas / 3,
+        )
+        .then(ext_self::on_reconcile(
+            sweep,
+            self.withdrawal_nonce.into(),
+            &env::current_account_id(),
+            0,
+            prepaid_gas / 3,
+        ))
+    }
+
+    /// Reports the reconciliation. When asked to sweep and no withdrawal started in the meantime,
+    /// records the surplus, or sweeps a surplus recorded long enough ago into the guild bank
+    #[private]
+    pub fn on_reconcile(&mut self, sweep: bool, withdrawal_nonce: U64) -> Reconciliation {
+        let balance = match env::promise_result(0) {
+            PromiseResult::Successful(result) => {
+                match near_sdk::serde_json::from_slice::<U128>(&result) {
+                    Ok(balance) => u128::from(balance),
+                    Err(_) => panic!("Approved token returned an invalid balance"),
+                }
+            }
+            _ => panic!("Could not get the balance from the approved token"),
+        };
+        let liabilities = u128::from(self.get_token_liabilities());
+        let surplus = balance.saturating_sub(liabilities);
+        let deficit = liabilities.saturating_sub(balance);
+        let mut swept = 0;
+        if sweep {
+            if self.pending_withdrawals != 0 || u64::from(withdrawal_nonce) != self.withdrawal_nonce
+            {
+                env::log(b"A withdrawal started during the reconciliation, skipping the sweep");
+            } else if surplus == 0 {
+                self.recorded_surplus = None;
+            } else {
+                let current_period = u64::from(self.get_current_period());
+                match self.recorded_surplus {
+                    Some((recorded, period))
+                        if current_period >= period.saturating_add(SURPLUS_SWEEP_DELAY) =>
+                    {
+                        swept = min(recorded, surplus);
+                        self.bank.deposit(swept);
+                        self.recorded_surplus = None;
+                    }
+                    Some((_, period)) => env::log(
+                        format!(
+                            "Surplus was recorded in period {}, it can be swept from period {}",
+                            period,
+                            period.saturating_add(SURPLUS_SWEEP_DELAY)
+                        )
+                        .as_bytes(),
+                    ),
+                    None => self.recorded_surplus = Some((surplus, current_period)),
+                };
+            };
+        };
+        env::log(
+            format!(
+                "Reconciled! token: {}, balance: {}, liabilities: {}, surplus: {}, deficit: {}, swept: {}",
+                self.token_id, balance, liabilities, surplus, deficit, swept
+            )
+            .as_bytes(),
+        );
+        Reconciliation {
+            balance: balance.into(),
+            guild_bank: self.bank.get_balance().into(),
+            escrow: self.escrow.total_balance().into(),
+            withdrawable: self.ledger.token_total().into(),
+            locked: self.escrow.total_locked().into(),
+            pending_withdrawals: self.pending_withdrawals.into(),
+            surplus: surplus.into(),
+            deficit: deficit.into(),
+            recorded_surplus: match self.recorded_surplus {
+                Some((recorded, _)) => recorded.into(),
+                None => 0.into(),
+            },
+            sweepable_from_period: match self.recorded_surplus {
+                Some((_, period)) => Some(period.saturating_add(SURPLUS_SWEEP_DELAY).into()),
+                None => None,
+            },
+            swept: swept.into(),
+        }
+    }
+
+    /// Deposit the attached NEAR into escrow so it can be offered as tribute.
+    ///
+    /// The NEAR is credited to account_id, or to the caller when it is omitted. Escrowed NEAR is
+    /// tracked apart from storage balances and can never be used to pay for storage. The caller
+    /// pays for any new escrow entry out of their storage balance, and when depositing for
+    /// another account is refunded once that account takes the storage over or frees it.
+    #[payable]
+    pub fn escrow_deposit_near(&mut self, account_id: Option<AccountId>) -> U128 {
+        let initial_storage_usage = env::storage_usage();
+        self.assert_active();
+        self.assert_not_paused();
+        let amount = env::attached_deposit();
+        assert!(amount > 0, "Attached deposit must be greater than 0");
+        let beneficiary_id = match account_id {
+            Some(account_id) => account_id,
+            None => env::predecessor_account_id(),
+        };
+        assert!(
+            self.user_storage_accounts.contains_key(&beneficiary_id),
+            "Beneficiary {} has not registered storage",
+            beneficiary_id
+        );
+        let balance = self.escrow.deposit_near(beneficiary_id.to_string(), amount);
+        self.record_statement(
+            &beneficiary_id,
+            StatementKind::Deposit,
+            NEAR_TOKEN_ID,
+            amount,
+            None,
+        );
+        env::log(
+            format!(
+                "Escrow NEAR deposit! sender: {}, beneficiary: {}, amount: {}",
+                env::predecessor_account_id(),
+                beneficiary_id,
+                amount
+            )
+            .as_bytes(),
+        );
+        self.sponsor_storage(
+            env::predecessor_account_id(),
+            &beneficiary_id,
+            initial_storage_usage,
+        );
+        balance.into()
+    }
+
+    /// Moves NEAR out of the caller's escrow into their withdrawable balance, from where
+    /// withdraw_balance pays it out
+    pub fn escrow_withdraw_near(&mut self, amount: U128) {
+        let initial_storage_usage = env::storage_usage();
+        let account_id = env::predecessor_account_id();
+        self.escrow
+            .withdraw_near(account_id.to_string(), amount.into());
+        self.ledger.credit(
+            account_id.to_string(),
+            NEAR_TOKEN_ID.to_string(),
+            amount.into(),
+        );
+        self.record_statement(
+            &account_id,
+            StatementKind::EscrowWithdrawal,
+            NEAR_TOKEN_ID,
+            amount.into(),
+            None,
+        );
+        self.update_available_storage(account_id, initial_storage_usage, env::storage_usage());
+    }
+
+    /// The guardian can pause the DAO when a bug or token exploit is found. While paused every
+    /// call fails except rage_quit and withdrawals, so members can still exit.
+    pub fn pause(&mut self) {
+        self.only_guardian();
+        assert!(!self.paused, "Moloch is already paused");
+        self.paused = true;
+        self.paused_at_period = self.get_current_period().into();
+        env::log(format!("Paused by {}", env::predecessor_account_id()).as_bytes());
+    }
+
+    /// The guardian lifts a pause
+    pub fn unpause(&mut self) {
+        self.only_guardian();
+        assert!(self.paused, "Moloch is not paused");
+        self.paused = false;
+        env::log(format!("Unpaused by {}", env::predecessor_account_id()).as_bytes());
+    }
+
+    // Getter functions
 
     /// The difference between the block_timestamp and the summoning_time is used to figure out how
-    /// many periods have elapsed and thus what the current period is.
+    /// many periods have elapsed and thus what the current period is. With the block height clock
+    /// the difference between the block height and the summoning block height is used instead.
     pub fn get_current_period(&self) -> U64 {
-        let period_64 = env::block_timestamp().saturating_sub(self.summoning_time);
+        let period_64 = match self.period_clock {
+            PeriodClock::Timestamp => env::block_timestamp().saturating_sub(self.summoning_time),
+            PeriodClock::BlockHeight => {
+                env::block_index().saturating_sub(self.summoning_block_height)
+            }
+        };
         period_64.wrapping_div(self.period_duration).into()
     }
 
+    /// Returns what periods are measured in
+    pub fn get_period_clock(&self) -> PeriodClock {
+        self.period_clock
+    }
+
     /// Returns the length of the proposal queue
     pub fn get_proposal_queue_length(&self) -> U64 {
         return self.proposal_queue.len().into();
     }
 
+    /// Returns a proposal waiting for the applicant's pledge
+    pub fn get_pending_proposal(&self, pending_index: U64) -> Option<Proposal> {
+        self.pending_proposals.get(&pending_index.into())
+    }
+
+    /// NEP-141 view of a member's shares, so wallets and explorers can show them
+    pub fn ft_balance_of(&self, account_id: AccountId) -> U128 {
+        match self.members.get(&account_id) {
+            Some(member) => member.shares.into(),
+            None => 0.into(),
+        }
+    }
+
+    /// NEP-141 view of the total shares
+    pub fn ft_total_supply(&self) -> U128 {
+        self.total_shares.into()
+    }
+
+    /// NEP-148 metadata describing shares as a token without decimals
+    pub fn ft_metadata(&self) -> FungibleTokenMetadata {
+        FungibleTokenMetadata {
+            spec: FT_METADATA_SPEC.to_string(),
+            name: format!("{} shares", env::current_account_id()),
+            symbol: SHARES_SYMBOL.to_string(),
+            icon: None,
+            reference: None,
+            reference_hash: None,
+            decimals: 0,
+        }
+    }
+
+    /// Shares are non-transferable, they are only granted by proposals and burned by rage quits
+    #[payable]
+    #[allow(unused_variables)]
+    pub fn ft_transfer(&mut self, receiver_id: AccountId, amount: U128, memo: Option<String>) {
+        panic!("Shares are non-transferable");
+    }
+
+    /// Shares are non-transferable, they are only granted by proposals and burned by rage quits
+    #[payable]
+    #[allow(unused_variables)]
+    pub fn ft_transfer_call(
+        &mut self,
+        receiver_id: AccountId,
+        amount: U128,
+        memo: Option<String>,
+        msg: String,
+    ) -> U128 {
+        panic!("Shares are non-transferable");
+    }
+
+    /// Returns whether a proposal has been processed and passed, so other contracts such as a
+    /// minion can act only on passed proposals
+    pub fn get_proposal_status(&self, proposal_index: U64) -> ProposalStatus {
+        let proposal = self
+            .proposal_queue
+            .get(proposal_index.into())
+            .expect("Proposal does not exist");
+        ProposalStatus {
+            proposer: proposal.proposer,
+            processed: proposal.processed,
+            did_pass: proposal.did_pass,
+            aborted: proposal.aborted,
+            starting_period: proposal.starting_period.into(),
+            current_period: self.get_current_period(),
+            details: proposal.details,
+        }
+    }
+
     /// Returns true if the highest_index_yes_vote has been processed
     pub fn can_rage_quit(&self, highest_index_yes_vote: U64) -> bool {
         let _highest_index_yes_vote = u64::from(highest_index_yes_vote);
@@ -914,63 +2897,523 @@ impl Moloch {
         };
     }
 
-    /// Get a users escrow balance
-    pub fn get_escrow_user_balance(&self, account_id: AccountId) -> U128 {
-        self.escrow.user_balance(account_id).into()
+    /// Returns a member's delegate key, shares, highest yes vote and profile
+    pub fn get_member(&self, member_id: AccountId) -> Option<MemberInfo> {
+        match self.members.get(&member_id) {
+            Some(member) => Some(MemberInfo {
+                delegate_key: member.delegate_key,
+                shares: member.shares.into(),
+                highest_index_yes_vote: member.highest_index_yes_vote.into(),
+                profile: member.profile,
+            }),
+            None => None,
+        }
     }
 
-    /// Get the guild bank balance
-    pub fn get_bank_balance(&self) -> U128 {
-        self.bank.get_balance().into()
+    /// Returns the scoped delegate keys of a member
+    pub fn get_scoped_delegate_keys(&self, member_id: AccountId) -> Vec<ScopedKey> {
+        match self.members.get(&member_id) {
+            Some(member) => member.scoped_keys,
+            None => vec![],
+        }
     }
 
-    /// Checks that previous caller is the delegate key of a
-    /// member with at least 1 share
-    fn only_delegate(&self) {
-        let delegate_key = match self
-            .members_by_delegate_key
-            .get(&env::predecessor_account_id())
-        {
-            Some(delegate_key) => delegate_key,
-            None => "".to_string(),
+    /// Returns the member a member has delegated their votes to
+    pub fn get_vote_delegatee(&self, member_id: AccountId) -> Option<AccountId> {
+        match self.members.get(&member_id) {
+            Some(member) => member.vote_delegatee,
+            None => None,
+        }
+    }
+
+    /// Returns the weight a member's vote currently carries, their own shares plus the shares of
+    /// everyone delegating to them. A member who has delegated has no weight of their own.
+    pub fn get_voting_weight(&self, member_id: AccountId) -> U128 {
+        let member = match self.members.get(&member_id) {
+            Some(member) => member,
+            None => Member::default(),
         };
-        assert!(delegate_key != "".to_string(), "Account is not a delegate");
+        assert!(member.exists, "Member does not exist");
+        if member.vote_delegatee.is_some() {
+            return 0.into();
+        };
+        let mut weight = member.shares;
+        for delegator_id in self.delegators_without_vote(&member_id, &Proposal::default()) {
+            weight = weight.saturating_add(self.members.get(&delegator_id).unwrap().shares);
+        }
+        weight.into()
     }
 
-    /// Checks that the calling account is the address of a member with at least 1 share
-    fn only_member(&self) {
-        let member = match self.members.get(&env::predecessor_account_id()) {
+    /// Returns the weight a member's own shares carry on a proposal, the shares they held at the
+    /// end of the period before the proposal's starting_period, capped at their current shares
+    pub fn get_member_voting_shares(&self, member_id: AccountId, proposal_index: U64) -> U128 {
+        let _proposal_index = u64::from(proposal_index);
+        let member = match self.members.get(&member_id) {
             Some(member) => member,
             None => Member::default(),
         };
-        assert!(member != Member::default(), "Account is not a member");
+        assert!(member.exists, "Member does not exist");
+        assert!(
+            _proposal_index < self.proposal_queue.len(),
+            "Proposal does not exist"
+        );
+        let proposal = self.proposal_queue.get(_proposal_index).unwrap();
+        member.shares_at(proposal.starting_period).into()
     }
-}
 
-#[cfg(not(target_arch = "wasm32"))]
-#[cfg(test)]
-pub mod mocks {
-    use super::*;
-    use near_sdk::test_utils::VMContextBuilder;
-    use near_sdk::VMContext;
-    use std::convert::TryInto;
+    /// Returns the period and shares of each change to a member's shares, oldest first
+    pub fn get_member_share_history(&self, member_id: AccountId) -> Vec<(U64, U128)> {
+        let member = match self.members.get(&member_id) {
+            Some(member) => member,
+            None => Member::default(),
+        };
+        assert!(member.exists, "Member does not exist");
+        member
+            .share_checkpoints
+            .iter()
+            .map(|checkpoint| (checkpoint.period.into(), checkpoint.shares.into()))
+            .collect()
+    }
 
-    pub fn get_context(is_view: bool) -> VMContext {
-        VMContextBuilder::new()
-            .signer_account_id(bob().try_into().unwrap())
-            .is_view(is_view)
-            .build()
+    /// Get a users escrow balance
+    pub fn get_escrow_user_balance(&self, account_id: AccountId) -> U128 {
+        self.escrow.user_balance(account_id).into()
     }
 
-    pub fn get_context_builder(is_view: bool) -> VMContextBuilder {
-        VMContextBuilder::new()
-            .signer_account_id(bob().try_into().unwrap())
-            .is_view(is_view)
-            .clone()
+    /// Get escrow token balances from the from'th account with a balance, at most limit of them
+    pub fn get_escrow_balances(&self, from: U64, limit: U64) -> Vec<(AccountId, U128)> {
+        self.escrow
+            .balances(from.into(), limit.into())
+            .into_iter()
+            .map(|(account_id, balance)| (account_id, balance.into()))
+            .collect()
     }
 
-    pub fn bob() -> AccountId {
-        "bob.near".to_string()
+    /// Get the tokens held in escrow, including locks
+    pub fn get_total_escrow_balance(&self) -> U128 {
+        self.escrow
+            .total_balance()
+            .saturating_add(self.escrow.total_locked())
+            .into()
+    }
+
+    /// Get the most recent movements of an account's escrow and withdrawable balances, oldest
+    /// first
+    pub fn get_account_statement(&self, account_id: AccountId) -> Vec<StatementEntry> {
+        self.statements.statement(&account_id)
+    }
+
+    /// Get the escrow locks an account has funds in, the deposits and tribute held for proposals
+    pub fn get_escrow_locks(&self, account_id: AccountId) -> Vec<EscrowLockInfo> {
+        self.escrow
+            .user_locks(account_id)
+            .into_iter()
+            .map(EscrowLockInfo::from)
+            .collect()
+    }
+
+    /// Get a users escrow NEAR balance
+    pub fn get_escrow_user_near_balance(&self, account_id: AccountId) -> U128 {
+        self.escrow.user_near_balance(account_id).into()
+    }
+
+    /// Get the balance of a token an account can withdraw, "NEAR" is used for native NEAR
+    pub fn get_withdrawable_balance(&self, account_id: AccountId, token_id: AccountId) -> U128 {
+        self.ledger.balance_of(account_id, token_id).into()
+    }
+
+    /// Get the guild bank balance
+    pub fn get_bank_balance(&self) -> U128 {
+        self.bank.get_balance().into()
+    }
+
+    /// Get what moloch owes in the approved token: the guild bank, escrow including locks,
+    /// withdrawable balances and withdrawals in flight. reconcile compares it with the balance on
+    /// the token contract.
+    pub fn get_token_liabilities(&self) -> U128 {
+        self.bank
+            .get_balance()
+            .saturating_add(self.escrow.total_balance())
+            .saturating_add(self.ledger.token_total())
+            .saturating_add(self.escrow.total_locked())
+            .saturating_add(self.pending_withdrawals)
+            .into()
+    }
+
+    /// Get the guild bank NEAR balance
+    pub fn get_bank_near_balance(&self) -> U128 {
+        self.bank.get_near_balance().into()
+    }
+
+    /// Get the NFTs a user holds in escrow
+    pub fn get_escrow_user_nfts(&self, account_id: AccountId) -> Vec<Nft> {
+        self.escrow.user_nfts(account_id)
+    }
+
+    /// Get limit NFTs held in the guild bank vault starting at from
+    pub fn get_bank_nfts(&self, from: U64, limit: U64) -> Vec<Nft> {
+        self.bank.get_nfts(from.into(), limit.into())
+    }
+
+    /// Returns how many vault NFTs a former member can still claim
+    pub fn get_nft_claims(&self, account_id: AccountId) -> U64 {
+        self.bank.nft_claims(&account_id).into()
+    }
+
+    /// Returns the token and NEAR tribute applicant allows proposer to pledge
+    pub fn get_tribute_authorization(
+        &self,
+        applicant: AccountId,
+        proposer: AccountId,
+    ) -> (U128, U128) {
+        match self.tribute_authorizations.get(&(applicant, proposer)) {
+            Some(authorization) => (
+                authorization.token_tribute.into(),
+                authorization.near_tribute.into(),
+            ),
+            None => (0.into(), 0.into()),
+        }
+    }
+
+    /// Returns true once the approved token has been confirmed
+    pub fn is_active(&self) -> bool {
+        self.token_info.is_some()
+    }
+
+    /// Returns the approved token's symbol and decimals, None while the DAO is inactive
+    pub fn get_token_info(&self) -> Option<TokenInfo> {
+        self.token_info.clone()
+    }
+
+    /// Returns true while the DAO is paused
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Returns the current guardian, if there is one
+    pub fn get_guardian(&self) -> Option<AccountId> {
+        self.guardian.clone()
+    }
+
+    /// Returns every member whose voting weight reaches member_id through a chain of delegations
+    /// and who has not voted on the proposal
+    fn delegators_without_vote(
+        &self,
+        member_id: &AccountId,
+        proposal: &Proposal,
+    ) -> Vec<AccountId> {
+        let mut delegators = vec![];
+        let mut to_visit = self.members.get(member_id).unwrap().vote_delegators;
+        while let Some(delegator_id) = to_visit.pop() {
+            let delegator = self.members.get(&delegator_id).unwrap();
+            to_visit.extend(delegator.vote_delegators.into_iter());
+            if !proposal.votes_by_member.contains_key(&delegator_id) {
+                delegators.push(delegator_id);
+            };
+        }
+        delegators
+    }
+
+    /// Removes a member's vote delegation, returns false if they had not delegated
+    fn remove_vote_delegation(&mut self, member_id: &AccountId) -> bool {
+        let mut member = self.members.get(member_id).unwrap();
+        let delegatee_id = match member.vote_delegatee.take() {
+            Some(delegatee_id) => delegatee_id,
+            None => return false,
+        };
+        let mut delegatee = self.members.get(&delegatee_id).unwrap();
+        delegatee
+            .vote_delegators
+            .retain(|delegator_id| delegator_id != member_id);
+        self.members.insert(&delegatee_id, &delegatee);
+        self.members.insert(member_id, &member);
+        true
+    }
+
+    /// The period a newly submitted proposal starts voting in, the next period or the one after
+    /// the last proposal in the queue
+    fn next_starting_period(&self) -> u64 {
+        let period_based_on_queue = match self
+            .proposal_queue
+            .get(self.proposal_queue.len().saturating_sub(1))
+        {
+            Some(proposal) => proposal.starting_period,
+            None => 0,
+        };
+        max(u64::from(self.get_current_period()), period_based_on_queue).saturating_add(1)
+    }
+
+    /// Uses the applicant's authorization for proposer to pledge the tribute, returns false if
+    /// it doesn't cover it
+    fn use_tribute_authorization(
+        &mut self,
+        applicant: &AccountId,
+        proposer: &AccountId,
+        token_tribute: u128,
+        near_tribute: u128,
+        nft_tribute: &Vec<Nft>,
+    ) -> bool {
+        let key = (applicant.to_string(), proposer.to_string());
+        let mut authorization = match self.tribute_authorizations.get(&key) {
+            Some(authorization) => authorization,
+            None => return false,
+        };
+        if !nft_tribute.is_empty()
+            || authorization.token_tribute < token_tribute
+            || authorization.near_tribute < near_tribute
+        {
+            return false;
+        };
+        authorization.token_tribute -= token_tribute;
+        authorization.near_tribute -= near_tribute;
+        if authorization.token_tribute == 0 && authorization.near_tribute == 0 {
+            self.tribute_authorizations.remove(&key);
+        } else {
+            self.tribute_authorizations.insert(&key, &authorization);
+        };
+        true
+    }
+
+    /// Locks a proposal's tribute in the applicant's escrow and takes its NFTs
+    fn pull_tribute(&mut self, proposal_index: u64, proposal: &Proposal) {
+        self.escrow.lock(
+            proposal_index,
+            proposal.applicant.to_string(),
+            proposal.token_tribute,
+            proposal.near_tribute,
+            LockPurpose::Tribute,
+        );
+        if let Some(tribute) = self.escrow.get_lock(proposal_index, LockPurpose::Tribute) {
+            self.record_lock(&tribute, StatementKind::TributeLock);
+        };
+        for nft in proposal.nft_tribute.iter() {
+            self.escrow
+                .withdraw_nft(proposal.applicant.to_string(), nft);
+        }
+    }
+
+    /// Releases a proposal's tribute lock and returns its NFTs to the applicant's escrow
+    fn return_tribute(&mut self, proposal_index: u64, proposal: &Proposal) {
+        if let Some(tribute) = self.escrow.refund(proposal_index, LockPurpose::Tribute) {
+            self.record_lock(&tribute, StatementKind::Refund);
+        };
+        for nft in proposal.nft_tribute.iter() {
+            self.escrow
+                .deposit_nft(proposal.applicant.to_string(), nft.clone());
+        }
+    }
+
+    /// Adds an entry to a registered account's statement. Unregistered accounts, like keepers
+    /// collecting processing rewards, keep no statement.
+    fn record_statement(
+        &mut self,
+        account_id: &AccountId,
+        kind: StatementKind,
+        token_id: &str,
+        amount: u128,
+        proposal_index: Option<u64>,
+    ) {
+        if amount == 0 || !self.user_storage_accounts.contains_key(account_id) {
+            return;
+        };
+        self.statements.record(
+            account_id,
+            kind,
+            token_id.to_string(),
+            amount,
+            proposal_index,
+        );
+    }
+
+    /// Adds the token and NEAR amounts of a lock to its owner's statement
+    fn record_lock(&mut self, lock: &EscrowLock, kind: StatementKind) {
+        let token_id = self.token_id.to_string();
+        self.record_statement(
+            &lock.owner,
+            kind,
+            &token_id,
+            lock.amount,
+            Some(lock.proposal_index),
+        );
+        self.record_statement(
+            &lock.owner,
+            kind,
+            NEAR_TOKEN_ID,
+            lock.near_amount,
+            Some(lock.proposal_index),
+        );
+    }
+
+    /// Checks that the approved token has been confirmed
+    fn assert_active(&self) {
+        assert!(
+            self.token_info.is_some(),
+            "Moloch is inactive until the approved token is confirmed"
+        );
+    }
+
+    /// Burns a member's shares and credits them their proportional share of the guild bank's
+    /// token and NEAR, plus claims on the vault NFTs. Shared by rage quit and guild kick.
+    fn burn_shares(&mut self, account_id: &AccountId, shares: u128, kind: StatementKind) {
+        let mut member = self.members.get(account_id).unwrap();
+        let initial_total_shares = self.total_shares;
+        member.shares = member.shares.saturating_sub(shares);
+        member.checkpoint_shares(u64::from(self.get_current_period()));
+        // A member who leaves fully leaves their profile behind
+        if member.shares == 0 {
+            member.profile = None;
+        };
+        self.total_shares = self.total_shares.saturating_sub(shares);
+        self.members.insert(account_id, &member);
+        let memo = match kind {
+            StatementKind::GuildKick => "Guild kick",
+            _ => "Rage quit",
+        };
+        log_share_event("ft_burn", account_id, shares, memo);
+
+        let (amount, near_amount) =
+            self.bank
+                .withdraw(account_id.to_string(), shares, initial_total_shares);
+        self.ledger
+            .credit(account_id.to_string(), self.token_id.to_string(), amount);
+        self.ledger.credit(
+            account_id.to_string(),
+            NEAR_TOKEN_ID.to_string(),
+            near_amount,
+        );
+        self.record_statement(account_id, kind, &self.token_id.to_string(), amount, None);
+        self.record_statement(account_id, kind, NEAR_TOKEN_ID, near_amount, None);
+
+        let nft_claims =
+            self.bank
+                .grant_nft_claims(account_id.to_string(), shares, initial_total_shares);
+        if nft_claims > 0 {
+            env::log(
+                format!(
+                    "NFT claims granted! account: {}, claims: {}",
+                    account_id, nft_claims
+                )
+                .as_bytes(),
+            );
+        };
+    }
+
+    /// Returns true once the DAO has been paused for a voting and a grace period
+    fn is_pause_exit_open(&self) -> bool {
+        let exit_period = self
+            .paused_at_period
+            .saturating_add(self.voting_period_length)
+            .saturating_add(self.grace_period_length);
+        self.paused && u64::from(self.get_current_period()) >= exit_period
+    }
+
+    /// Removes a member's profile and refunds its storage to the member, who paid for it.
+    /// Returns the bytes refunded.
+    fn remove_member_profile(&mut self, account_id: &AccountId) -> u64 {
+        let initial_storage_usage = env::storage_usage();
+        let mut member = self.members.get(account_id).unwrap();
+        if member.profile.is_none() || !self.user_storage_accounts.contains_key(account_id) {
+            return 0;
+        };
+        member.profile = None;
+        self.members.insert(account_id, &member);
+        let storage_used = env::storage_usage();
+        self.update_available_storage(account_id.to_string(), initial_storage_usage, storage_used);
+        initial_storage_usage - storage_used
+    }
+
+    /// Checks that the DAO is not paused
+    fn assert_not_paused(&self) {
+        assert!(
+            !self.paused,
+            "Moloch is paused, only rage_quit and withdrawals are available"
+        );
+    }
+
+    /// Checks that the calling account is the guardian
+    fn only_guardian(&self) {
+        assert!(
+            self.guardian == Some(env::predecessor_account_id()),
+            "Only the guardian can pause or unpause"
+        );
+    }
+
+    /// Checks that previous caller is the delegate key of a
+    /// member with at least 1 share
+    fn only_delegate(&self) {
+        let delegate_key = match self
+            .members_by_delegate_key
+            .get(&env::predecessor_account_id())
+        {
+            Some(delegate_key) => delegate_key,
+            None => "".to_string(),
+        };
+        assert!(delegate_key != "".to_string(), "Account is not a delegate");
+    }
+
+    /// Checks that a scoped delegate key calling for its member allows the action and has not
+    /// expired. The member's delegate_key can do everything.
+    fn assert_delegate_scope(&self, scope: KeyScope) {
+        let delegate_key = env::predecessor_account_id();
+        let member_id = self.members_by_delegate_key.get(&delegate_key).unwrap();
+        let member = self.members.get(&member_id).unwrap();
+        let scoped_key = match member
+            .scoped_keys
+            .into_iter()
+            .find(|key| key.account_id == delegate_key)
+        {
+            Some(scoped_key) => scoped_key,
+            None => return,
+        };
+        assert!(
+            scoped_key.scope.allows(scope),
+            "Delegate key {} is not allowed to {:?}",
+            delegate_key,
+            scope
+        );
+        if let Some(expires_at_period) = scoped_key.expires_at_period {
+            assert!(
+                u64::from(self.get_current_period()) < expires_at_period,
+                "Delegate key {} has expired",
+                delegate_key
+            );
+        };
+    }
+
+    /// Checks that the calling account is the address of a member with at least 1 share
+    fn only_member(&self) {
+        let member = match self.members.get(&env::predecessor_account_id()) {
+            Some(member) => member,
+            None => Member::default(),
+        };
+        assert!(member != Member::default(), "Account is not a member");
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+pub mod mocks {
+    use super::*;
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::VMContext;
+    use std::convert::TryInto;
+
+    pub fn get_context(is_view: bool) -> VMContext {
+        VMContextBuilder::new()
+            .signer_account_id(bob().try_into().unwrap())
+            .is_view(is_view)
+            .build()
+    }
+
+    pub fn get_context_builder(is_view: bool) -> VMContextBuilder {
+        VMContextBuilder::new()
+            .signer_account_id(bob().try_into().unwrap())
+            .is_view(is_view)
+            .clone()
+    }
+
+    pub fn bob() -> AccountId {
+        "bob.near".to_string()
     }
 
     pub fn robert() -> AccountId {
@@ -1027,6 +3470,14 @@ pub mod mocks {
                 shares: self.shares,
                 exists: true,
                 highest_index_yes_vote: self.highest_index_yes_vote,
+                vote_delegatee: None,
+                vote_delegators: vec![],
+                share_checkpoints: vec![ShareCheckpoint {
+                    period: 0,
+                    shares: self.shares,
+                }],
+                scoped_keys: vec![],
+                profile: None,
             }
         }
     }
@@ -1052,12 +3503,20 @@ pub mod mocks {
         aborted: bool,
         /// Amount of tokens offered as tribute
         token_tribute: u128,
+        /// Amount of NEAR offered as tribute
+        near_tribute: u128,
+        /// NFTs offered as tribute
+        nft_tribute: Vec<Nft>,
         /// The proposal details - could be an IPFS hash, plaintext, or JSON
         details: String,
         /// The maximum number of total shares encountered at a yes vote on this proposal
         max_total_shares_at_yes_vote: u128,
         /// Mapping of votes for each member
         votes_by_member: HashMap<AccountId, Vote>,
+        /// What the proposal does when it passes
+        kind: ProposalKind,
+        /// true once the applicant has consented and the tribute is held by the proposal
+        tribute_pledged: bool,
     }
 
     impl MockProposal {
@@ -1073,9 +3532,13 @@ pub mod mocks {
                 did_pass: false,
                 aborted: false,
                 token_tribute: 12,
+                near_tribute: 0,
+                nft_tribute: vec![],
                 details: "".to_string(),
                 max_total_shares_at_yes_vote: 0,
                 votes_by_member: HashMap::new(),
+                kind: ProposalKind::Membership,
+                tribute_pledged: true,
             }
         }
 
@@ -1089,6 +3552,11 @@ pub mod mocks {
             self
         }
 
+        pub fn did_pass(&mut self, did_pass: bool) -> &mut Self {
+            self.did_pass = did_pass;
+            self
+        }
+
         pub fn shares_requested(&mut self, shares_requested: u128) -> &mut Self {
             self.shares_requested = shares_requested;
             self
@@ -1104,6 +3572,26 @@ pub mod mocks {
             self
         }
 
+        pub fn near_tribute(&mut self, near_tribute: u128) -> &mut Self {
+            self.near_tribute = near_tribute;
+            self
+        }
+
+        pub fn nft_tribute(&mut self, nft: Nft) -> &mut Self {
+            self.nft_tribute.push(nft);
+            self
+        }
+
+        pub fn kind(&mut self, kind: ProposalKind) -> &mut Self {
+            self.kind = kind;
+            self
+        }
+
+        pub fn tribute_pledged(&mut self, tribute_pledged: bool) -> &mut Self {
+            self.tribute_pledged = tribute_pledged;
+            self
+        }
+
         pub fn yes_vote(&mut self, member: &Member) -> &mut Self {
             self.yes_votes += member.shares;
             self.votes_by_member
@@ -1135,23 +3623,30 @@ pub mod mocks {
                 did_pass: self.did_pass,
                 aborted: self.aborted,
                 token_tribute: self.token_tribute,
+                near_tribute: self.near_tribute,
+                nft_tribute: self.nft_tribute.clone(),
                 details: self.details.to_string(),
                 max_total_shares_at_yes_vote: self.max_total_shares_at_yes_vote,
                 votes_by_member: votes_by_member,
+                kind: self.kind.clone(),
+                tribute_pledged: self.tribute_pledged,
             }
         }
     }
 
     pub struct MockMoloch {
-        summoner: AccountId,
+        founders: Vec<Founder>,
         approved_token: AccountId,
         period_duration: U64,
+        period_clock: PeriodClock,
         voting_period_length: U64,
         grace_period_length: U64,
         abort_window: U64,
         proposal_deposit: U128,
         dilution_bound: U128,
         processing_reward: U128,
+        abort_penalty: U128,
+        guardian: Option<AccountId>,
         proposal_queue: Vector<Proposal>,
         total_shares_requested: u128,
         total_shares: u128,
@@ -1160,21 +3655,26 @@ pub mod mocks {
         user_storage_accounts: UnorderedMap<AccountId, UserStorageBalance>,
         min_account_storage_usage: u64,
         user_balances: UnorderedMap<AccountId, u128>,
+        tribute_authorizations: Vec<(AccountId, AccountId, u128, u128)>,
+        pending_proposals: Vec<Proposal>,
     }
 
     impl MockMoloch {
         pub fn new() -> Self {
             MockMoloch {
-                summoner: bob(),
+                founders: vec![Founder::new(bob(), 1.into(), None)],
                 approved_token: fdai(),
                 // period_duration 30 seconds
                 period_duration: 10u64.pow(9).into(),
+                period_clock: PeriodClock::Timestamp,
                 voting_period_length: 3.into(),
                 grace_period_length: 2.into(),
                 abort_window: 1.into(),
                 proposal_deposit: 100.into(),
                 dilution_bound: 10.into(),
                 processing_reward: 10.into(),
+                abort_penalty: 0.into(),
+                guardian: None,
                 proposal_queue: Vector::new(b"proposal_queue".to_vec()),
                 total_shares_requested: 0,
                 total_shares: 0,
@@ -1185,6 +3685,8 @@ pub mod mocks {
                 user_storage_accounts: UnorderedMap::new(b"mock_user_storage_account".to_vec()),
                 min_account_storage_usage: 5,
                 user_balances: UnorderedMap::new(b"mock_user_balances".to_vec()),
+                tribute_authorizations: vec![],
+                pending_proposals: vec![],
             }
         }
 
@@ -1193,8 +3695,45 @@ pub mod mocks {
             self
         }
 
+        pub fn period_duration(&mut self, period_duration: u64) -> &mut Self {
+            self.period_duration = period_duration.into();
+            self
+        }
+
+        pub fn period_clock(&mut self, period_clock: PeriodClock) -> &mut Self {
+            self.period_clock = period_clock;
+            self
+        }
+
+        pub fn abort_penalty(&mut self, abort_penalty: u128) -> &mut Self {
+            self.abort_penalty = abort_penalty.into();
+            self
+        }
+
+        pub fn guardian(&mut self, guardian: AccountId) -> &mut Self {
+            self.guardian = Some(guardian);
+            self
+        }
+
+        pub fn authorize_tribute(
+            &mut self,
+            applicant: AccountId,
+            proposer: AccountId,
+            token_tribute: u128,
+            near_tribute: u128,
+        ) -> &mut Self {
+            self.tribute_authorizations
+                .push((applicant, proposer, token_tribute, near_tribute));
+            self
+        }
+
         pub fn summoner(&mut self, summoner: AccountId) -> &mut Self {
-            self.summoner = summoner;
+            self.founders = vec![Founder::new(summoner, 1.into(), None)];
+            self
+        }
+
+        pub fn add_founder(&mut self, founder: Founder) -> &mut Self {
+            self.founders.push(founder);
             self
         }
 
@@ -1204,6 +3743,12 @@ pub mod mocks {
             self
         }
 
+        pub fn add_pending_proposal(&mut self, proposal: Proposal) -> &mut Self {
+            self.total_shares_requested += proposal.shares_requested;
+            self.pending_proposals.push(proposal);
+            self
+        }
+
         pub fn add_member(&mut self, member: Member) -> &mut Self {
             self.members_by_delegate_key
                 .insert(&member.delegate_key, &member.delegate_key);
@@ -1224,6 +3769,7 @@ pub mod mocks {
                 &UserStorageBalance {
                     total: total,
                     available: available,
+                    used_bytes: 0,
                 },
             );
             self
@@ -1254,17 +3800,69 @@ pub mod mocks {
 
         pub fn build(&self) -> Moloch {
             let mut moloch = Moloch::new(
-                self.summoner.to_string(),
+                self.founders.clone(),
                 self.approved_token.to_string(),
                 self.period_duration,
+                self.period_clock,
                 self.voting_period_length,
                 self.grace_period_length,
                 self.abort_window,
                 self.proposal_deposit,
                 self.dilution_bound,
                 self.processing_reward,
+                self.abort_penalty,
+                self.guardian.clone(),
             );
             moloch.proposal_queue.extend(self.proposal_queue.iter());
+            // Lock the funds each unprocessed proposal holds
+            let proposal_deposit = u128::from(self.proposal_deposit);
+            for (index, proposal) in self.proposal_queue.iter().enumerate() {
+                if proposal.processed {
+                    continue;
+                };
+                let index = index as u64;
+                moloch
+                    .escrow
+                    .deposit(proposal.proposer.to_string(), proposal_deposit);
+                moloch.escrow.lock(
+                    index,
+                    proposal.proposer.to_string(),
+                    proposal_deposit,
+                    0,
+                    LockPurpose::Deposit,
+                );
+                if proposal.tribute_pledged {
+                    moloch
+                        .escrow
+                        .deposit(proposal.applicant.to_string(), proposal.token_tribute);
+                    moloch
+                        .escrow
+                        .deposit_near(proposal.applicant.to_string(), proposal.near_tribute);
+                    moloch.escrow.lock(
+                        index,
+                        proposal.applicant.to_string(),
+                        proposal.token_tribute,
+                        proposal.near_tribute,
+                        LockPurpose::Tribute,
+                    );
+                };
+            }
+            // Lock the deposit each pending proposal holds
+            for (index, proposal) in self.pending_proposals.iter().enumerate() {
+                let index = index as u64;
+                moloch
+                    .escrow
+                    .deposit(proposal.proposer.to_string(), proposal_deposit);
+                moloch.escrow.lock(
+                    index,
+                    proposal.proposer.to_string(),
+                    proposal_deposit,
+                    0,
+                    LockPurpose::PendingDeposit,
+                );
+                moloch.pending_proposals.insert(&index, proposal);
+            }
+            moloch.pending_proposal_count = self.pending_proposals.len() as u64;
             moloch.total_shares_requested += self.total_shares_requested;
             moloch.total_shares += self.total_shares;
             moloch.members.extend(self.members.iter());
@@ -1272,12 +3870,27 @@ pub mod mocks {
                 .user_storage_accounts
                 .extend(self.user_storage_accounts.iter());
             moloch.min_account_storage_usage = self.min_account_storage_usage;
+            moloch.token_info = Some(TokenInfo {
+                symbol: "FDAI".to_string(),
+                decimals: 18,
+            });
             moloch
                 .members_by_delegate_key
                 .extend(self.members_by_delegate_key.iter());
             for (account_id, balance) in self.user_balances.iter() {
                 moloch.escrow.deposit(account_id, balance);
             }
+            for (applicant, proposer, token_tribute, near_tribute) in
+                self.tribute_authorizations.iter()
+            {
+                moloch.tribute_authorizations.insert(
+                    &(applicant.to_string(), proposer.to_string()),
+                    &TributeAuthorization {
+                        token_tribute: *token_tribute,
+                        near_tribute: *near_tribute,
+                    },
+                );
+            }
             moloch
         }
     }
@@ -1288,1102 +3901,3710 @@ pub mod mocks {
 mod tests {
     use super::*;
     use crate::mocks::{
-        alice, bob, get_context, get_context_builder, robert, storage_deposit, MockMember,
+        alice, bob, fdai, get_context, get_context_builder, robert, storage_deposit, MockMember,
         MockMoloch, MockProposal,
     };
-    use near_sdk::{testing_env, MockedBlockchain};
+    use near_contract_standards::storage_management::StorageManagement;
+    use near_sdk::test_utils::get_logs;
+    use near_sdk::{testing_env, MockedBlockchain, RuntimeFeesConfig, VMConfig};
     use std::convert::TryInto;
 
+    fn ft_metadata_result(spec: &str, symbol: &str) -> PromiseResult {
+        let metadata = FungibleTokenMetadata {
+            spec: spec.to_string(),
+            name: "Fake DAI".to_string(),
+            symbol: symbol.to_string(),
+            icon: None,
+            reference: None,
+            reference_hash: None,
+            decimals: 18,
+        };
+        PromiseResult::Successful(near_sdk::serde_json::to_vec(&metadata).unwrap())
+    }
+
+    fn balance_result(balance: u128) -> PromiseResult {
+        PromiseResult::Successful(near_sdk::serde_json::to_vec(&U128(balance)).unwrap())
+    }
+
     /// Tests for submit propposal
     #[test]
-    fn submit_proposal() {
+    fn new_with_founders() {
         let context = get_context(false);
         testing_env!(context);
-        let mut contract = MockMoloch::new()
-            .add_escrow_deposit(robert(), 13)
-            .add_escrow_deposit(bob(), 101)
-            .register_user(bob(), storage_deposit(), storage_deposit())
+        let contract = MockMoloch::new()
+            .add_founder(Founder::new(robert(), 10.into(), Some(alice())))
             .build();
-        contract.submit_proposal(robert(), 12.into(), 10.into(), "".to_string());
-
-        let mut context_builder = get_context_builder(false);
-        testing_env!(context_builder
-            .storage_usage(env::storage_usage())
-            .attached_deposit(1)
-            .build());
-
-        let proposal = contract.proposal_queue.get(0);
-        let expected_proposal = Proposal {
-            proposer: bob(),
-            applicant: robert(),
-            shares_requested: 10,
-            starting_period: 1,
-            yes_votes: 0,
-            no_votes: 0,
-            processed: false,
-            did_pass: false,
-            aborted: false,
-            token_tribute: 12,
-            details: "".to_string(),
-            max_total_shares_at_yes_vote: 0,
-            votes_by_member: HashMap::new(),
-        };
-
-        assert_eq!(proposal.unwrap(), expected_proposal);
-        assert_eq!(contract.total_shares_requested, 10);
+        assert_eq!(contract.total_shares, 11, "Total shares is incorrect");
+        let robert_member = contract.members.get(&robert()).unwrap();
+        assert_eq!(robert_member.shares, 10);
+        assert_eq!(robert_member.delegate_key, alice());
+        assert_eq!(
+            contract.members_by_delegate_key.get(&alice()).unwrap(),
+            robert()
+        );
+        assert_eq!(contract.members_by_delegate_key.get(&bob()).unwrap(), bob());
+        assert_eq!(
+            contract.get_member_share_history(robert()),
+            vec![(U64::from(0), U128::from(10))]
+        );
     }
 
     #[test]
-    fn submit_proposal_multiple_proposals() {
+    #[should_panic(expected = r#"Duplicate founder robert.testnet"#)]
+    fn new_duplicate_founder() {
         let context = get_context(false);
         testing_env!(context);
-        let mut contract = MockMoloch::new()
-            .add_escrow_deposit(bob(), 200)
-            .add_escrow_deposit(robert(), 32)
-            .register_user(bob(), storage_deposit(), storage_deposit())
+        MockMoloch::new()
+            .add_founder(Founder::new(robert(), 10.into(), None))
+            .add_founder(Founder::new(robert(), 5.into(), Some(alice())))
+            .build();
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Duplicate delegate key alice.testnet"#)]
+    fn new_duplicate_delegate_key() {
+        let context = get_context(false);
+        testing_env!(context);
+        MockMoloch::new()
+            .add_founder(Founder::new(robert(), 10.into(), Some(alice())))
+            .add_founder(Founder::new(fdai(), 5.into(), Some(alice())))
             .build();
-        contract.submit_proposal(robert(), 12.into(), 10.into(), "".to_string());
+    }
 
+    #[test]
+    #[should_panic(expected = r#"Delegate key robert.testnet belongs to another founder"#)]
+    fn new_delegate_key_of_another_founder() {
         let context = get_context(false);
         testing_env!(context);
-        contract.submit_proposal(robert(), 20.into(), 20.into(), "".to_string());
+        MockMoloch::new()
+            .add_founder(Founder::new(alice(), 5.into(), Some(robert())))
+            .add_founder(Founder::new(robert(), 10.into(), None))
+            .build();
+    }
 
-        let proposal = contract.proposal_queue.get(1);
+    #[test]
+    #[should_panic(expected = r#"Too many founder shares: greater than max shares"#)]
+    fn new_too_many_founder_shares() {
+        let context = get_context(false);
+        testing_env!(context);
+        MockMoloch::new()
+            .add_founder(Founder::new(robert(), MAX_NUMBER_OF_SHARES.into(), None))
+            .build();
+    }
+
+    #[test]
+    fn submit_proposal() {
+        let context = get_context(false);
+        testing_env!(context);
+        let mut contract = MockMoloch::new()
+            .add_escrow_deposit(robert(), 13)
+            .add_escrow_deposit(bob(), 101)
+            .authorize_tribute(robert(), bob(), 12, 0)
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .build();
+        contract.submit_proposal(
+            robert(),
+            12.into(),
+            0.into(),
+            vec![],
+            10.into(),
+            "".to_string(),
+        );
+
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder
+            .storage_usage(env::storage_usage())
+            .attached_deposit(1)
+            .build());
+
+        let proposal = contract.proposal_queue.get(0);
         let expected_proposal = Proposal {
             proposer: bob(),
             applicant: robert(),
-            shares_requested: 20,
-            starting_period: 2,
+            shares_requested: 10,
+            starting_period: 1,
             yes_votes: 0,
             no_votes: 0,
             processed: false,
             did_pass: false,
             aborted: false,
-            token_tribute: 20,
+            token_tribute: 12,
+            near_tribute: 0,
+            nft_tribute: vec![],
             details: "".to_string(),
             max_total_shares_at_yes_vote: 0,
             votes_by_member: HashMap::new(),
+            kind: ProposalKind::Membership,
+            tribute_pledged: true,
         };
+
         assert_eq!(proposal.unwrap(), expected_proposal);
-        assert_eq!(contract.total_shares_requested, 30);
+        assert_eq!(contract.total_shares_requested, 10);
     }
 
     #[test]
-    #[should_panic(expected = r#"applicant must be a valid account id"#)]
-    fn submit_proposal_invalid_account() {
+    fn submit_proposal_locks_deposit_and_tribute() {
         let context = get_context(false);
         testing_env!(context);
-        let mut contract = MockMoloch::new().build();
-        contract.submit_proposal("".to_string(), 10.into(), 10.into(), "".to_string());
-    }
+        let mut contract = MockMoloch::new()
+            .add_escrow_deposit(robert(), 13)
+            .add_escrow_deposit(bob(), 101)
+            .authorize_tribute(robert(), bob(), 12, 0)
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .build();
+        contract.submit_proposal(
+            robert(),
+            12.into(),
+            0.into(),
+            vec![],
+            10.into(),
+            "".to_string(),
+        );
 
-    #[test]
-    #[should_panic(expected = r#"Too many shares were requested"#)]
-    fn submit_proposal_shares_requested_overflow() {
-        let context = get_context(false);
-        testing_env!(context);
-        let mut contract = MockMoloch::new().build();
-        contract.submit_proposal(robert(), 10.into(), u128::MAX.into(), "".to_string());
+        assert_eq!(
+            contract.get_escrow_locks(bob()),
+            vec![EscrowLockInfo {
+                proposal_index: 0.into(),
+                owner: bob(),
+                amount: 100.into(),
+                near_amount: 0.into(),
+                purpose: LockPurpose::Deposit,
+            }]
+        );
+        assert_eq!(
+            contract.get_escrow_locks(robert()),
+            vec![EscrowLockInfo {
+                proposal_index: 0.into(),
+                owner: robert(),
+                amount: 12.into(),
+                near_amount: 0.into(),
+                purpose: LockPurpose::Tribute,
+            }]
+        );
+        assert_eq!(u128::from(contract.get_escrow_user_balance(bob())), 1);
+        assert_eq!(u128::from(contract.get_escrow_user_balance(robert())), 1);
     }
 
     #[test]
-    #[should_panic(
-        expected = r#"Too many shares were requested: due to outstanding shares requested"#
-    )]
-    fn submit_proposal_total_shares_requested_overflow() {
+    fn submit_proposal_then_abort_statements() {
         let context = get_context(false);
         testing_env!(context);
         let mut contract = MockMoloch::new()
-            .add_escrow_deposit(bob(), 100)
-            .add_escrow_deposit(robert(), 10)
-            .total_shares_requested(u128::MAX.saturating_sub(1).into())
+            .add_escrow_deposit(robert(), 13)
+            .add_escrow_deposit(bob(), 101)
+            .authorize_tribute(robert(), bob(), 12, 0)
             .register_user(bob(), storage_deposit(), storage_deposit())
+            .register_user(robert(), storage_deposit(), storage_deposit())
             .build();
-        contract.submit_proposal(robert(), 10.into(), 1.into(), "".to_string());
+        contract.submit_proposal(
+            robert(),
+            12.into(),
+            0.into(),
+            vec![],
+            10.into(),
+            "".to_string(),
+        );
+        let bob_statement = contract.get_account_statement(bob());
+        assert_eq!(bob_statement.len(), 1);
+        assert_eq!(bob_statement[0].kind, StatementKind::DepositLock);
+        assert_eq!(u128::from(bob_statement[0].amount), 100);
+        assert_eq!(bob_statement[0].proposal_index, Some(0.into()));
+        let robert_statement = contract.get_account_statement(robert());
+        assert_eq!(robert_statement.len(), 1);
+        assert_eq!(robert_statement[0].kind, StatementKind::TributeLock);
+        assert_eq!(robert_statement[0].token_id, fdai());
+        assert_eq!(u128::from(robert_statement[0].amount), 12);
+
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder
+            .predecessor_account_id(robert().try_into().unwrap())
+            .block_timestamp(contract.summoning_time + contract.period_duration)
+            .build());
+        contract.abort(0.into());
+        let robert_statement = contract.get_account_statement(robert());
+        assert_eq!(robert_statement.len(), 2);
+        assert_eq!(robert_statement[1].kind, StatementKind::Refund);
+        assert_eq!(u128::from(robert_statement[1].amount), 12);
+        assert_eq!(
+            u64::from(robert_statement[1].timestamp),
+            contract.summoning_time + contract.period_duration
+        );
     }
+
     #[test]
-    #[should_panic(expected = r#"Too many shares were requested: greater than max shares"#)]
-    fn submit_proposal_too_many_shares_submitted() {
+    fn submit_proposal_multiple_proposals() {
         let context = get_context(false);
         testing_env!(context);
         let mut contract = MockMoloch::new()
-            .add_escrow_deposit(bob(), 100)
-            .add_escrow_deposit(robert(), 10)
+            .add_escrow_deposit(bob(), 200)
+            .add_escrow_deposit(robert(), 32)
+            .authorize_tribute(robert(), bob(), 32, 0)
             .register_user(bob(), storage_deposit(), storage_deposit())
             .build();
         contract.submit_proposal(
             robert(),
+            12.into(),
+            0.into(),
+            vec![],
             10.into(),
-            u128::MAX.saturating_sub(1).into(),
             "".to_string(),
         );
-    }
 
-    #[test]
-    #[should_panic(expected = r#"Account is not a delegate"#)]
-    fn submit_proposal_not_delegate() {
         let context = get_context(false);
         testing_env!(context);
-        let mut contract = MockMoloch::new().summoner(robert()).build();
-        contract.submit_proposal(robert(), 10.into(), 10.into(), "".to_string());
+        contract.submit_proposal(
+            robert(),
+            20.into(),
+            0.into(),
+            vec![],
+            20.into(),
+            "".to_string(),
+        );
+
+        let proposal = contract.proposal_queue.get(1);
+        let expected_proposal = Proposal {
+            proposer: bob(),
+            applicant: robert(),
+            shares_requested: 20,
+            starting_period: 2,
+            yes_votes: 0,
+            no_votes: 0,
+            processed: false,
+            did_pass: false,
+            aborted: false,
+            token_tribute: 20,
+            near_tribute: 0,
+            nft_tribute: vec![],
+            details: "".to_string(),
+            max_total_shares_at_yes_vote: 0,
+            votes_by_member: HashMap::new(),
+            kind: ProposalKind::Membership,
+            tribute_pledged: true,
+        };
+        assert_eq!(proposal.unwrap(), expected_proposal);
+        assert_eq!(contract.total_shares_requested, 30);
     }
 
-    // Single vote
     #[test]
-    fn submit_vote_yes() {
+    fn submit_proposal_without_consent() {
         let context = get_context(false);
         testing_env!(context);
-        let proposal = MockProposal::new().build();
         let mut contract = MockMoloch::new()
-            .add_proposal(proposal)
+            .add_escrow_deposit(robert(), 13)
+            .add_escrow_deposit(bob(), 101)
             .register_user(bob(), storage_deposit(), storage_deposit())
             .build();
+        contract.submit_proposal(
+            robert(),
+            12.into(),
+            0.into(),
+            vec![],
+            10.into(),
+            "".to_string(),
+        );
 
-        let mut context_builder = get_context_builder(false);
-        let context = context_builder
-            .block_timestamp((contract.summoning_time + contract.period_duration).into())
-            .build();
+        assert_eq!(
+            u64::from(contract.get_proposal_queue_length()),
+            0,
+            "Proposal without a pledge joined the queue"
+        );
+        let proposal = contract.get_pending_proposal(0.into()).unwrap();
+        assert!(!proposal.tribute_pledged, "Tribute was pledged");
+        let robert_balance = contract.get_escrow_user_balance(robert());
+        assert_eq!(
+            u128::from(robert_balance),
+            13,
+            "Tribute was taken without consent"
+        );
+        assert_eq!(
+            contract.get_escrow_locks(bob())[0].purpose,
+            LockPurpose::PendingDeposit
+        );
+    }
+
+    #[test]
+    fn submit_proposal_pending_does_not_hold_up_queue() {
+        let context = get_context(false);
         testing_env!(context);
-        contract.submit_vote(0.into(), 1);
+        let mut contract = MockMoloch::new()
+            .add_escrow_deposit(robert(), 13)
+            .add_escrow_deposit(bob(), 213)
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .build();
+        contract.submit_proposal(
+            robert(),
+            12.into(),
+            0.into(),
+            vec![],
+            10.into(),
+            "".to_string(),
+        );
+        contract.submit_proposal(
+            bob(),
+            12.into(),
+            0.into(),
+            vec![],
+            10.into(),
+            "".to_string(),
+        );
 
+        assert_eq!(u64::from(contract.get_proposal_queue_length()), 1);
         let proposal = contract.proposal_queue.get(0).unwrap();
-        assert_eq!(proposal.yes_votes, 1);
-        assert_eq!(proposal.no_votes, 0);
-        assert_eq!(proposal.max_total_shares_at_yes_vote, 1);
-        let member = contract.members.get(&bob()).unwrap();
-        assert_eq!(member.highest_index_yes_vote, 0);
+        assert_eq!(proposal.applicant, bob(), "Pledged proposal was not queued");
+        assert!(contract.get_pending_proposal(0.into()).is_some());
     }
 
     #[test]
-    fn submit_vote_no() {
-        // Setup
+    fn submit_proposal_authorization_too_small() {
         let context = get_context(false);
         testing_env!(context);
-        let proposal = MockProposal::new().build();
         let mut contract = MockMoloch::new()
-            .add_proposal(proposal)
+            .add_escrow_deposit(robert(), 13)
+            .add_escrow_deposit(bob(), 101)
+            .authorize_tribute(robert(), bob(), 11, 0)
             .register_user(bob(), storage_deposit(), storage_deposit())
             .build();
+        contract.submit_proposal(
+            robert(),
+            12.into(),
+            0.into(),
+            vec![],
+            10.into(),
+            "".to_string(),
+        );
 
-        let mut context_builder = get_context_builder(false);
-        let context = context_builder
-            .block_timestamp((contract.summoning_time + contract.period_duration).into())
-            .build();
+        let proposal = contract.get_pending_proposal(0.into()).unwrap();
+        assert!(!proposal.tribute_pledged, "Tribute was pledged");
+        assert_eq!(
+            contract.get_tribute_authorization(robert(), bob()),
+            (U128::from(11), U128::from(0))
+        );
+    }
 
+    #[test]
+    fn submit_proposal_applicant_is_proposer() {
+        let context = get_context(false);
         testing_env!(context);
-        contract.submit_vote(0.into(), 2);
+        let mut contract = MockMoloch::new()
+            .add_escrow_deposit(bob(), 113)
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .build();
+        contract.submit_proposal(
+            bob(),
+            12.into(),
+            0.into(),
+            vec![],
+            10.into(),
+            "".to_string(),
+        );
 
         let proposal = contract.proposal_queue.get(0).unwrap();
-        assert_eq!(proposal.yes_votes, 0);
-        assert_eq!(proposal.no_votes, 1);
-        let member = contract.members.get(&bob()).unwrap();
-        assert_eq!(member.highest_index_yes_vote, 0);
+        assert!(proposal.tribute_pledged, "Tribute was not pledged");
+        let bob_balance = contract.get_escrow_user_balance(bob());
+        assert_eq!(u128::from(bob_balance), 1, "Tribute was not taken");
     }
 
     #[test]
-    fn submit_vote_complex() {
+    fn authorize_and_revoke_tribute() {
         let context = get_context(false);
         testing_env!(context);
-        let proposal_one = MockProposal::new().build();
-        let proposal_two = MockProposal::new().build();
-        let robert_member_info = MockMember::new().delegate_key(robert()).shares(30).build();
-        let alice_member_info = MockMember::new().delegate_key(alice()).shares(50).build();
         let mut contract = MockMoloch::new()
-            .add_proposal(proposal_one)
-            .add_proposal(proposal_two)
-            .add_member(robert_member_info)
-            .add_member(alice_member_info)
-            .register_user(bob(), storage_deposit(), storage_deposit())
-            .register_user(alice(), storage_deposit(), storage_deposit())
             .register_user(robert(), storage_deposit(), storage_deposit())
             .build();
-
-        // Make sure two periods pass so each proposal can
-        // be voted on
         let mut context_builder = get_context_builder(false);
-        let context_block_timestamp = context_builder
-            .block_timestamp((contract.summoning_time + contract.period_duration * 2).into());
-        testing_env!(context_block_timestamp.build());
-
-        // Actions by bob
-        contract.submit_vote(0.into(), 1);
-        testing_env!(context_block_timestamp.build());
-        contract.submit_vote(1.into(), 1);
-        let member = contract.members.get(&bob()).unwrap();
-        let proposal = contract.proposal_queue.get(0).unwrap();
+        testing_env!(context_builder
+            .predecessor_account_id(robert().try_into().unwrap())
+            .build());
+        contract.authorize_tribute(bob(), 12.into(), 5.into());
         assert_eq!(
-            member.highest_index_yes_vote, 1,
-            "Highest proposal index is wrong"
+            contract.get_tribute_authorization(robert(), bob()),
+            (U128::from(12), U128::from(5))
         );
+        contract.revoke_tribute_authorization(bob());
         assert_eq!(
-            proposal.max_total_shares_at_yes_vote, 81,
-            "Max number of total shares is wrong"
+            contract.get_tribute_authorization(robert(), bob()),
+            (U128::from(0), U128::from(0))
         );
+    }
 
-        // Roberts actions
-        testing_env!(context_block_timestamp
-            .predecessor_account_id(robert().try_into().unwrap())
-            .build());
-
-        contract.submit_vote(0.into(), 1);
-        testing_env!(context_block_timestamp.build());
-        contract.submit_vote(1.into(), 2);
-        let member = contract.members.get(&robert()).unwrap();
-        assert_eq!(
-            member.highest_index_yes_vote, 0,
-            "Highest proposal index is wrong for robert"
-        );
-
-        // Alices actions
-        testing_env!(context_block_timestamp
-            .predecessor_account_id(alice().try_into().unwrap())
+    #[test]
+    fn pledge_tribute() {
+        let context = get_context(false);
+        testing_env!(context);
+        let proposal = MockProposal::new().tribute_pledged(false).build();
+        let mut contract = MockMoloch::new()
+            .add_pending_proposal(proposal)
+            .add_escrow_deposit(robert(), 13)
+            .register_user(robert(), storage_deposit(), storage_deposit())
+            .build();
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder
+            .predecessor_account_id(robert().try_into().unwrap())
             .build());
+        let proposal_index = contract.pledge_tribute(0.into());
 
-        contract.submit_vote(0.into(), 1);
-        testing_env!(context_block_timestamp.build());
-        contract.submit_vote(1.into(), 1);
-        let member = contract.members.get(&alice()).unwrap();
+        assert_eq!(u64::from(proposal_index), 0);
+        assert!(contract.get_pending_proposal(0.into()).is_none());
+        let proposal = contract.proposal_queue.get(0).unwrap();
+        assert!(proposal.tribute_pledged, "Tribute was not pledged");
+        assert_eq!(proposal.starting_period, 1);
+        let robert_balance = contract.get_escrow_user_balance(robert());
+        assert_eq!(u128::from(robert_balance), 1, "Tribute was not taken");
         assert_eq!(
-            member.highest_index_yes_vote, 1,
-            "Highest proposal index is wrong for alice"
+            contract
+                .escrow
+                .get_lock(0, LockPurpose::Deposit)
+                .unwrap()
+                .amount,
+            100,
+            "Deposit was not moved to the queued proposal"
         );
-
-        let proposal_zero = contract.proposal_queue.get(0).unwrap();
-        assert_eq!(proposal_zero.yes_votes, 81);
-        assert_eq!(proposal_zero.no_votes, 0);
-        assert_eq!(proposal_zero.processed, false);
-        assert_eq!(proposal_zero.aborted, false);
-
-        let proposal_one = contract.proposal_queue.get(1).unwrap();
-        assert_eq!(proposal_one.yes_votes, 51);
-        assert_eq!(proposal_one.no_votes, 30);
-        assert_eq!(proposal_one.processed, false);
-        assert_eq!(proposal_one.aborted, false);
+        assert!(contract
+            .escrow
+            .get_lock(0, LockPurpose::PendingDeposit)
+            .is_none());
     }
 
-    // Proposal does not exist
     #[test]
-    #[should_panic(expected = r#"Proposal does not exist"#)]
-    fn submit_vote_no_existing_proposal() {
+    #[should_panic(expected = r#"Calling account is not the proposal applicant"#)]
+    fn pledge_tribute_not_applicant() {
         let context = get_context(false);
         testing_env!(context);
-        let mut contract = MockMoloch::new().build();
-        contract.submit_vote(0.into(), 1)
+        let proposal = MockProposal::new().tribute_pledged(false).build();
+        let mut contract = MockMoloch::new()
+            .add_pending_proposal(proposal)
+            .add_escrow_deposit(robert(), 13)
+            .build();
+        contract.pledge_tribute(0.into());
     }
 
     #[test]
-    #[should_panic(expected = r#"uint vote must be less than 3, 1 is yes 2 is no"#)]
-    pub fn submit_vote_undefined_vote() {
+    #[should_panic(expected = r#"Pending proposal does not exist"#)]
+    fn pledge_tribute_not_pending() {
         let context = get_context(false);
         testing_env!(context);
         let proposal = MockProposal::new().build();
         let mut contract = MockMoloch::new().add_proposal(proposal).build();
-        contract.submit_vote(0.into(), 3)
+        contract.pledge_tribute(0.into());
     }
 
-    // voting has not begun
     #[test]
-    #[should_panic(expected = r#"Voting period has not begun"#)]
-    pub fn submit_vote_has_not_begun() {
+    fn cancel_pending_proposal() {
         let context = get_context(false);
         testing_env!(context);
+        let proposal = MockProposal::new().tribute_pledged(false).build();
+        let mut contract = MockMoloch::new()
+            .add_pending_proposal(proposal)
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .build();
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder
+            .predecessor_account_id(robert().try_into().unwrap())
+            .build());
+        contract.cancel_pending_proposal(0.into());
 
-        let proposal = MockProposal::new().build();
-        let mut contract = MockMoloch::new().add_proposal(proposal).build();
-        contract.submit_vote(0.into(), 2)
+        assert!(contract.get_pending_proposal(0.into()).is_none());
+        assert_eq!(contract.total_shares_requested, 0);
+        assert_eq!(contract.get_escrow_locks(bob()), vec![]);
+        let bob_balance = contract.get_escrow_user_balance(bob());
+        assert_eq!(u128::from(bob_balance), 100, "Deposit was not refunded");
     }
 
-    // voting has expired
     #[test]
-    #[should_panic(expected = r#"Proposal voting period has expired"#)]
-    pub fn submit_vote_expired() {
+    fn cancel_pending_proposal_refunds_proposer_storage() {
         let context = get_context(false);
         testing_env!(context);
+        let mut contract = MockMoloch::new()
+            .add_escrow_deposit(robert(), 13)
+            .add_escrow_deposit(bob(), 101)
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .build();
+        let initial_used_bytes = contract
+            .user_storage_accounts
+            .get(&bob())
+            .unwrap()
+            .used_bytes;
+        contract.submit_proposal(
+            robert(),
+            12.into(),
+            0.into(),
+            vec![],
+            10.into(),
+            "".to_string(),
+        );
+        let used_bytes = contract
+            .user_storage_accounts
+            .get(&bob())
+            .unwrap()
+            .used_bytes;
+        let receipts = contract.pending_storage_receipts.get(&0).unwrap();
+        assert_eq!(receipts.len(), 1);
+        assert_eq!(receipts[0].payer, bob());
+        assert_eq!(receipts[0].bytes, used_bytes - initial_used_bytes);
 
         let mut context_builder = get_context_builder(false);
-        let proposal = MockProposal::new().build();
-        let mut contract = MockMoloch::new().add_proposal(proposal).build();
-        let block_time = contract.summoning_time
-            + (contract.period_duration * (contract.voting_period_length + 1));
-        let context = context_builder.block_timestamp(block_time.into()).build();
-        testing_env!(context);
+        testing_env!(context_builder
+            .predecessor_account_id(robert().try_into().unwrap())
+            .build());
+        contract.cancel_pending_proposal(0.into());
 
-        contract.submit_vote(0.into(), 2)
+        assert!(contract.pending_storage_receipts.get(&0).is_none());
+        assert!(
+            contract
+                .user_storage_accounts
+                .get(&bob())
+                .unwrap()
+                .used_bytes
+                < used_bytes,
+            "Proposer was not refunded"
+        );
     }
 
-    // member has already voted
     #[test]
-    #[should_panic(expected = r#"Member has already voted"#)]
-    pub fn submit_vote_member_already_voted() {
+    fn pledge_tribute_adds_storage_receipt() {
         let context = get_context(false);
         testing_env!(context);
-
-        let mut context_builder = get_context_builder(false);
-        let proposal = MockProposal::new().build();
         let mut contract = MockMoloch::new()
-            .add_proposal(proposal)
+            .add_escrow_deposit(robert(), 13)
+            .add_escrow_deposit(bob(), 101)
             .register_user(bob(), storage_deposit(), storage_deposit())
+            .register_user(robert(), storage_deposit(), storage_deposit())
             .build();
-        let block_time = contract.summoning_time + contract.period_duration;
-        let context = context_builder.block_timestamp(block_time.into()).build();
-        testing_env!(context);
+        contract.submit_proposal(
+            robert(),
+            12.into(),
+            0.into(),
+            vec![],
+            10.into(),
+            "".to_string(),
+        );
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder
+            .predecessor_account_id(robert().try_into().unwrap())
+            .build());
+        contract.pledge_tribute(0.into());
 
-        contract.submit_vote(0.into(), 2);
-        contract.submit_vote(0.into(), 1);
-        let proposal = contract.proposal_queue.get(0).unwrap();
-        assert_eq!(proposal.no_votes, 1)
+        assert!(contract.pending_storage_receipts.get(&0).is_none());
+        let receipts = contract.proposal_storage_receipts.get(&0).unwrap();
+        assert_eq!(receipts.len(), 2);
+        assert_eq!(receipts[0].payer, bob());
+        assert_eq!(receipts[1].payer, robert());
     }
 
-    // proposal has been aborted
     #[test]
-    #[should_panic(expected = r#"Proposal has been aborted"#)]
-    pub fn submit_vote_proposal_aborted() {
+    #[should_panic(
+        expected = r#"Only the proposer or the applicant can cancel a pending proposal"#
+    )]
+    fn cancel_pending_proposal_not_proposer_or_applicant() {
         let context = get_context(false);
         testing_env!(context);
-
+        let proposal = MockProposal::new().tribute_pledged(false).build();
+        let mut contract = MockMoloch::new().add_pending_proposal(proposal).build();
         let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder
+            .predecessor_account_id(alice().try_into().unwrap())
+            .build());
+        contract.cancel_pending_proposal(0.into());
+    }
 
-        let proposal = MockProposal::new().aborted(true).build();
-        let mut contract = MockMoloch::new().add_proposal(proposal).build();
-        let block_time = contract.summoning_time + contract.period_duration;
-        let context = context_builder.block_timestamp(block_time.into()).build();
+    #[test]
+    #[should_panic(expected = r#"applicant must be a valid account id"#)]
+    fn submit_proposal_invalid_account() {
+        let context = get_context(false);
         testing_env!(context);
-        contract.submit_vote(0.into(), 2);
+        let mut contract = MockMoloch::new().build();
+        contract.submit_proposal(
+            "".to_string(),
+            10.into(),
+            0.into(),
+            vec![],
+            10.into(),
+            "".to_string(),
+        );
     }
 
-    // Member not delegate
     #[test]
-    #[should_panic(expected = r#"Account is not a delegate"#)]
-    pub fn submit_vote_member_not_delegate() {
+    #[should_panic(expected = r#"Too many shares were requested"#)]
+    fn submit_proposal_shares_requested_overflow() {
         let context = get_context(false);
         testing_env!(context);
+        let mut contract = MockMoloch::new().build();
+        contract.submit_proposal(
+            robert(),
+            10.into(),
+            0.into(),
+            vec![],
+            u128::MAX.into(),
+            "".to_string(),
+        );
+    }
 
-        let mut context_builder = get_context_builder(false);
-
-        let proposal = MockProposal::new().aborted(true).build();
-        let mut contract = MockMoloch::new().add_proposal(proposal).build();
-        let block_time = contract.summoning_time + contract.period_duration;
-        let context = context_builder
-            .block_timestamp(block_time.into())
-            .predecessor_account_id(alice().try_into().unwrap())
+    #[test]
+    #[should_panic(
+        expected = r#"Too many shares were requested: due to outstanding shares requested"#
+    )]
+    fn submit_proposal_total_shares_requested_overflow() {
+        let context = get_context(false);
+        testing_env!(context);
+        let mut contract = MockMoloch::new()
+            .add_escrow_deposit(bob(), 100)
+            .add_escrow_deposit(robert(), 10)
+            .total_shares_requested(u128::MAX.saturating_sub(1).into())
+            .register_user(bob(), storage_deposit(), storage_deposit())
             .build();
+        contract.submit_proposal(
+            robert(),
+            10.into(),
+            0.into(),
+            vec![],
+            1.into(),
+            "".to_string(),
+        );
+    }
+    #[test]
+    #[should_panic(expected = r#"Too many shares were requested: greater than max shares"#)]
+    fn submit_proposal_too_many_shares_submitted() {
+        let context = get_context(false);
         testing_env!(context);
-        contract.submit_vote(0.into(), 2);
+        let mut contract = MockMoloch::new()
+            .add_escrow_deposit(bob(), 100)
+            .add_escrow_deposit(robert(), 10)
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .build();
+        contract.submit_proposal(
+            robert(),
+            10.into(),
+            0.into(),
+            vec![],
+            u128::MAX.saturating_sub(1).into(),
+            "".to_string(),
+        );
     }
 
-    // Test passed proposal
     #[test]
-    fn process_proposal_passed() {
+    #[should_panic(expected = r#"Account is not a delegate"#)]
+    fn submit_proposal_not_delegate() {
         let context = get_context(false);
         testing_env!(context);
-        let member = MockMember::new().build();
-        let proposal = MockProposal::new().yes_vote(&member).build();
+        let mut contract = MockMoloch::new().summoner(robert()).build();
+        contract.submit_proposal(
+            robert(),
+            10.into(),
+            0.into(),
+            vec![],
+            10.into(),
+            "".to_string(),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Tribute has not been pledged by the applicant"#)]
+    fn submit_vote_tribute_not_pledged() {
+        let context = get_context(false);
+        testing_env!(context);
+        let proposal = MockProposal::new().tribute_pledged(false).build();
         let mut contract = MockMoloch::new()
             .add_proposal(proposal)
-            .add_member(member)
-            .add_escrow_deposit(bob(), 400)
-            .add_escrow_deposit(robert(), 100)
             .register_user(bob(), storage_deposit(), storage_deposit())
-            .register_user(alice(), storage_deposit(), storage_deposit())
             .build();
-        let proposal = contract.proposal_queue.get(0).unwrap();
-        assert_eq!(proposal.processed, false, "Proposal has been processed");
-        assert_eq!(
-            contract.total_shares_requested, proposal.shares_requested,
-            "Total shares requested has not been set correctly",
-        );
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder
+            .block_timestamp(contract.summoning_time + contract.period_duration)
+            .build());
+        contract.submit_vote(0.into(), 1);
+    }
+
+    // Single vote
+    #[test]
+    fn submit_vote_yes() {
+        let context = get_context(false);
+        testing_env!(context);
+        let proposal = MockProposal::new().build();
+        let mut contract = MockMoloch::new()
+            .add_proposal(proposal)
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .build();
+
         let mut context_builder = get_context_builder(false);
         let context = context_builder
-            .block_timestamp(
-                contract.summoning_time
-                    + (contract.period_duration
-                        * (contract.voting_period_length + contract.grace_period_length + 1)),
-            )
-            .predecessor_account_id(alice().try_into().unwrap())
+            .block_timestamp((contract.summoning_time + contract.period_duration).into())
             .build();
         testing_env!(context);
-        contract.process_proposal(0.into());
+        contract.submit_vote(0.into(), 1);
 
         let proposal = contract.proposal_queue.get(0).unwrap();
-        assert_eq!(proposal.processed, true, "Proposal has not been processed");
-        assert_eq!(
-            contract.total_shares_requested, 0,
-            "Number of requested shares has not been subtracted",
+        assert_eq!(proposal.yes_votes, 1);
+        assert_eq!(proposal.no_votes, 0);
+        assert_eq!(proposal.max_total_shares_at_yes_vote, 1);
+        let member = contract.members.get(&bob()).unwrap();
+        assert_eq!(member.highest_index_yes_vote, 0);
+    }
+
+    #[test]
+    fn submit_vote_no() {
+        // Setup
+        let context = get_context(false);
+        testing_env!(context);
+        let proposal = MockProposal::new().build();
+        let mut contract = MockMoloch::new()
+            .add_proposal(proposal)
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .build();
+
+        let mut context_builder = get_context_builder(false);
+        let context = context_builder
+            .block_timestamp((contract.summoning_time + contract.period_duration).into())
+            .build();
+
+        testing_env!(context);
+        contract.submit_vote(0.into(), 2);
+
+        let proposal = contract.proposal_queue.get(0).unwrap();
+        assert_eq!(proposal.yes_votes, 0);
+        assert_eq!(proposal.no_votes, 1);
+        let member = contract.members.get(&bob()).unwrap();
+        assert_eq!(member.highest_index_yes_vote, 0);
+    }
+
+    #[test]
+    fn submit_vote_complex() {
+        let context = get_context(false);
+        testing_env!(context);
+        let proposal_one = MockProposal::new().build();
+        let proposal_two = MockProposal::new().build();
+        let robert_member_info = MockMember::new().delegate_key(robert()).shares(30).build();
+        let alice_member_info = MockMember::new().delegate_key(alice()).shares(50).build();
+        let mut contract = MockMoloch::new()
+            .add_proposal(proposal_one)
+            .add_proposal(proposal_two)
+            .add_member(robert_member_info)
+            .add_member(alice_member_info)
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .register_user(alice(), storage_deposit(), storage_deposit())
+            .register_user(robert(), storage_deposit(), storage_deposit())
+            .build();
+
+        // Make sure two periods pass so each proposal can
+        // be voted on
+        let mut context_builder = get_context_builder(false);
+        let context_block_timestamp = context_builder
+            .block_timestamp((contract.summoning_time + contract.period_duration * 2).into());
+        testing_env!(context_block_timestamp.build());
+
+        // Actions by bob
+        contract.submit_vote(0.into(), 1);
+        testing_env!(context_block_timestamp.build());
+        contract.submit_vote(1.into(), 1);
+        let member = contract.members.get(&bob()).unwrap();
+        let proposal = contract.proposal_queue.get(0).unwrap();
+        assert_eq!(
+            member.highest_index_yes_vote, 1,
+            "Highest proposal index is wrong"
+        );
+        assert_eq!(
+            proposal.max_total_shares_at_yes_vote, 81,
+            "Max number of total shares is wrong"
+        );
+
+        // Roberts actions
+        testing_env!(context_block_timestamp
+            .predecessor_account_id(robert().try_into().unwrap())
+            .build());
+
+        contract.submit_vote(0.into(), 1);
+        testing_env!(context_block_timestamp.build());
+        contract.submit_vote(1.into(), 2);
+        let member = contract.members.get(&robert()).unwrap();
+        assert_eq!(
+            member.highest_index_yes_vote, 0,
+            "Highest proposal index is wrong for robert"
+        );
+
+        // Alices actions
+        testing_env!(context_block_timestamp
+            .predecessor_account_id(alice().try_into().unwrap())
+            .build());
+
+        contract.submit_vote(0.into(), 1);
+        testing_env!(context_block_timestamp.build());
+        contract.submit_vote(1.into(), 1);
+        let member = contract.members.get(&alice()).unwrap();
+        assert_eq!(
+            member.highest_index_yes_vote, 1,
+            "Highest proposal index is wrong for alice"
+        );
+
+        let proposal_zero = contract.proposal_queue.get(0).unwrap();
+        assert_eq!(proposal_zero.yes_votes, 81);
+        assert_eq!(proposal_zero.no_votes, 0);
+        assert_eq!(proposal_zero.processed, false);
+        assert_eq!(proposal_zero.aborted, false);
+
+        let proposal_one = contract.proposal_queue.get(1).unwrap();
+        assert_eq!(proposal_one.yes_votes, 51);
+        assert_eq!(proposal_one.no_votes, 30);
+        assert_eq!(proposal_one.processed, false);
+        assert_eq!(proposal_one.aborted, false);
+    }
+
+    // Shares gained after a proposal starts don't count towards it
+    #[test]
+    fn submit_vote_uses_share_snapshot() {
+        let context = get_context(false);
+        testing_env!(context);
+        let proposal = MockProposal::new().build();
+        let mut robert_member = MockMember::new().shares(10).build();
+        robert_member.shares = 50;
+        robert_member.checkpoint_shares(1);
+        let mut contract = MockMoloch::new()
+            .add_proposal(proposal)
+            .add_member(robert_member)
+            .register_user(robert(), storage_deposit(), storage_deposit())
+            .build();
+
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder
+            .predecessor_account_id(robert().try_into().unwrap())
+            .block_timestamp(contract.summoning_time + contract.period_duration)
+            .build());
+        contract.submit_vote(0.into(), 1);
+
+        let proposal = contract.proposal_queue.get(0).unwrap();
+        assert_eq!(proposal.yes_votes, 10, "Vote did not use the snapshot");
+    }
+
+    // Shares burned after a proposal starts don't count towards it
+    #[test]
+    fn submit_vote_snapshot_capped_by_current_shares() {
+        let context = get_context(false);
+        testing_env!(context);
+        let proposal = MockProposal::new().build();
+        let mut robert_member = MockMember::new().shares(10).build();
+        robert_member.shares = 4;
+        robert_member.checkpoint_shares(1);
+        let mut contract = MockMoloch::new()
+            .add_proposal(proposal)
+            .add_member(robert_member)
+            .register_user(robert(), storage_deposit(), storage_deposit())
+            .build();
+
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder
+            .predecessor_account_id(robert().try_into().unwrap())
+            .block_timestamp(contract.summoning_time + contract.period_duration)
+            .build());
+        contract.submit_vote(0.into(), 2);
+
+        let proposal = contract.proposal_queue.get(0).unwrap();
+        assert_eq!(proposal.no_votes, 4, "Vote used burned shares");
+    }
+
+    // Proposal does not exist
+    #[test]
+    #[should_panic(expected = r#"Proposal does not exist"#)]
+    fn submit_vote_no_existing_proposal() {
+        let context = get_context(false);
+        testing_env!(context);
+        let mut contract = MockMoloch::new().build();
+        contract.submit_vote(0.into(), 1)
+    }
+
+    #[test]
+    #[should_panic(expected = r#"uint vote must be less than 3, 1 is yes 2 is no"#)]
+    pub fn submit_vote_undefined_vote() {
+        let context = get_context(false);
+        testing_env!(context);
+        let proposal = MockProposal::new().build();
+        let mut contract = MockMoloch::new().add_proposal(proposal).build();
+        contract.submit_vote(0.into(), 3)
+    }
+
+    // voting has not begun
+    #[test]
+    #[should_panic(expected = r#"Voting period has not begun"#)]
+    pub fn submit_vote_has_not_begun() {
+        let context = get_context(false);
+        testing_env!(context);
+
+        let proposal = MockProposal::new().build();
+        let mut contract = MockMoloch::new().add_proposal(proposal).build();
+        contract.submit_vote(0.into(), 2)
+    }
+
+    // voting has expired
+    #[test]
+    #[should_panic(expected = r#"Proposal voting period has expired"#)]
+    pub fn submit_vote_expired() {
+        let context = get_context(false);
+        testing_env!(context);
+
+        let mut context_builder = get_context_builder(false);
+        let proposal = MockProposal::new().build();
+        let mut contract = MockMoloch::new().add_proposal(proposal).build();
+        let block_time = contract.summoning_time
+            + (contract.period_duration * (contract.voting_period_length + 1));
+        let context = context_builder.block_timestamp(block_time.into()).build();
+        testing_env!(context);
+
+        contract.submit_vote(0.into(), 2)
+    }
+
+    // member has already voted
+    #[test]
+    #[should_panic(expected = r#"Member has already voted"#)]
+    pub fn submit_vote_member_already_voted() {
+        let context = get_context(false);
+        testing_env!(context);
+
+        let mut context_builder = get_context_builder(false);
+        let proposal = MockProposal::new().build();
+        let mut contract = MockMoloch::new()
+            .add_proposal(proposal)
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .build();
+        let block_time = contract.summoning_time + contract.period_duration;
+        let context = context_builder.block_timestamp(block_time.into()).build();
+        testing_env!(context);
+
+        contract.submit_vote(0.into(), 2);
+        contract.submit_vote(0.into(), 1);
+        let proposal = contract.proposal_queue.get(0).unwrap();
+        assert_eq!(proposal.no_votes, 1)
+    }
+
+    // proposal has been aborted
+    #[test]
+    #[should_panic(expected = r#"Proposal has been aborted"#)]
+    pub fn submit_vote_proposal_aborted() {
+        let context = get_context(false);
+        testing_env!(context);
+
+        let mut context_builder = get_context_builder(false);
+
+        let proposal = MockProposal::new().aborted(true).build();
+        let mut contract = MockMoloch::new().add_proposal(proposal).build();
+        let block_time = contract.summoning_time + contract.period_duration;
+        let context = context_builder.block_timestamp(block_time.into()).build();
+        testing_env!(context);
+        contract.submit_vote(0.into(), 2);
+    }
+
+    // Member not delegate
+    #[test]
+    #[should_panic(expected = r#"Account is not a delegate"#)]
+    pub fn submit_vote_member_not_delegate() {
+        let context = get_context(false);
+        testing_env!(context);
+
+        let mut context_builder = get_context_builder(false);
+
+        let proposal = MockProposal::new().aborted(true).build();
+        let mut contract = MockMoloch::new().add_proposal(proposal).build();
+        let block_time = contract.summoning_time + contract.period_duration;
+        let context = context_builder
+            .block_timestamp(block_time.into())
+            .predecessor_account_id(alice().try_into().unwrap())
+            .build();
+        testing_env!(context);
+        contract.submit_vote(0.into(), 2);
+    }
+
+    // Test passed proposal
+    #[test]
+    fn process_proposal_passed() {
+        let context = get_context(false);
+        testing_env!(context);
+        let member = MockMember::new().build();
+        let proposal = MockProposal::new().yes_vote(&member).build();
+        let mut contract = MockMoloch::new()
+            .add_proposal(proposal)
+            .add_member(member)
+            .add_escrow_deposit(bob(), 400)
+            .add_escrow_deposit(robert(), 100)
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .register_user(alice(), storage_deposit(), storage_deposit())
+            .build();
+        let proposal = contract.proposal_queue.get(0).unwrap();
+        assert_eq!(proposal.processed, false, "Proposal has been processed");
+        assert_eq!(
+            contract.total_shares_requested, proposal.shares_requested,
+            "Total shares requested has not been set correctly",
+        );
+        let mut context_builder = get_context_builder(false);
+        let context = context_builder
+            .block_timestamp(
+                contract.summoning_time
+                    + (contract.period_duration
+                        * (contract.voting_period_length + contract.grace_period_length + 1)),
+            )
+            .predecessor_account_id(alice().try_into().unwrap())
+            .build();
+        testing_env!(context);
+        contract.process_proposal(0.into());
+
+        let proposal = contract.proposal_queue.get(0).unwrap();
+        assert_eq!(proposal.processed, true, "Proposal has not been processed");
+        assert_eq!(
+            contract.total_shares_requested, 0,
+            "Number of requested shares has not been subtracted",
+        );
+        let member = contract.members.get(&robert()).unwrap();
+        assert_eq!(
+            member.shares, 20,
+            "Member does not have the correct number not shares"
+        );
+        assert_eq!(
+            contract.total_shares, 21,
+            "Total shares has not been updated correctly"
+        );
+        let bob_balance = contract.get_escrow_user_balance(bob());
+        assert_eq!(u128::from(bob_balance), 490, "Bob's balance is incorrect");
+        let bank_balance = contract.get_bank_balance();
+        assert_eq!(u128::from(bank_balance), 12, "Bank balance is incorrect");
+        let alice_reward = contract.get_withdrawable_balance(alice(), fdai());
+        assert_eq!(
+            u128::from(alice_reward),
+            10,
+            "Processing reward was not credited"
+        );
+    }
+
+    // Test passed proposal existing member, Assert shares are added
+    #[test]
+    fn process_proposal_passed_existing_member() {
+        let context = get_context(false);
+        testing_env!(context);
+        let member = MockMember::new().shares(1).delegate_key(bob()).build();
+        let proposal = MockProposal::new()
+            .applicant(alice())
+            .yes_vote(&member)
+            .shares_requested(15)
+            .build();
+
+        let existing_member = MockMember::new().shares(7).delegate_key(alice()).build();
+        let mut contract = MockMoloch::new()
+            .add_proposal(proposal)
+            .add_member(existing_member)
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .build();
+        let mut context_builder = get_context_builder(false);
+        let context = context_builder
+            .block_timestamp(
+                contract.summoning_time
+                    + (contract.period_duration
+                        * (contract.voting_period_length + contract.grace_period_length + 1)),
+            )
+            .build();
+        testing_env!(context);
+        contract.process_proposal(0.into());
+
+        let member = contract.members.get(&alice()).unwrap();
+        assert_eq!(
+            member.shares, 22,
+            "Member does not have the correct number not shares"
+        );
+        assert_eq!(
+            contract.total_shares, 23,
+            "Total shares has not been updated correctly"
+        );
+    }
+
+    // Test passed proposal New member, assert member saved,
+    #[test]
+    fn process_proposal_passed_new_member() {
+        let context = get_context(false);
+        testing_env!(context);
+        let member = MockMember::new().shares(1).delegate_key(bob()).build();
+        let proposal = MockProposal::new()
+            .applicant(alice())
+            .yes_vote(&member)
+            .shares_requested(15)
+            .build();
+
+        let mut contract = MockMoloch::new()
+            .add_proposal(proposal)
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .build();
+        let mut context_builder = get_context_builder(false);
+        let context = context_builder
+            .block_timestamp(
+                contract.summoning_time
+                    + (contract.period_duration
+                        * (contract.voting_period_length + contract.grace_period_length + 1)),
+            )
+            .build();
+        testing_env!(context);
+        contract.process_proposal(0.into());
+
+        let member = contract.members.get(&alice()).unwrap();
+        let member_delegate_key = contract.members_by_delegate_key.get(&alice()).unwrap();
+        assert_eq!(
+            member.shares, 15,
+            "Member does not have the correct number not shares"
+        );
+        assert_eq!(
+            member.delegate_key,
+            alice().to_string(),
+            "Member delegate_key is incorrect"
+        );
+        assert_eq!(member.exists, true, "Member does not exists");
+        assert_eq!(
+            member.highest_index_yes_vote, 0,
+            "Highest index vote is incorrect"
+        );
+        assert_eq!(member_delegate_key, alice().to_string());
+        assert_eq!(
+            contract.total_shares, 16,
+            "Total shares has not been updated correctly"
+        );
+    }
+
+    // Test passed proposal New member, delegate_key already exists
+    #[test]
+    fn process_proposal_passed_new_member_existing_delegate_key() {
+        let context = get_context(false);
+        testing_env!(context);
+        let member = MockMember::new().shares(10).delegate_key(robert()).build();
+        let proposal = MockProposal::new()
+            .applicant(alice())
+            .yes_vote(&member)
+            .shares_requested(15)
+            .build();
+
+        let mut contract = MockMoloch::new()
+            .add_proposal(proposal)
+            .add_member(member)
+            .update_member_delegate_key(&alice(), &robert())
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .build();
+        let mut context_builder = get_context_builder(false);
+        let context = context_builder
+            .block_timestamp(
+                contract.summoning_time
+                    + (contract.period_duration
+                        * (contract.voting_period_length + contract.grace_period_length + 1)),
+            )
+            .build();
+        testing_env!(context);
+        let member_robert = contract.members.get(&robert()).unwrap();
+        assert_eq!(
+            member_robert.delegate_key,
+            alice(),
+            "Delegate key has not been setup correctly"
+        );
+        contract.process_proposal(0.into());
+
+        let member_robert = contract.members.get(&robert()).unwrap();
+        assert_eq!(
+            member_robert.delegate_key,
+            robert(),
+            "Delegate key has not been updated correctly"
+        );
+
+        let member = contract.members.get(&alice()).unwrap();
+        let member_delegate_key = contract.members_by_delegate_key.get(&alice()).unwrap();
+        assert_eq!(
+            member.shares, 15,
+            "Member does not have the correct number not shares"
+        );
+        assert_eq!(
+            member.delegate_key,
+            alice().to_string(),
+            "Member delegate_key is incorrect"
+        );
+        assert_eq!(member.exists, true, "Member does not exists");
+        assert_eq!(
+            member.highest_index_yes_vote, 0,
+            "Highest index vote is incorrect"
+        );
+        assert_eq!(member_delegate_key, alice().to_string());
+        assert_eq!(
+            contract.total_shares, 26,
+            "Total shares has not been updated correctly"
+        );
+    }
+
+    #[test]
+    fn process_proposal_failed() {
+        let context = get_context(false);
+        testing_env!(context);
+        let member = MockMember::new().build();
+        let proposal = MockProposal::new().no_vote(&member).build();
+        let mut contract = MockMoloch::new()
+            .add_proposal(proposal)
+            .add_member(member)
+            .add_escrow_deposit(bob(), 400)
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .build();
+        let proposal = contract.proposal_queue.get(0).unwrap();
+        assert_eq!(proposal.processed, false, "Proposal has been processed");
+        assert_eq!(
+            contract.total_shares_requested, proposal.shares_requested,
+            "Total shares requested has not been set correctly",
+        );
+        let mut context_builder = get_context_builder(false);
+        let context = context_builder
+            .block_timestamp(
+                contract.summoning_time
+                    + (contract.period_duration
+                        * (contract.voting_period_length + contract.grace_period_length + 1)),
+            )
+            .build();
+        testing_env!(context);
+        contract.process_proposal(0.into());
+
+        let proposal = contract.proposal_queue.get(0).unwrap();
+        assert_eq!(proposal.processed, true, "Proposal has not been processed");
+        assert_eq!(
+            contract.total_shares_requested, 0,
+            "Number of requested shares has not been subtracted",
+        );
+        let member = contract.members.get(&robert()).unwrap();
+        assert_eq!(
+            member.shares, 10,
+            "Member does not have the correct number not shares"
+        );
+        assert_eq!(contract.total_shares, 11, "Total shares is not correct");
+        let bob_balance = contract.get_escrow_user_balance(bob());
+        assert_eq!(u128::from(bob_balance), 490, "Bob's balance is incorrect");
+        let robert_balance = contract.get_escrow_user_balance(robert());
+        assert_eq!(
+            u128::from(robert_balance),
+            12,
+            "Robert's balance is incorrect"
+        );
+        let bank_balance = contract.get_bank_balance();
+        assert_eq!(u128::from(bank_balance), 0, "Bank balance is incorrect");
+        assert_eq!(contract.get_escrow_locks(bob()), vec![]);
+        assert_eq!(contract.get_escrow_locks(robert()), vec![]);
+    }
+
+    // Test failed proposal aborted
+    #[test]
+    fn process_proposal_failed_aborted() {
+        let context = get_context(false);
+        testing_env!(context);
+        let member = MockMember::new().build();
+        let proposal = MockProposal::new().yes_vote(&member).aborted(true).build();
+        let mut contract = MockMoloch::new()
+            .add_proposal(proposal)
+            .add_member(member)
+            .add_escrow_deposit(bob(), 400)
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .build();
+        let proposal = contract.proposal_queue.get(0).unwrap();
+        assert_eq!(proposal.processed, false, "Proposal has been processed");
+        assert_eq!(
+            contract.total_shares_requested, proposal.shares_requested,
+            "Total shares requested has not been set correctly",
+        );
+        let mut context_builder = get_context_builder(false);
+        let context = context_builder
+            .block_timestamp(
+                contract.summoning_time
+                    + (contract.period_duration
+                        * (contract.voting_period_length + contract.grace_period_length + 1)),
+            )
+            .build();
+        testing_env!(context);
+        contract.process_proposal(0.into());
+
+        let proposal = contract.proposal_queue.get(0).unwrap();
+        assert_eq!(proposal.processed, true, "Proposal has not been processed");
+        assert_eq!(
+            contract.total_shares_requested, 0,
+            "Number of requested shares has not been subtracted",
+        );
+        let member = contract.members.get(&robert()).unwrap();
+        assert_eq!(
+            member.shares, 10,
+            "Member does not have the correct number not shares"
+        );
+        assert_eq!(contract.total_shares, 11, "Total shares is not correct");
+
+        let bob_balance = contract.get_escrow_user_balance(bob());
+        assert_eq!(u128::from(bob_balance), 490, "Bob's balance is incorrect");
+        let robert_balance = contract.get_escrow_user_balance(robert());
+        assert_eq!(
+            u128::from(robert_balance),
+            12,
+            "Robert's balance is incorrect"
+        );
+        let bank_balance = contract.get_bank_balance();
+        assert_eq!(u128::from(bank_balance), 0, "Bank balance is incorrect");
+    }
+
+    #[test]
+    fn process_proposal_unregistered_keeper() {
+        let context = get_context(false);
+        testing_env!(context);
+        let member = MockMember::new().build();
+        let proposal = MockProposal::new().yes_vote(&member).build();
+        let mut contract = MockMoloch::new()
+            .add_proposal(proposal)
+            .add_member(member)
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .build();
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder
+            .block_timestamp(
+                contract.summoning_time
+                    + (contract.period_duration
+                        * (contract.voting_period_length + contract.grace_period_length + 1)),
+            )
+            .predecessor_account_id("keeper.testnet".to_string().try_into().unwrap())
+            .build());
+        contract.process_proposal(0.into());
+
+        let keeper_reward = contract.get_withdrawable_balance("keeper.testnet".to_string(), fdai());
+        assert_eq!(u128::from(keeper_reward), 10, "Keeper was not rewarded");
+        let bob_storage = contract.user_storage_accounts.get(&bob()).unwrap();
+        assert!(
+            bob_storage.available < storage_deposit(),
+            "Proposer was not charged for storage"
+        );
+    }
+
+    #[test]
+    fn process_proposal_charges_storage_pool() {
+        let context = get_context(false);
+        testing_env!(context);
+        let member = MockMember::new().build();
+        let proposal = MockProposal::new().yes_vote(&member).build();
+        let mut contract = MockMoloch::new()
+            .add_proposal(proposal)
+            .add_member(member)
+            .register_user(bob(), 0, 0)
+            .build();
+        contract.storage_pool = storage_deposit();
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder
+            .block_timestamp(
+                contract.summoning_time
+                    + (contract.period_duration
+                        * (contract.voting_period_length + contract.grace_period_length + 1)),
+            )
+            .predecessor_account_id("keeper.testnet".to_string().try_into().unwrap())
+            .build());
+        contract.process_proposal(0.into());
+
+        assert!(
+            u128::from(contract.get_storage_pool()) < storage_deposit(),
+            "Storage pool was not charged"
+        );
+    }
+
+    #[test]
+    fn process_proposal_charges_registered_keeper() {
+        let context = get_context(false);
+        testing_env!(context);
+        let member = MockMember::new().build();
+        let proposal = MockProposal::new().yes_vote(&member).build();
+        let mut contract = MockMoloch::new()
+            .add_proposal(proposal)
+            .add_member(member)
+            .register_user(bob(), 0, 0)
+            .register_user(alice(), storage_deposit(), storage_deposit())
+            .build();
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder
+            .block_timestamp(
+                contract.summoning_time
+                    + (contract.period_duration
+                        * (contract.voting_period_length + contract.grace_period_length + 1)),
+            )
+            .predecessor_account_id(alice().try_into().unwrap())
+            .build());
+        contract.process_proposal(0.into());
+
+        assert!(contract.proposal_queue.get(0).unwrap().processed);
+        let alice_storage = contract.user_storage_accounts.get(&alice()).unwrap();
+        assert!(
+            alice_storage.available < storage_deposit(),
+            "Keeper was not charged for storage"
+        );
+        assert_eq!(u128::from(contract.get_storage_pool()), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"The DAO storage pool cannot cover the storage"#)]
+    fn process_proposal_storage_pool_empty() {
+        let context = get_context(false);
+        testing_env!(context);
+        let proposal = MockProposal::new().build();
+        let mut contract = MockMoloch::new().add_proposal(proposal).build();
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder
+            .block_timestamp(
+                contract.summoning_time
+                    + (contract.period_duration
+                        * (contract.voting_period_length + contract.grace_period_length + 1)),
+            )
+            .predecessor_account_id("keeper.testnet".to_string().try_into().unwrap())
+            .build());
+        contract.process_proposal(0.into());
+    }
+
+    // Process a proposal after members holding the given shares rage quit following a yes vote
+    fn process_after_mass_rage_quit(shares_to_burn: u128) -> Moloch {
+        let context = get_context(false);
+        testing_env!(context);
+        let robert_member = MockMember::new().build();
+        let alice_member = MockMember::new().delegate_key(alice()).shares(100).build();
+        let mut contract = MockMoloch::new()
+            .add_proposal(MockProposal::new().processed(true).build())
+            .add_proposal(MockProposal::new().build())
+            .add_member(robert_member)
+            .add_member(alice_member)
+            .register_user(robert(), storage_deposit(), storage_deposit())
+            .register_user(alice(), storage_deposit(), storage_deposit())
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .build();
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder
+            .predecessor_account_id(robert().try_into().unwrap())
+            .block_timestamp(contract.summoning_time + contract.period_duration)
+            .build());
+        contract.submit_vote(1.into(), 1);
+        let proposal = contract.proposal_queue.get(1).unwrap();
+        assert_eq!(proposal.max_total_shares_at_yes_vote, 111);
+
+        testing_env!(context_builder
+            .predecessor_account_id(alice().try_into().unwrap())
+            .build());
+        contract.rage_quit(shares_to_burn.into());
+
+        testing_env!(context_builder
+            .predecessor_account_id(bob().try_into().unwrap())
+            .block_timestamp(
+                contract.summoning_time
+                    + (contract.period_duration
+                        * (contract.voting_period_length + contract.grace_period_length + 1)),
+            )
+            .build());
+        contract.process_proposal(1.into());
+        contract
+    }
+
+    #[test]
+    fn process_proposal_dilution_bound_exceeded() {
+        // 11 shares remain, 11 * 10 < 111
+        let contract = process_after_mass_rage_quit(100);
+        let proposal = contract.proposal_queue.get(1).unwrap();
+        assert_eq!(
+            proposal.did_pass, false,
+            "Proposal passed past the dilution bound"
+        );
+        assert_eq!(contract.total_shares, 11);
+        let member = contract.members.get(&robert()).unwrap();
+        assert_eq!(member.shares, 10, "Applicant received shares");
+        let robert_balance = contract.get_escrow_user_balance(robert());
+        assert_eq!(u128::from(robert_balance), 12, "Tribute was not returned");
+    }
+
+    #[test]
+    fn process_proposal_dilution_bound_not_exceeded() {
+        // 21 shares remain, 21 * 10 >= 111
+        let contract = process_after_mass_rage_quit(90);
+        let proposal = contract.proposal_queue.get(1).unwrap();
+        assert_eq!(
+            proposal.did_pass, true,
+            "Proposal failed within the dilution bound"
+        );
+        assert_eq!(contract.total_shares, 31);
+        let bank_balance = contract.get_bank_balance();
+        assert_eq!(u128::from(bank_balance), 12, "Tribute was not deposited");
+    }
+
+    // Proposall does not exist
+    #[test]
+    #[should_panic(expected = r#"Proposal does not exist"#)]
+    fn process_proposal_does_not_exist() {
+        let context = get_context(false);
+        testing_env!(context);
+        let mut contract = MockMoloch::new().build();
+        contract.process_proposal(0.into());
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Proposal is not ready to be processed"#)]
+    fn process_proposal_not_ready_to_be_processed() {
+        let context = get_context(false);
+        testing_env!(context);
+        let proposal = MockProposal::new().build();
+        let mut contract = MockMoloch::new().add_proposal(proposal).build();
+        contract.process_proposal(0.into());
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Proposal has already been processed"#)]
+    fn process_proposal_already_processed() {
+        let context = get_context(false);
+        testing_env!(context);
+        let proposal = MockProposal::new().processed(true).build();
+        let mut contract = MockMoloch::new().add_proposal(proposal).build();
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder
+            .block_timestamp(
+                contract.summoning_time
+                    + contract.period_duration
+                        * (contract.voting_period_length + contract.grace_period_length + 1)
+            )
+            .build());
+
+        contract.process_proposal(0.into());
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Previous proposal must be processed"#)]
+    fn process_proposal_previous_proposal_not_processed() {
+        let context = get_context(false);
+        testing_env!(context);
+        let proposal_one = MockProposal::new().build();
+        let proposal_two = MockProposal::new().build();
+        let mut contract = MockMoloch::new()
+            .add_proposal(proposal_one)
+            .add_proposal(proposal_two)
+            .build();
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder
+            .block_timestamp(
+                contract.summoning_time
+                    + contract.period_duration
+                        * (contract.voting_period_length + contract.grace_period_length + 1)
+            )
+            .build());
+        contract.process_proposal(1.into());
+    }
+
+    #[test]
+    fn rage_quit_basic() {
+        let context = get_context(false);
+        testing_env!(context);
+        let robert_member_info = MockMember::new()
+            .delegate_key(robert())
+            .highest_index_yes_vote(0)
+            .shares(30)
+            .build();
+        let proposal = MockProposal::new().processed(true).build();
+        let mut contract = MockMoloch::new()
+            .add_proposal(proposal)
+            .add_member(robert_member_info)
+            .build();
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder
+            .predecessor_account_id(robert().try_into().unwrap())
+            .build());
+
+        contract.rage_quit(30.into());
+        assert_eq!(
+            contract.total_shares, 1,
+            "Total shares have not properly been subtracted"
+        );
+        let member = contract.members.get(&robert()).unwrap();
+        assert_eq!(
+            member.shares, 0,
+            "Shares have not been properly subtracted by a memeber"
+        );
+    }
+    #[test]
+    fn rage_quit_partial() {
+        let context = get_context(false);
+        testing_env!(context);
+        let robert_member_info = MockMember::new()
+            .delegate_key(robert())
+            .highest_index_yes_vote(0)
+            .shares(30)
+            .build();
+        let proposal = MockProposal::new().processed(true).build();
+        let mut contract = MockMoloch::new()
+            .add_proposal(proposal)
+            .add_member(robert_member_info)
+            .build();
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder
+            .predecessor_account_id(robert().try_into().unwrap())
+            .build());
+
+        contract.rage_quit(20.into());
+        assert_eq!(
+            contract.total_shares, 11,
+            "Total shares have not properly been subtracted"
+        );
+        let member = contract.members.get(&robert()).unwrap();
+        assert_eq!(
+            member.shares, 10,
+            "Shares have not been properly subtracted by a memeber"
+        );
+    }
+
+    // Rage quitting the last shares dissolves the guild and empties the vault
+    #[test]
+    fn rage_quit_dissolution_nfts() {
+        let context = get_context(false);
+        testing_env!(context);
+        let proposal = MockProposal::new().processed(true).build();
+        let mut contract = MockMoloch::new()
+            .add_proposal(proposal)
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .build();
+        contract
+            .bank
+            .deposit_nft(Nft::new("nft.testnet".to_string(), "1".to_string()));
+
+        contract.rage_quit(1.into());
+        assert_eq!(contract.total_shares, 0, "Guild was not dissolved");
+        assert_eq!(u64::from(contract.get_nft_claims(bob())), 1);
+
+        contract.claim_nft(0.into());
+        assert_eq!(
+            contract.get_bank_nfts(0.into(), 10.into()),
+            vec![],
+            "Vault was not emptied"
+        );
+        assert_eq!(u64::from(contract.get_nft_claims(bob())), 0);
+    }
+
+    #[test]
+    fn rage_quit_nft_claims_proportional() {
+        let context = get_context(false);
+        testing_env!(context);
+        let robert_member_info = MockMember::new().delegate_key(robert()).build();
+        let proposal = MockProposal::new().processed(true).build();
+        let mut contract = MockMoloch::new()
+            .add_proposal(proposal)
+            .add_member(robert_member_info)
+            .register_user(robert(), storage_deposit(), storage_deposit())
+            .build();
+        for token_id in 0..4 {
+            contract
+                .bank
+                .deposit_nft(Nft::new("nft.testnet".to_string(), token_id.to_string()));
+        }
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder
+            .predecessor_account_id(robert().try_into().unwrap())
+            .build());
+
+        // 10 of 11 shares claim 3 of the 4 NFTs, the remainder stays with the guild
+        contract.rage_quit(10.into());
+        assert_eq!(u64::from(contract.get_nft_claims(robert())), 3);
+        assert_eq!(contract.get_bank_nfts(0.into(), 10.into()).len(), 4);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"bob.near has no NFT claims"#)]
+    fn claim_nft_without_claims() {
+        let context = get_context(false);
+        testing_env!(context);
+        let mut contract = MockMoloch::new().build();
+        contract
+            .bank
+            .deposit_nft(Nft::new("nft.testnet".to_string(), "1".to_string()));
+        contract.claim_nft(0.into());
+    }
+
+    #[test]
+    fn rage_quit_partial_keeps_nfts() {
+        let context = get_context(false);
+        testing_env!(context);
+        let robert_member_info = MockMember::new().delegate_key(robert()).build();
+        let proposal = MockProposal::new().processed(true).build();
+        let mut contract = MockMoloch::new()
+            .add_proposal(proposal)
+            .add_member(robert_member_info)
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .build();
+        let nft = Nft::new("nft.testnet".to_string(), "1".to_string());
+        contract.bank.deposit_nft(nft.clone());
+
+        contract.rage_quit(1.into());
+        assert_eq!(
+            contract.get_bank_nfts(0.into(), 10.into()),
+            vec![nft],
+            "Vault was emptied"
+        );
+        assert_eq!(u64::from(contract.get_nft_claims(bob())), 0);
+    }
+
+    // Highest proposal has not been indexed
+    #[test]
+    #[should_panic(
+        expected = r#"Can't rage quit until the highest index proposal member voted YES is processed"#
+    )]
+    fn rage_quit_cant() {
+        let context = get_context(false);
+        testing_env!(context);
+        let proposal = MockProposal::new().build();
+        let member = MockMember::new().delegate_key(robert()).shares(30).build();
+        let mut contract = MockMoloch::new()
+            .add_member(member)
+            .add_proposal(proposal)
+            .build();
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder
+            .predecessor_account_id(robert().try_into().unwrap())
+            .build());
+
+        contract.rage_quit(20.into());
+    }
+
+    // Not a member
+    #[test]
+    #[should_panic(expected = r#"Account is not a member"#)]
+    fn rage_quit_not_a_member() {
+        let context = get_context(false);
+        testing_env!(context);
+        let proposal = MockProposal::new().build();
+        let mut contract = MockMoloch::new().add_proposal(proposal).build();
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder
+            .predecessor_account_id(robert().try_into().unwrap())
+            .build());
+
+        contract.rage_quit(20.into());
+    }
+
+    // Simple abort
+    #[test]
+    fn abort() {
+        let context = get_context(false);
+        testing_env!(context);
+        let proposal = MockProposal::new().applicant(robert()).build();
+        let mut contract = MockMoloch::new()
+            .add_proposal(proposal)
+            .register_user(robert(), storage_deposit(), storage_deposit())
+            .build();
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder
+            .predecessor_account_id(robert().try_into().unwrap())
+            .block_timestamp(contract.summoning_time + contract.period_duration)
+            .build());
+        contract.abort(0.into());
+
+        let proposal = contract.proposal_queue.get(0).unwrap();
+        assert_eq!(proposal.aborted, true);
+        assert_eq!(proposal.token_tribute, 0);
+    }
+
+    #[test]
+    fn abort_returns_tribute() {
+        let context = get_context(false);
+        testing_env!(context);
+        let proposal = MockProposal::new()
+            .applicant(robert())
+            .near_tribute(30)
+            .build();
+        let mut contract = MockMoloch::new()
+            .add_proposal(proposal)
+            .register_user(robert(), storage_deposit(), storage_deposit())
+            .build();
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder
+            .predecessor_account_id(robert().try_into().unwrap())
+            .block_timestamp(contract.summoning_time + contract.period_duration)
+            .build());
+        contract.abort(0.into());
+
+        let proposal = contract.proposal_queue.get(0).unwrap();
+        assert_eq!(proposal.near_tribute, 0);
+        let robert_balance = contract.get_escrow_user_balance(robert());
+        assert_eq!(
+            u128::from(robert_balance),
+            12,
+            "Token tribute was not returned"
+        );
+        let robert_near_balance = contract.get_escrow_user_near_balance(robert());
+        assert_eq!(
+            u128::from(robert_near_balance),
+            30,
+            "NEAR tribute was not returned"
+        );
+        let bob_balance = contract.get_escrow_user_balance(bob());
+        assert_eq!(u128::from(bob_balance), 0, "Proposer was credited at abort");
+        assert_eq!(contract.get_escrow_locks(robert()), vec![]);
+        assert_eq!(
+            contract.get_escrow_locks(bob()).len(),
+            1,
+            "Deposit lock was released at abort"
+        );
+    }
+
+    #[test]
+    fn abort_then_process_funds_balance() {
+        let context = get_context(false);
+        testing_env!(context);
+        let mut contract = MockMoloch::new()
+            .abort_penalty(20)
+            .add_escrow_deposit(robert(), 13)
+            .add_escrow_deposit(bob(), 101)
+            .authorize_tribute(robert(), bob(), 12, 0)
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .register_user(robert(), storage_deposit(), storage_deposit())
+            .register_user(alice(), storage_deposit(), storage_deposit())
+            .build();
+        // Deposit and tribute are held by the proposal until it is processed
+        let funds_held = |contract: &Moloch| {
+            let proposal = contract.proposal_queue.get(0).unwrap();
+            let held_by_proposal = match proposal.processed {
+                true => 0,
+                false => contract.proposal_deposit + proposal.token_tribute,
+            };
+            held_by_proposal
+                + u128::from(contract.get_escrow_user_balance(bob()))
+                + u128::from(contract.get_escrow_user_balance(robert()))
+                + u128::from(contract.get_bank_balance())
+                + u128::from(contract.get_withdrawable_balance(alice(), fdai()))
+        };
+        contract.submit_proposal(
+            robert(),
+            12.into(),
+            0.into(),
+            vec![],
+            10.into(),
+            "".to_string(),
+        );
+        assert_eq!(
+            funds_held(&contract),
+            114,
+            "Funds don't balance after submit"
+        );
+
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder
+            .predecessor_account_id(robert().try_into().unwrap())
+            .block_timestamp(contract.summoning_time + contract.period_duration)
+            .build());
+        contract.abort(0.into());
+        assert_eq!(
+            funds_held(&contract),
+            114,
+            "Funds don't balance after abort"
+        );
+
+        testing_env!(context_builder
+            .predecessor_account_id(alice().try_into().unwrap())
+            .block_timestamp(
+                contract.summoning_time
+                    + (contract.period_duration
+                        * (contract.voting_period_length + contract.grace_period_length + 1)),
+            )
+            .build());
+        contract.process_proposal(0.into());
+        assert_eq!(
+            funds_held(&contract),
+            114,
+            "Funds don't balance after process"
+        );
+
+        assert_eq!(u128::from(contract.get_escrow_user_balance(robert())), 13);
+        assert_eq!(u128::from(contract.get_escrow_user_balance(bob())), 71);
+        assert_eq!(u128::from(contract.get_bank_balance()), 20);
+        assert_eq!(
+            u128::from(contract.get_withdrawable_balance(alice(), fdai())),
+            10
+        );
+        assert_eq!(
+            u128::from(contract.get_token_liabilities()),
+            114,
+            "Liabilities don't match the funds held"
+        );
+    }
+
+    #[test]
+    fn abort_returns_nft_tribute() {
+        let context = get_context(false);
+        testing_env!(context);
+        let nft = Nft::new("nft.testnet".to_string(), "1".to_string());
+        let proposal = MockProposal::new()
+            .applicant(robert())
+            .nft_tribute(nft.clone())
+            .build();
+        let mut contract = MockMoloch::new()
+            .add_proposal(proposal)
+            .register_user(robert(), storage_deposit(), storage_deposit())
+            .build();
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder
+            .predecessor_account_id(robert().try_into().unwrap())
+            .block_timestamp(contract.summoning_time + contract.period_duration)
+            .build());
+        contract.abort(0.into());
+
+        let proposal = contract.proposal_queue.get(0).unwrap();
+        assert_eq!(proposal.nft_tribute, vec![]);
+        assert_eq!(contract.get_escrow_user_nfts(robert()), vec![nft]);
+    }
+
+    // Proposal does not exist
+    #[test]
+    #[should_panic(expected = r#"Proposal does not exist"#)]
+    fn abort_proposal_does_not_exist() {
+        let context = get_context(false);
+        testing_env!(context);
+        let mut contract = MockMoloch::new().build();
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder
+            .predecessor_account_id(robert().try_into().unwrap())
+            .block_timestamp(contract.summoning_time + contract.period_duration)
+            .build());
+        contract.abort(0.into());
+    }
+
+    // Caller is not applicant
+    #[test]
+    #[should_panic(expected = r#"Calling account is not the proposal applicant"#)]
+    fn abort_proposal_calling_account_is_not_applicant() {
+        let context = get_context(false);
+        testing_env!(context);
+        let proposal = MockProposal::new().applicant(robert()).build();
+        let mut contract = MockMoloch::new().add_proposal(proposal).build();
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder
+            .block_timestamp(contract.summoning_time + contract.period_duration)
+            .build());
+        contract.abort(0.into());
+    }
+
+    // The kicked member can't abort their own kick
+    #[test]
+    #[should_panic(expected = r#"Only membership proposals can be aborted"#)]
+    fn abort_guild_kick_by_kicked_member() {
+        let context = get_context(false);
+        testing_env!(context);
+        let member = MockMember::new().build();
+        let mut contract = MockMoloch::new()
+            .add_member(member)
+            .add_escrow_deposit(bob(), 101)
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .build();
+        contract.submit_guild_kick_proposal(robert(), "".to_string());
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder
+            .predecessor_account_id(robert().try_into().unwrap())
+            .block_timestamp(contract.summoning_time + contract.period_duration)
+            .build());
+        contract.abort(0.into());
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Only membership proposals can be aborted"#)]
+    fn abort_guardian_proposal_by_proposer() {
+        let context = get_context(false);
+        testing_env!(context);
+        let proposal = MockProposal::new()
+            .applicant(bob())
+            .kind(ProposalKind::UpdateGuardian(Some(alice())))
+            .build();
+        let mut contract = MockMoloch::new().add_proposal(proposal).build();
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder
+            .predecessor_account_id(bob().try_into().unwrap())
+            .block_timestamp(contract.summoning_time + contract.period_duration)
+            .build());
+        contract.abort(0.into());
+    }
+
+    // Abort window has passed
+    #[test]
+    #[should_panic(expected = r#"Abort window has passed!"#)]
+    fn abort_proposal_abort_window_has_passed() {
+        let context = get_context(false);
+        testing_env!(context);
+        let proposal = MockProposal::new().applicant(robert()).build();
+        let mut contract = MockMoloch::new().add_proposal(proposal).build();
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder
+            .predecessor_account_id(robert().try_into().unwrap())
+            .block_timestamp(contract.summoning_time + contract.period_duration * 5)
+            .build());
+        contract.abort(0.into());
+    }
+
+    // Proposal has already been aborted
+    #[test]
+    #[should_panic(expected = r#"Proposal has already been aborted"#)]
+    fn abort_proposal_proposal_has_already_been_aborted() {
+        let context = get_context(false);
+        testing_env!(context);
+        let proposal = MockProposal::new()
+            .applicant(robert())
+            .aborted(true)
+            .build();
+        let mut contract = MockMoloch::new().add_proposal(proposal).build();
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder
+            .predecessor_account_id(robert().try_into().unwrap())
+            .block_timestamp(contract.summoning_time + contract.period_duration)
+            .build());
+        contract.abort(0.into());
+    }
+
+    #[test]
+    fn update_delegate_key() {
+        let context = get_context(false);
+        testing_env!(context);
+        let mut contract = MockMoloch::new()
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .build();
+        contract.update_delegate_key("soda".to_string());
+        let old_key = contract.members_by_delegate_key.get(&bob()).unwrap();
+        assert_eq!(
+            old_key,
+            "".to_string(),
+            "Old has not been updated to an empty string"
+        );
+        let new_key = contract
+            .members_by_delegate_key
+            .get(&"soda".to_string())
+            .unwrap();
+        assert_eq!(new_key, bob().to_string(), "New key has been created");
+        let member = contract.members.get(&bob()).unwrap();
+        assert_eq!(
+            member.delegate_key,
+            "soda".to_string(),
+            "Member delegate key has not been updated"
+        );
+    }
+
+    fn set_bob_profile(contract: &mut Moloch) {
+        contract.set_member_profile(
+            "Bob".to_string(),
+            Some("https://bob.near/avatar.png".to_string()),
+            vec!["https://bob.near".to_string()],
+        );
+    }
+
+    #[test]
+    fn set_member_profile() {
+        let context = get_context(false);
+        testing_env!(context);
+        let mut contract = MockMoloch::new()
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .build();
+        set_bob_profile(&mut contract);
+        let member = contract.get_member(bob()).unwrap();
+        assert_eq!(u128::from(member.shares), 1);
+        assert_eq!(
+            member.profile,
+            Some(MemberProfile {
+                display_name: "Bob".to_string(),
+                avatar_url: Some("https://bob.near/avatar.png".to_string()),
+                links: vec!["https://bob.near".to_string()],
+            })
+        );
+        let bob_storage = contract.user_storage_accounts.get(&bob()).unwrap();
+        assert!(
+            bob_storage.available < storage_deposit(),
+            "Profile storage was not charged"
+        );
+
+        contract.clear_member_profile();
+        assert_eq!(contract.get_member(bob()).unwrap().profile, None);
+        let bob_storage = contract.user_storage_accounts.get(&bob()).unwrap();
+        assert_eq!(bob_storage.available, storage_deposit());
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Display name must be between 1 and 64 bytes"#)]
+    fn set_member_profile_name_too_long() {
+        let context = get_context(false);
+        testing_env!(context);
+        let mut contract = MockMoloch::new()
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .build();
+        contract.set_member_profile("b".repeat(65), None, vec![]);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"A profile can have at most 5 links"#)]
+    fn set_member_profile_too_many_links() {
+        let context = get_context(false);
+        testing_env!(context);
+        let mut contract = MockMoloch::new()
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .build();
+        contract.set_member_profile("Bob".to_string(), None, vec!["a".to_string(); 6]);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Urls can be at most 256 bytes"#)]
+    fn set_member_profile_url_too_long() {
+        let context = get_context(false);
+        testing_env!(context);
+        let mut contract = MockMoloch::new()
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .build();
+        contract.set_member_profile("Bob".to_string(), Some("a".repeat(257)), vec![]);
+    }
+
+    #[test]
+    fn rage_quit_all_shares_clears_profile() {
+        let context = get_context(false);
+        testing_env!(context);
+        let mut contract = MockMoloch::new()
+            .add_proposal(MockProposal::new().processed(true).build())
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .build();
+        set_bob_profile(&mut contract);
+        contract.rage_quit(1.into());
+        assert_eq!(contract.get_member(bob()).unwrap().profile, None);
+    }
+
+    #[test]
+    fn get_member_not_a_member() {
+        let context = get_context(false);
+        testing_env!(context);
+        let contract = MockMoloch::new().build();
+        assert_eq!(contract.get_member(robert()), None);
+    }
+
+    // A moloch where bob has added alice as a scoped delegate key and proposal 0 is in voting
+    fn moloch_with_scoped_key(scope: KeyScope, expires_at_period: Option<U64>) -> Moloch {
+        let context = get_context(false);
+        testing_env!(context);
+        let mut contract = MockMoloch::new()
+            .add_proposal(MockProposal::new().build())
+            .add_escrow_deposit(bob(), 100)
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .build();
+        contract.add_delegate_key(alice(), scope, expires_at_period);
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder
+            .predecessor_account_id(alice().try_into().unwrap())
+            .block_timestamp(contract.summoning_time + contract.period_duration)
+            .build());
+        contract
+    }
+
+    #[test]
+    fn add_delegate_key_vote_only() {
+        let mut contract = moloch_with_scoped_key(KeyScope::Vote, None);
+        assert_eq!(
+            contract.get_scoped_delegate_keys(bob()),
+            vec![ScopedKey {
+                account_id: alice(),
+                scope: KeyScope::Vote,
+                expires_at_period: None,
+            }]
+        );
+        contract.submit_vote(0.into(), 1);
+        let proposal = contract.proposal_queue.get(0).unwrap();
+        assert_eq!(proposal.yes_votes, 1);
+        assert_eq!(proposal.votes_by_member.get(&bob()), Some(&Vote::Yes));
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Delegate key alice.testnet is not allowed to Propose"#)]
+    fn add_delegate_key_vote_only_cannot_propose() {
+        let mut contract = moloch_with_scoped_key(KeyScope::Vote, None);
+        contract.submit_proposal(bob(), 0.into(), 0.into(), vec![], 1.into(), "".to_string());
+    }
+
+    #[test]
+    fn add_delegate_key_propose_only() {
+        let mut contract = moloch_with_scoped_key(KeyScope::Propose, None);
+        contract.submit_proposal(bob(), 0.into(), 0.into(), vec![], 1.into(), "".to_string());
+        let proposal = contract.proposal_queue.get(1).unwrap();
+        assert_eq!(proposal.proposer, bob());
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Delegate key alice.testnet is not allowed to Vote"#)]
+    fn add_delegate_key_propose_only_cannot_vote() {
+        let mut contract = moloch_with_scoped_key(KeyScope::Propose, None);
+        contract.submit_vote(0.into(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Delegate key alice.testnet has expired"#)]
+    fn add_delegate_key_expired() {
+        let mut contract = moloch_with_scoped_key(KeyScope::Both, Some(1.into()));
+        contract.submit_vote(0.into(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Expiry must be a future period"#)]
+    fn add_delegate_key_expiry_in_past() {
+        moloch_with_scoped_key(KeyScope::Both, Some(0.into()));
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Can't overwrite existing delegate keys"#)]
+    fn add_delegate_key_existing_key() {
+        let context = get_context(false);
+        testing_env!(context);
+        let mut contract = MockMoloch::new()
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .build();
+        contract.add_delegate_key(alice(), KeyScope::Vote, None);
+        contract.add_delegate_key(alice(), KeyScope::Propose, None);
+    }
+
+    #[test]
+    fn remove_delegate_key() {
+        let context = get_context(false);
+        testing_env!(context);
+        let mut contract = MockMoloch::new()
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .build();
+        contract.add_delegate_key(alice(), KeyScope::Vote, None);
+        contract.remove_delegate_key(alice());
+        assert_eq!(contract.get_scoped_delegate_keys(bob()), vec![]);
+        assert!(contract.members_by_delegate_key.get(&alice()).is_none());
+        let bob_storage = contract.user_storage_accounts.get(&bob()).unwrap();
+        assert_eq!(bob_storage.available, storage_deposit());
+    }
+
+    #[test]
+    #[should_panic(expected = r#"alice.testnet is not a scoped delegate key of bob.near"#)]
+    fn remove_delegate_key_not_scoped() {
+        let context = get_context(false);
+        testing_env!(context);
+        let mut contract = MockMoloch::new().build();
+        contract.remove_delegate_key(alice());
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Account is not a member"#)]
+    fn update_delegate_key_only_member() {
+        let context = get_context(false);
+        testing_env!(context);
+        let mut contract = MockMoloch::new().build();
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder
+            .predecessor_account_id(robert().try_into().unwrap())
+            .build());
+        contract.update_delegate_key("soda".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Delegate key cannot be an empty string"#)]
+    fn update_delegate_key_empty_string() {
+        let context = get_context(false);
+        testing_env!(context);
+        let mut contract = MockMoloch::new().build();
+        contract.update_delegate_key("".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Can't overwrite an existing members delegate_key"#)]
+    fn update_delegate_cannot_be_an_existing_member() {
+        let context = get_context(false);
+        testing_env!(context);
+        let member = MockMember::new().build();
+        let mut contract = MockMoloch::new().add_member(member).build();
+        contract.update_delegate_key(robert().to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Can't overwrite existing delegate keys"#)]
+    fn update_delegate_cannot_be_an_existing_member_from_delegate_key() {
+        let context = get_context(false);
+        testing_env!(context);
+        let member = MockMember::new().build();
+        let mut contract = MockMoloch::new()
+            .add_member(member)
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .build();
+        contract.update_delegate_key("soda".to_string());
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder
+            .predecessor_account_id(robert().try_into().unwrap())
+            .build());
+        contract.update_delegate_key("soda".to_string());
+    }
+    #[test]
+    fn delegate_votes() {
+        let context = get_context(false);
+        testing_env!(context);
+        let member = MockMember::new().build();
+        let mut contract = MockMoloch::new()
+            .add_member(member)
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .build();
+        contract.delegate_votes(robert());
+
+        assert_eq!(contract.get_vote_delegatee(bob()), Some(robert()));
+        let robert_member = contract.members.get(&robert()).unwrap();
+        assert_eq!(robert_member.vote_delegators, vec![bob()]);
+        assert_eq!(u128::from(contract.get_voting_weight(robert())), 11);
+        assert_eq!(u128::from(contract.get_voting_weight(bob())), 0);
+    }
+
+    #[test]
+    fn revoke_vote_delegation() {
+        let context = get_context(false);
+        testing_env!(context);
+        let member = MockMember::new().build();
+        let mut contract = MockMoloch::new()
+            .add_member(member)
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .build();
+        contract.delegate_votes(robert());
+        contract.revoke_vote_delegation();
+
+        assert_eq!(contract.get_vote_delegatee(bob()), None);
+        let robert_member = contract.members.get(&robert()).unwrap();
+        assert_eq!(robert_member.vote_delegators, Vec::<AccountId>::new());
+        assert_eq!(u128::from(contract.get_voting_weight(robert())), 10);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Delegation would create a cycle"#)]
+    fn delegate_votes_cycle() {
+        let context = get_context(false);
+        testing_env!(context);
+        let robert_member = MockMember::new().build();
+        let alice_member = MockMember::new().delegate_key(alice()).build();
+        let mut contract = MockMoloch::new()
+            .add_member(robert_member)
+            .add_member(alice_member)
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .register_user(robert(), storage_deposit(), storage_deposit())
+            .register_user(alice(), storage_deposit(), storage_deposit())
+            .build();
+        // bob -> robert -> alice -> bob
+        contract.delegate_votes(robert());
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder
+            .predecessor_account_id(robert().try_into().unwrap())
+            .build());
+        contract.delegate_votes(alice());
+        testing_env!(context_builder
+            .predecessor_account_id(alice().try_into().unwrap())
+            .build());
+        contract.delegate_votes(bob());
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Delegatee is not a member"#)]
+    fn delegate_votes_not_a_member() {
+        let context = get_context(false);
+        testing_env!(context);
+        let mut contract = MockMoloch::new()
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .build();
+        contract.delegate_votes(robert());
+    }
+
+    // Delegated weight is counted and locks the delegator's rage quit
+    #[test]
+    fn submit_vote_with_delegated_weight() {
+        let context = get_context(false);
+        testing_env!(context);
+        let proposal = MockProposal::new().build();
+        let robert_member = MockMember::new().shares(30).build();
+        let alice_member = MockMember::new().delegate_key(alice()).shares(50).build();
+        let mut contract = MockMoloch::new()
+            .add_proposal(MockProposal::new().build())
+            .add_proposal(proposal)
+            .add_member(robert_member)
+            .add_member(alice_member)
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .register_user(robert(), storage_deposit(), storage_deposit())
+            .register_user(alice(), storage_deposit(), storage_deposit())
+            .build();
+        // bob -> robert -> alice
+        contract.delegate_votes(robert());
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder
+            .predecessor_account_id(robert().try_into().unwrap())
+            .build());
+        contract.delegate_votes(alice());
+
+        testing_env!(context_builder
+            .predecessor_account_id(alice().try_into().unwrap())
+            .block_timestamp(contract.summoning_time + contract.period_duration * 2)
+            .build());
+        contract.submit_vote(1.into(), 1);
+
+        let proposal = contract.proposal_queue.get(1).unwrap();
+        assert_eq!(proposal.yes_votes, 81, "Delegated weight was not counted");
+        for member_id in vec![bob(), robert(), alice()] {
+            let member = contract.members.get(&member_id).unwrap();
+            assert_eq!(
+                member.highest_index_yes_vote, 1,
+                "Highest proposal index is wrong for {}",
+                member_id
+            );
+            assert_eq!(
+                contract.get_member_proposal_vote(member_id, 1.into()),
+                Vote::Yes
+            );
+        }
+    }
+
+    // A delegator who already voted is not counted again
+    #[test]
+    fn submit_vote_delegator_already_voted() {
+        let context = get_context(false);
+        testing_env!(context);
+        let mut proposal = MockProposal::new();
+        let bob_member = MockMember::new().delegate_key(bob()).shares(1).build();
+        proposal.yes_vote(&bob_member);
+        let robert_member = MockMember::new().shares(30).build();
+        let mut contract = MockMoloch::new()
+            .add_proposal(proposal.build())
+            .add_member(robert_member)
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .register_user(robert(), storage_deposit(), storage_deposit())
+            .build();
+        contract.delegate_votes(robert());
+
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder
+            .predecessor_account_id(robert().try_into().unwrap())
+            .block_timestamp(contract.summoning_time + contract.period_duration)
+            .build());
+        contract.submit_vote(0.into(), 2);
+
+        let proposal = contract.proposal_queue.get(0).unwrap();
+        assert_eq!(proposal.yes_votes, 1);
+        assert_eq!(proposal.no_votes, 30);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Member has delegated their votes"#)]
+    fn submit_vote_member_delegated() {
+        let context = get_context(false);
+        testing_env!(context);
+        let proposal = MockProposal::new().build();
+        let member = MockMember::new().build();
+        let mut contract = MockMoloch::new()
+            .add_proposal(proposal)
+            .add_member(member)
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .build();
+        contract.delegate_votes(robert());
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder
+            .block_timestamp(contract.summoning_time + contract.period_duration)
+            .build());
+        contract.submit_vote(0.into(), 1);
+    }
+
+    #[test]
+    fn escrow_withdraw() {
+        let context = get_context(false);
+        testing_env!(context);
+        let member = MockMember::new().build();
+        let mut contract = MockMoloch::new()
+            .add_member(member)
+            .add_escrow_deposit(bob(), 10)
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .build();
+        contract.escrow_withdraw(bob(), 5.into());
+        let bob_balance = contract.get_escrow_user_balance(bob());
+        assert_eq!(u128::from(bob_balance), 5, "Bob's balance is incorrect");
+        let bob_withdrawable = contract.get_withdrawable_balance(bob(), fdai());
+        assert_eq!(
+            u128::from(bob_withdrawable),
+            5,
+            "Bob's withdrawable balance is incorrect"
         );
-        let member = contract.members.get(&robert()).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Predecessor account id does not equal withdrawl account id"#)]
+    fn escrow_withdraw_different_account() {
+        let context = get_context(false);
+        testing_env!(context);
+        let member = MockMember::new().build();
+        let mut contract = MockMoloch::new()
+            .add_member(member)
+            .add_escrow_deposit(robert(), 10)
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .build();
+        contract.escrow_withdraw(robert(), 5.into());
+    }
+
+    #[test]
+    fn escrow_deposit_near() {
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder.build());
+        let mut contract = MockMoloch::new()
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .register_user(robert(), storage_deposit(), storage_deposit())
+            .build();
+        testing_env!(context_builder.attached_deposit(100).build());
+        contract.escrow_deposit_near(Some(robert()));
+        let robert_balance = contract.get_escrow_user_near_balance(robert());
         assert_eq!(
-            member.shares, 20,
-            "Member does not have the correct number not shares"
+            u128::from(robert_balance),
+            100,
+            "Robert's NEAR balance is incorrect"
         );
+        let bob_storage = contract.user_storage_accounts.get(&bob()).unwrap();
         assert_eq!(
-            contract.total_shares, 21,
-            "Total shares has not been updated correctly"
+            bob_storage.total,
+            storage_deposit(),
+            "Tribute was added to storage"
         );
-        let bob_balance = contract.get_escrow_user_balance(bob());
-        assert_eq!(u128::from(bob_balance), 490, "Bob's balance is incorrect");
-        let bank_balance = contract.get_bank_balance();
-        assert_eq!(u128::from(bank_balance), 12, "Bank balance is incorrect");
     }
 
-    // Test passed proposal existing member, Assert shares are added
     #[test]
-    fn process_proposal_passed_existing_member() {
+    #[should_panic(expected = r#"Beneficiary robert.testnet has not registered storage"#)]
+    fn escrow_deposit_near_not_registered() {
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder.build());
+        let mut contract = MockMoloch::new()
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .build();
+        testing_env!(context_builder.attached_deposit(100).build());
+        contract.escrow_deposit_near(Some(robert()));
+    }
+
+    #[test]
+    fn escrow_withdraw_near() {
         let context = get_context(false);
         testing_env!(context);
-        let member = MockMember::new().shares(1).delegate_key(bob()).build();
+        let mut contract = MockMoloch::new()
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .build();
+        contract.escrow.deposit_near(bob(), 10);
+        contract.escrow_withdraw_near(4.into());
+        let bob_balance = contract.get_escrow_user_near_balance(bob());
+        assert_eq!(
+            u128::from(bob_balance),
+            6,
+            "Bob's NEAR balance is incorrect"
+        );
+    }
+
+    // NEAR tribute moves to the guild bank with the token tribute
+    #[test]
+    fn process_proposal_passed_near_tribute() {
+        let context = get_context(false);
+        testing_env!(context);
+        let member = MockMember::new().build();
         let proposal = MockProposal::new()
-            .applicant(alice())
+            .near_tribute(50)
             .yes_vote(&member)
-            .shares_requested(15)
             .build();
-
-        let existing_member = MockMember::new().shares(7).delegate_key(alice()).build();
         let mut contract = MockMoloch::new()
             .add_proposal(proposal)
-            .add_member(existing_member)
+            .add_member(member)
+            .add_escrow_deposit(bob(), 400)
             .register_user(bob(), storage_deposit(), storage_deposit())
             .build();
         let mut context_builder = get_context_builder(false);
-        let context = context_builder
+        testing_env!(context_builder
             .block_timestamp(
                 contract.summoning_time
                     + (contract.period_duration
                         * (contract.voting_period_length + contract.grace_period_length + 1)),
             )
-            .build();
-        testing_env!(context);
+            .build());
         contract.process_proposal(0.into());
 
-        let member = contract.members.get(&alice()).unwrap();
+        let bank_near_balance = contract.get_bank_near_balance();
         assert_eq!(
-            member.shares, 22,
-            "Member does not have the correct number not shares"
+            u128::from(bank_near_balance),
+            50,
+            "Bank NEAR balance is incorrect"
         );
+        let robert_near_balance = contract.get_escrow_user_near_balance(robert());
         assert_eq!(
-            contract.total_shares, 23,
-            "Total shares has not been updated correctly"
+            u128::from(robert_near_balance),
+            0,
+            "Robert's NEAR balance is incorrect"
         );
     }
 
-    // Test passed proposal New member, assert member saved,
+    // NFT tribute moves into the guild bank vault
     #[test]
-    fn process_proposal_passed_new_member() {
+    fn process_proposal_passed_nft_tribute() {
         let context = get_context(false);
         testing_env!(context);
-        let member = MockMember::new().shares(1).delegate_key(bob()).build();
+        let member = MockMember::new().build();
+        let nft = Nft::new("nft.testnet".to_string(), "1".to_string());
         let proposal = MockProposal::new()
-            .applicant(alice())
+            .nft_tribute(nft.clone())
             .yes_vote(&member)
-            .shares_requested(15)
             .build();
-
         let mut contract = MockMoloch::new()
             .add_proposal(proposal)
+            .add_member(member)
+            .add_escrow_deposit(bob(), 400)
             .register_user(bob(), storage_deposit(), storage_deposit())
             .build();
         let mut context_builder = get_context_builder(false);
-        let context = context_builder
+        testing_env!(context_builder
             .block_timestamp(
                 contract.summoning_time
                     + (contract.period_duration
                         * (contract.voting_period_length + contract.grace_period_length + 1)),
             )
-            .build();
-        testing_env!(context);
+            .build());
         contract.process_proposal(0.into());
 
-        let member = contract.members.get(&alice()).unwrap();
-        let member_delegate_key = contract.members_by_delegate_key.get(&alice()).unwrap();
+        assert_eq!(contract.get_bank_nfts(0.into(), 10.into()), vec![nft]);
+        assert_eq!(contract.get_escrow_user_nfts(robert()), vec![]);
+    }
+
+    #[test]
+    fn rage_quit_credits_withdrawable_balance() {
+        let context = get_context(false);
+        testing_env!(context);
+        let robert_member_info = MockMember::new().shares(9).build();
+        let proposal = MockProposal::new().processed(true).build();
+        let mut contract = MockMoloch::new()
+            .add_proposal(proposal)
+            .add_member(robert_member_info)
+            .register_user(robert(), storage_deposit(), storage_deposit())
+            .build();
+        contract.bank.deposit(100);
+        contract.bank.deposit_near(1000);
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder
+            .predecessor_account_id(robert().try_into().unwrap())
+            .build());
+
+        contract.rage_quit(9.into());
+        let token_balance = contract.get_withdrawable_balance(robert(), fdai());
+        assert_eq!(u128::from(token_balance), 90, "Token payout is incorrect");
+        let near_balance = contract.get_withdrawable_balance(robert(), NEAR_TOKEN_ID.to_string());
+        assert_eq!(u128::from(near_balance), 900, "NEAR payout is incorrect");
+        assert_eq!(u128::from(contract.get_bank_balance()), 10);
+        assert_eq!(u128::from(contract.get_bank_near_balance()), 100);
+    }
+
+    #[test]
+    fn withdraw_balance() {
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder.build());
+        let mut contract = MockMoloch::new().build();
+        contract.ledger.credit(bob(), fdai(), 10);
+        contract.ledger.credit(bob(), NEAR_TOKEN_ID.to_string(), 10);
+        testing_env!(context_builder.attached_deposit(1).build());
+        contract.withdraw_balance(fdai(), 4.into());
+        contract.withdraw_balance(NEAR_TOKEN_ID.to_string(), 10.into());
+        let token_balance = contract.get_withdrawable_balance(bob(), fdai());
+        assert_eq!(u128::from(token_balance), 6, "Token balance is incorrect");
+        let near_balance = contract.get_withdrawable_balance(bob(), NEAR_TOKEN_ID.to_string());
+        assert_eq!(u128::from(near_balance), 0, "NEAR balance is incorrect");
+    }
+
+    #[test]
+    fn near_ledger_does_not_collide_with_a_near_account() {
+        let context = get_context(false);
+        testing_env!(context);
+        let mut contract = MockMoloch::new().build();
+        contract.ledger.credit(bob(), NEAR_TOKEN_ID.to_string(), 10);
         assert_eq!(
-            member.shares, 15,
-            "Member does not have the correct number not shares"
+            u128::from(contract.get_withdrawable_balance(bob(), "near".to_string())),
+            0,
+            "NEAR was credited to the near account's token"
+        );
+        assert!(!env::is_valid_account_id(NEAR_TOKEN_ID.as_bytes()));
+    }
+
+    #[test]
+    fn withdraw_balance_pending_until_resolved() {
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder.build());
+        let mut contract = MockMoloch::new().build();
+        contract.ledger.credit(bob(), fdai(), 10);
+        testing_env!(context_builder.attached_deposit(1).build());
+        contract.withdraw_balance(fdai(), 4.into());
+        assert_eq!(contract.pending_withdrawals, 4);
+        assert_eq!(contract.withdrawal_nonce, 1);
+        assert_eq!(u128::from(contract.get_token_liabilities()), 10);
+
+        testing_env!(
+            get_context(false),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            HashMap::default(),
+            vec![PromiseResult::Successful(vec![])]
+        );
+        contract.on_withdraw_balance(bob(), fdai(), 4.into(), 0.into());
+        assert_eq!(contract.pending_withdrawals, 0);
+        assert_eq!(u128::from(contract.get_token_liabilities()), 6);
+    }
+
+    #[test]
+    fn on_reconcile_sweeps_surplus() {
+        let context = get_context(false);
+        testing_env!(context);
+        let mut contract = MockMoloch::new().add_escrow_deposit(bob(), 10).build();
+        contract.bank.deposit(5);
+        contract.ledger.credit(robert(), fdai(), 3);
+        testing_env!(
+            get_context(false),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            HashMap::default(),
+            vec![balance_result(25)]
         );
+        let reconciliation = contract.on_reconcile(true, 0.into());
+        assert_eq!(u128::from(reconciliation.surplus), 7);
+        assert_eq!(u128::from(reconciliation.recorded_surplus), 7);
+        assert_eq!(reconciliation.sweepable_from_period, Some(1.into()));
         assert_eq!(
-            member.delegate_key,
-            alice().to_string(),
-            "Member delegate_key is incorrect"
+            u128::from(reconciliation.swept),
+            0,
+            "Swept before the delay"
         );
-        assert_eq!(member.exists, true, "Member does not exists");
+
+        let mut context_builder = get_context_builder(false);
+        testing_env!(
+            context_builder
+                .block_timestamp(contract.summoning_time + contract.period_duration)
+                .build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            HashMap::default(),
+            vec![balance_result(25)]
+        );
+        let reconciliation = contract.on_reconcile(true, 0.into());
+        assert_eq!(u128::from(reconciliation.balance), 25);
+        assert_eq!(u128::from(reconciliation.guild_bank), 12);
+        assert_eq!(u128::from(reconciliation.escrow), 10);
+        assert_eq!(u128::from(reconciliation.withdrawable), 3);
+        assert_eq!(u128::from(reconciliation.surplus), 7);
+        assert_eq!(u128::from(reconciliation.deficit), 0);
+        assert_eq!(u128::from(reconciliation.recorded_surplus), 0);
+        assert_eq!(u128::from(reconciliation.swept), 7);
+        assert_eq!(u128::from(contract.get_bank_balance()), 12);
+        assert_eq!(u128::from(contract.get_token_liabilities()), 25);
+    }
+
+    #[test]
+    fn on_reconcile_sweeps_at_most_the_recorded_surplus() {
+        let context = get_context(false);
+        testing_env!(context);
+        let mut contract = MockMoloch::new().build();
+        contract.recorded_surplus = Some((5, 0));
+        let mut context_builder = get_context_builder(false);
+        testing_env!(
+            context_builder
+                .block_timestamp(contract.summoning_time + contract.period_duration)
+                .build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            HashMap::default(),
+            vec![balance_result(8)]
+        );
+        let reconciliation = contract.on_reconcile(true, 0.into());
+        assert_eq!(u128::from(reconciliation.surplus), 8);
+        assert_eq!(u128::from(reconciliation.swept), 5);
+        assert_eq!(u128::from(contract.get_bank_balance()), 5);
+    }
+
+    #[test]
+    fn on_reconcile_sweeps_at_most_the_remeasured_surplus() {
+        let context = get_context(false);
+        testing_env!(context);
+        let mut contract = MockMoloch::new().build();
+        // A deposit in flight inflated the recorded surplus
+        contract.recorded_surplus = Some((20, 0));
+        let mut context_builder = get_context_builder(false);
+        testing_env!(
+            context_builder
+                .block_timestamp(contract.summoning_time + contract.period_duration)
+                .build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            HashMap::default(),
+            vec![balance_result(8)]
+        );
+        let reconciliation = contract.on_reconcile(true, 0.into());
+        assert_eq!(u128::from(reconciliation.swept), 8);
+        assert_eq!(u128::from(contract.get_bank_balance()), 8);
+    }
+
+    #[test]
+    fn on_reconcile_drops_recorded_surplus_when_gone() {
+        let context = get_context(false);
+        testing_env!(context);
+        let mut contract = MockMoloch::new().build();
+        contract.recorded_surplus = Some((20, 0));
+        testing_env!(
+            get_context(false),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            HashMap::default(),
+            vec![balance_result(0)]
+        );
+        let reconciliation = contract.on_reconcile(true, 0.into());
+        assert_eq!(u128::from(reconciliation.recorded_surplus), 0);
+        assert_eq!(contract.recorded_surplus, None);
+    }
+
+    #[test]
+    fn on_reconcile_reports_deficit() {
+        let context = get_context(false);
+        testing_env!(context);
+        let mut contract = MockMoloch::new()
+            .add_proposal(MockProposal::new().build())
+            .build();
+        testing_env!(
+            get_context(false),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            HashMap::default(),
+            vec![balance_result(100)]
+        );
+        let reconciliation = contract.on_reconcile(true, 0.into());
+        // The proposal holds the proposal deposit and 12 tribute
+        assert_eq!(u128::from(reconciliation.locked), 112);
+        assert_eq!(u128::from(reconciliation.surplus), 0);
+        assert_eq!(u128::from(reconciliation.deficit), 12);
+        assert_eq!(u128::from(reconciliation.swept), 0);
+    }
+
+    #[test]
+    fn on_reconcile_without_sweep() {
+        let context = get_context(false);
+        testing_env!(context);
+        let mut contract = MockMoloch::new().build();
+        testing_env!(
+            get_context(false),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            HashMap::default(),
+            vec![balance_result(8)]
+        );
+        let reconciliation = contract.on_reconcile(false, 0.into());
+        assert_eq!(u128::from(reconciliation.surplus), 8);
+        assert_eq!(u128::from(reconciliation.swept), 0);
+        assert_eq!(u128::from(contract.get_bank_balance()), 0);
+    }
+
+    #[test]
+    fn on_reconcile_skips_sweep_after_withdrawal() {
+        let context = get_context(false);
+        testing_env!(context);
+        let mut contract = MockMoloch::new().build();
+        contract.withdrawal_nonce = 1;
+        testing_env!(
+            get_context(false),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            HashMap::default(),
+            vec![balance_result(8)]
+        );
+        let reconciliation = contract.on_reconcile(true, 0.into());
+        assert_eq!(u128::from(reconciliation.surplus), 8);
+        assert_eq!(u128::from(reconciliation.swept), 0);
+        assert_eq!(u128::from(contract.get_bank_balance()), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Could not get the balance from the approved token"#)]
+    fn on_reconcile_failed() {
+        let context = get_context(false);
+        testing_env!(context);
+        let mut contract = MockMoloch::new().build();
+        testing_env!(
+            get_context(false),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            HashMap::default(),
+            vec![PromiseResult::Failed]
+        );
+        contract.on_reconcile(false, 0.into());
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Account is not a member"#)]
+    fn reconcile_sweep_not_member() {
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder.build());
+        let mut contract = MockMoloch::new().build();
+        testing_env!(context_builder
+            .predecessor_account_id(alice().try_into().unwrap())
+            .build());
+        contract.reconcile(true);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Cannot sweep while withdrawals are in flight"#)]
+    fn reconcile_sweep_pending_withdrawal() {
+        let context = get_context(false);
+        testing_env!(context);
+        let mut contract = MockMoloch::new().build();
+        contract.pending_withdrawals = 1;
+        contract.reconcile(true);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Unknown token dai.testnet"#)]
+    fn withdraw_balance_unknown_token() {
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder.build());
+        let mut contract = MockMoloch::new().build();
+        contract.ledger.credit(bob(), "dai.testnet".to_string(), 10);
+        testing_env!(context_builder.attached_deposit(1).build());
+        contract.withdraw_balance("dai.testnet".to_string(), 4.into());
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Requires attached deposit of exactly 1 yoctoNEAR"#)]
+    fn withdraw_balance_no_yocto() {
+        let context = get_context(false);
+        testing_env!(context);
+        let mut contract = MockMoloch::new().build();
+        contract.ledger.credit(bob(), fdai(), 10);
+        contract.withdraw_balance(fdai(), 4.into());
+    }
+
+    #[test]
+    fn on_ft_metadata() {
+        let context = get_context(false);
+        testing_env!(context);
+        let mut contract = MockMoloch::new().build();
+        contract.token_info = None;
+        assert!(!contract.is_active(), "Moloch is active");
+        testing_env!(
+            get_context(false),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            HashMap::default(),
+            vec![ft_metadata_result(FT_METADATA_SPEC, "FDAI")]
+        );
+        assert!(contract.on_ft_metadata(), "Token was not confirmed");
+        assert!(contract.is_active(), "Moloch is not active");
         assert_eq!(
-            member.highest_index_yes_vote, 0,
-            "Highest index vote is incorrect"
+            contract.get_token_info(),
+            Some(TokenInfo {
+                symbol: "FDAI".to_string(),
+                decimals: 18
+            })
+        );
+    }
+
+    #[test]
+    fn on_ft_metadata_invalid_spec() {
+        let context = get_context(false);
+        testing_env!(context);
+        let mut contract = MockMoloch::new().build();
+        contract.token_info = None;
+        testing_env!(
+            get_context(false),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            HashMap::default(),
+            vec![ft_metadata_result("nft-1.0.0", "FDAI")]
         );
-        assert_eq!(member_delegate_key, alice().to_string());
-        assert_eq!(
-            contract.total_shares, 16,
-            "Total shares has not been updated correctly"
+        assert!(!contract.on_ft_metadata(), "Token was confirmed");
+        assert!(!contract.is_active(), "Moloch is active");
+    }
+
+    #[test]
+    fn on_ft_metadata_failed() {
+        let context = get_context(false);
+        testing_env!(context);
+        let mut contract = MockMoloch::new().build();
+        contract.token_info = None;
+        testing_env!(
+            get_context(false),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            HashMap::default(),
+            vec![PromiseResult::Failed]
         );
+        assert!(!contract.on_ft_metadata(), "Token was confirmed");
+        assert_eq!(contract.get_token_info(), None);
     }
 
-    // Test passed proposal New member, delegate_key already exists
     #[test]
-    fn process_proposal_passed_new_member_existing_delegate_key() {
+    #[should_panic(expected = r#"Approved token has already been confirmed"#)]
+    fn check_approved_token_already_confirmed() {
         let context = get_context(false);
         testing_env!(context);
-        let member = MockMember::new().shares(10).delegate_key(robert()).build();
-        let proposal = MockProposal::new()
-            .applicant(alice())
-            .yes_vote(&member)
-            .shares_requested(15)
-            .build();
+        let mut contract = MockMoloch::new().build();
+        contract.check_approved_token();
+    }
 
+    #[test]
+    #[should_panic(expected = r#"Moloch is inactive until the approved token is confirmed"#)]
+    fn submit_proposal_inactive() {
+        let context = get_context(false);
+        testing_env!(context);
         let mut contract = MockMoloch::new()
-            .add_proposal(proposal)
-            .add_member(member)
-            .update_member_delegate_key(&alice(), &robert())
+            .add_escrow_deposit(robert(), 13)
+            .add_escrow_deposit(bob(), 101)
             .register_user(bob(), storage_deposit(), storage_deposit())
             .build();
-        let mut context_builder = get_context_builder(false);
-        let context = context_builder
-            .block_timestamp(
-                contract.summoning_time
-                    + (contract.period_duration
-                        * (contract.voting_period_length + contract.grace_period_length + 1)),
-            )
-            .build();
-        testing_env!(context);
-        let member_robert = contract.members.get(&robert()).unwrap();
-        assert_eq!(
-            member_robert.delegate_key,
-            alice(),
-            "Delegate key has not been setup correctly"
-        );
-        contract.process_proposal(0.into());
-
-        let member_robert = contract.members.get(&robert()).unwrap();
-        assert_eq!(
-            member_robert.delegate_key,
+        contract.token_info = None;
+        contract.submit_proposal(
             robert(),
-            "Delegate key has not been updated correctly"
+            12.into(),
+            0.into(),
+            vec![],
+            10.into(),
+            "".to_string(),
         );
+    }
 
-        let member = contract.members.get(&alice()).unwrap();
-        let member_delegate_key = contract.members_by_delegate_key.get(&alice()).unwrap();
-        assert_eq!(
-            member.shares, 15,
-            "Member does not have the correct number not shares"
-        );
-        assert_eq!(
-            member.delegate_key,
-            alice().to_string(),
-            "Member delegate_key is incorrect"
-        );
-        assert_eq!(member.exists, true, "Member does not exists");
-        assert_eq!(
-            member.highest_index_yes_vote, 0,
-            "Highest index vote is incorrect"
-        );
-        assert_eq!(member_delegate_key, alice().to_string());
-        assert_eq!(
-            contract.total_shares, 26,
-            "Total shares has not been updated correctly"
-        );
+    #[test]
+    fn pause_and_unpause() {
+        let context = get_context(false);
+        testing_env!(context);
+        let mut contract = MockMoloch::new().guardian(alice()).build();
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder
+            .predecessor_account_id(alice().try_into().unwrap())
+            .build());
+        contract.pause();
+        assert!(contract.is_paused(), "Moloch is not paused");
+        contract.unpause();
+        assert!(!contract.is_paused(), "Moloch is still paused");
     }
 
     #[test]
-    fn process_proposal_failed() {
+    #[should_panic(expected = r#"Only the guardian can pause or unpause"#)]
+    fn pause_not_guardian() {
+        let context = get_context(false);
+        testing_env!(context);
+        let mut contract = MockMoloch::new().guardian(alice()).build();
+        contract.pause();
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Moloch is paused, only rage_quit and withdrawals are available"#)]
+    fn submit_proposal_paused() {
         let context = get_context(false);
         testing_env!(context);
-        let member = MockMember::new().build();
-        let proposal = MockProposal::new().no_vote(&member).build();
         let mut contract = MockMoloch::new()
-            .add_proposal(proposal)
-            .add_member(member)
-            .add_escrow_deposit(bob(), 400)
+            .add_escrow_deposit(robert(), 13)
+            .add_escrow_deposit(bob(), 101)
             .register_user(bob(), storage_deposit(), storage_deposit())
             .build();
-        let proposal = contract.proposal_queue.get(0).unwrap();
-        assert_eq!(proposal.processed, false, "Proposal has been processed");
-        assert_eq!(
-            contract.total_shares_requested, proposal.shares_requested,
-            "Total shares requested has not been set correctly",
+        contract.paused = true;
+        contract.submit_proposal(
+            robert(),
+            12.into(),
+            0.into(),
+            vec![],
+            10.into(),
+            "".to_string(),
         );
-        let mut context_builder = get_context_builder(false);
-        let context = context_builder
-            .block_timestamp(
-                contract.summoning_time
-                    + (contract.period_duration
-                        * (contract.voting_period_length + contract.grace_period_length + 1)),
-            )
-            .build();
-        testing_env!(context);
-        contract.process_proposal(0.into());
+    }
 
-        let proposal = contract.proposal_queue.get(0).unwrap();
-        assert_eq!(proposal.processed, true, "Proposal has not been processed");
-        assert_eq!(
-            contract.total_shares_requested, 0,
-            "Number of requested shares has not been subtracted",
-        );
-        let member = contract.members.get(&robert()).unwrap();
-        assert_eq!(
-            member.shares, 10,
-            "Member does not have the correct number not shares"
-        );
-        assert_eq!(contract.total_shares, 11, "Total shares is not correct");
-        let bob_balance = contract.get_escrow_user_balance(bob());
-        assert_eq!(u128::from(bob_balance), 490, "Bob's balance is incorrect");
-        let robert_balance = contract.get_escrow_user_balance(robert());
-        assert_eq!(
-            u128::from(robert_balance),
-            12,
-            "Robert's balance is incorrect"
-        );
-        let bank_balance = contract.get_bank_balance();
-        assert_eq!(u128::from(bank_balance), 0, "Bank balance is incorrect");
+    #[test]
+    fn escrow_withdraw_while_paused() {
+        let context = get_context(false);
+        testing_env!(context);
+        let mut contract = MockMoloch::new()
+            .add_escrow_deposit(bob(), 10)
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .build();
+        contract.escrow.deposit_near(bob(), 10);
+        contract.paused = true;
+        contract.escrow_withdraw(bob(), 10.into());
+        contract.escrow_withdraw_near(10.into());
+        let token_balance = contract.get_withdrawable_balance(bob(), fdai());
+        assert_eq!(u128::from(token_balance), 10, "Token balance is incorrect");
+        let near_balance = contract.get_withdrawable_balance(bob(), NEAR_TOKEN_ID.to_string());
+        assert_eq!(u128::from(near_balance), 10, "NEAR balance is incorrect");
     }
 
-    // Test failed proposal aborted
     #[test]
-    fn process_proposal_failed_aborted() {
+    fn rage_quit_pending_yes_vote_while_paused() {
         let context = get_context(false);
         testing_env!(context);
-        let member = MockMember::new().build();
-        let proposal = MockProposal::new().yes_vote(&member).aborted(true).build();
+        let member = MockMember::new().delegate_key(robert()).shares(30).build();
+        let proposal = MockProposal::new().yes_vote(&member).build();
         let mut contract = MockMoloch::new()
-            .add_proposal(proposal)
             .add_member(member)
-            .add_escrow_deposit(bob(), 400)
-            .register_user(bob(), storage_deposit(), storage_deposit())
+            .add_proposal(proposal)
             .build();
-        let proposal = contract.proposal_queue.get(0).unwrap();
-        assert_eq!(proposal.processed, false, "Proposal has been processed");
-        assert_eq!(
-            contract.total_shares_requested, proposal.shares_requested,
-            "Total shares requested has not been set correctly",
-        );
+        contract.bank.deposit(100);
+        contract.paused = true;
         let mut context_builder = get_context_builder(false);
-        let context = context_builder
+        testing_env!(context_builder
+            .predecessor_account_id(robert().try_into().unwrap())
             .block_timestamp(
                 contract.summoning_time
-                    + (contract.period_duration
-                        * (contract.voting_period_length + contract.grace_period_length + 1)),
+                    + contract.period_duration
+                        * (contract.voting_period_length + contract.grace_period_length),
             )
-            .build();
-        testing_env!(context);
-        contract.process_proposal(0.into());
+            .build());
 
-        let proposal = contract.proposal_queue.get(0).unwrap();
-        assert_eq!(proposal.processed, true, "Proposal has not been processed");
-        assert_eq!(
-            contract.total_shares_requested, 0,
-            "Number of requested shares has not been subtracted",
-        );
-        let member = contract.members.get(&robert()).unwrap();
-        assert_eq!(
-            member.shares, 10,
-            "Member does not have the correct number not shares"
-        );
-        assert_eq!(contract.total_shares, 11, "Total shares is not correct");
+        contract.rage_quit(30.into());
 
-        let bob_balance = contract.get_escrow_user_balance(bob());
-        assert_eq!(u128::from(bob_balance), 490, "Bob's balance is incorrect");
-        let robert_balance = contract.get_escrow_user_balance(robert());
-        assert_eq!(
-            u128::from(robert_balance),
-            12,
-            "Robert's balance is incorrect"
-        );
-        let bank_balance = contract.get_bank_balance();
-        assert_eq!(u128::from(bank_balance), 0, "Bank balance is incorrect");
+        let token_balance = contract.get_withdrawable_balance(robert(), fdai());
+        assert_eq!(u128::from(token_balance), 100, "Token payout is incorrect");
+        assert_eq!(contract.total_shares, 0, "Shares were not burned");
     }
 
-    // Proposall does not exist
     #[test]
-    #[should_panic(expected = r#"Proposal does not exist"#)]
-    fn process_proposal_does_not_exist() {
+    #[should_panic(
+        expected = r#"Can't rage quit until the highest index proposal member voted YES is processed"#
+    )]
+    fn rage_quit_pending_yes_vote_recently_paused() {
         let context = get_context(false);
         testing_env!(context);
-        let mut contract = MockMoloch::new().build();
-        contract.process_proposal(0.into());
-    }
+        let member = MockMember::new().delegate_key(robert()).shares(30).build();
+        let proposal = MockProposal::new().yes_vote(&member).build();
+        let mut contract = MockMoloch::new()
+            .add_member(member)
+            .add_proposal(proposal)
+            .guardian(alice())
+            .build();
+        contract.bank.deposit(100);
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder
+            .predecessor_account_id(alice().try_into().unwrap())
+            .block_timestamp(contract.summoning_time + contract.period_duration)
+            .build());
+        contract.pause();
+        testing_env!(context_builder
+            .predecessor_account_id(robert().try_into().unwrap())
+            .block_timestamp(
+                contract.summoning_time
+                    + contract.period_duration
+                        * (contract.voting_period_length + contract.grace_period_length),
+            )
+            .build());
 
-    #[test]
-    #[should_panic(expected = r#"Proposal is not ready to be processed"#)]
-    fn process_proposal_not_ready_to_be_processed() {
-        let context = get_context(false);
-        testing_env!(context);
-        let proposal = MockProposal::new().build();
-        let mut contract = MockMoloch::new().add_proposal(proposal).build();
-        contract.process_proposal(0.into());
+        contract.rage_quit(30.into());
     }
 
     #[test]
-    #[should_panic(expected = r#"Proposal has already been processed"#)]
-    fn process_proposal_already_processed() {
+    fn rage_quit_and_withdraw_while_paused() {
         let context = get_context(false);
         testing_env!(context);
+        let robert_member_info = MockMember::new().shares(9).build();
         let proposal = MockProposal::new().processed(true).build();
-        let mut contract = MockMoloch::new().add_proposal(proposal).build();
+        let mut contract = MockMoloch::new()
+            .add_proposal(proposal)
+            .add_member(robert_member_info)
+            .register_user(robert(), storage_deposit(), storage_deposit())
+            .build();
+        contract.bank.deposit(100);
+        contract.paused = true;
         let mut context_builder = get_context_builder(false);
         testing_env!(context_builder
-            .block_timestamp(
-                contract.summoning_time
-                    + contract.period_duration
-                        * (contract.voting_period_length + contract.grace_period_length + 1)
-            )
+            .predecessor_account_id(robert().try_into().unwrap())
             .build());
+        contract.rage_quit(9.into());
+        testing_env!(context_builder.attached_deposit(1).build());
+        contract.withdraw_balance(fdai(), 90.into());
+        let token_balance = contract.get_withdrawable_balance(robert(), fdai());
+        assert_eq!(u128::from(token_balance), 0, "Token payout is incorrect");
+    }
 
-        contract.process_proposal(0.into());
+    #[test]
+    fn submit_guardian_proposal() {
+        let context = get_context(false);
+        testing_env!(context);
+        let mut contract = MockMoloch::new()
+            .add_escrow_deposit(bob(), 101)
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .build();
+        contract.submit_guardian_proposal(Some(alice()), "".to_string());
+
+        let proposal = contract.proposal_queue.get(0).unwrap();
+        assert_eq!(proposal.proposer, bob());
+        assert_eq!(proposal.applicant, bob());
+        assert_eq!(proposal.shares_requested, 0);
+        assert_eq!(proposal.starting_period, 1);
+        assert_eq!(proposal.kind, ProposalKind::UpdateGuardian(Some(alice())));
+        let bob_balance = contract.get_escrow_user_balance(bob());
+        assert_eq!(u128::from(bob_balance), 1, "Deposit was not taken");
     }
 
     #[test]
-    #[should_panic(expected = r#"Previous proposal must be processed"#)]
-    fn process_proposal_previous_proposal_not_processed() {
+    fn process_proposal_passed_update_guardian() {
         let context = get_context(false);
         testing_env!(context);
-        let proposal_one = MockProposal::new().build();
-        let proposal_two = MockProposal::new().build();
+        let member = MockMember::new().build();
+        let proposal = MockProposal::new()
+            .applicant(bob())
+            .shares_requested(0)
+            .kind(ProposalKind::UpdateGuardian(Some(robert())))
+            .yes_vote(&member)
+            .build();
         let mut contract = MockMoloch::new()
-            .add_proposal(proposal_one)
-            .add_proposal(proposal_two)
+            .guardian(alice())
+            .add_proposal(proposal)
+            .add_member(member)
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .register_user(alice(), storage_deposit(), storage_deposit())
             .build();
         let mut context_builder = get_context_builder(false);
         testing_env!(context_builder
             .block_timestamp(
                 contract.summoning_time
-                    + contract.period_duration
-                        * (contract.voting_period_length + contract.grace_period_length + 1)
+                    + (contract.period_duration
+                        * (contract.voting_period_length + contract.grace_period_length + 1)),
             )
+            .predecessor_account_id(alice().try_into().unwrap())
             .build());
-        contract.process_proposal(1.into());
+        contract.process_proposal(0.into());
+
+        assert!(contract.proposal_queue.get(0).unwrap().did_pass);
+        assert_eq!(contract.get_guardian(), Some(robert()));
+        assert_eq!(contract.members.get(&bob()).unwrap().shares, 1);
     }
 
     #[test]
-    fn rage_quit_basic() {
+    fn submit_upgrade_proposal() {
         let context = get_context(false);
         testing_env!(context);
-        let robert_member_info = MockMember::new()
-            .delegate_key(robert())
-            .highest_index_yes_vote(0)
-            .shares(30)
-            .build();
-        let proposal = MockProposal::new().processed(true).build();
         let mut contract = MockMoloch::new()
-            .add_proposal(proposal)
-            .add_member(robert_member_info)
+            .add_escrow_deposit(bob(), 101)
+            .register_user(bob(), storage_deposit(), storage_deposit())
             .build();
-        let mut context_builder = get_context_builder(false);
-        testing_env!(context_builder
-            .predecessor_account_id(robert().try_into().unwrap())
-            .build());
+        contract.submit_upgrade_proposal("factory.near".to_string(), 2.into(), "".to_string());
 
-        contract.rage_quit(30.into());
-        assert_eq!(
-            contract.total_shares, 1,
-            "Total shares have not properly been subtracted"
-        );
-        let member = contract.members.get(&robert()).unwrap();
+        let proposal = contract.proposal_queue.get(0).unwrap();
+        assert_eq!(proposal.proposer, bob());
+        assert_eq!(proposal.applicant, bob());
+        assert_eq!(proposal.shares_requested, 0);
+        assert_eq!(proposal.starting_period, 1);
         assert_eq!(
-            member.shares, 0,
-            "Shares have not been properly subtracted by a memeber"
+            proposal.kind,
+            ProposalKind::Upgrade("factory.near".to_string(), 2)
         );
+        let bob_balance = contract.get_escrow_user_balance(bob());
+        assert_eq!(u128::from(bob_balance), 1, "Deposit was not taken");
     }
+
     #[test]
-    fn rage_quit_partial() {
+    #[should_panic(expected = r#"Factory must be a valid account"#)]
+    fn submit_upgrade_proposal_invalid_factory() {
         let context = get_context(false);
         testing_env!(context);
-        let robert_member_info = MockMember::new()
-            .delegate_key(robert())
-            .highest_index_yes_vote(0)
-            .shares(30)
+        let mut contract = MockMoloch::new()
+            .add_escrow_deposit(bob(), 101)
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .build();
+        contract.submit_upgrade_proposal("Factory".to_string(), 2.into(), "".to_string());
+    }
+
+    #[test]
+    fn process_proposal_passed_upgrade() {
+        let context = get_context(false);
+        testing_env!(context);
+        let member = MockMember::new().build();
+        let proposal = MockProposal::new()
+            .applicant(bob())
+            .shares_requested(0)
+            .kind(ProposalKind::Upgrade("factory.near".to_string(), 2))
+            .yes_vote(&member)
             .build();
-        let proposal = MockProposal::new().processed(true).build();
         let mut contract = MockMoloch::new()
             .add_proposal(proposal)
-            .add_member(robert_member_info)
+            .add_member(member)
+            .register_user(bob(), storage_deposit(), storage_deposit())
             .build();
         let mut context_builder = get_context_builder(false);
         testing_env!(context_builder
-            .predecessor_account_id(robert().try_into().unwrap())
+            .block_timestamp(
+                contract.summoning_time
+                    + (contract.period_duration
+                        * (contract.voting_period_length + contract.grace_period_length + 1)),
+            )
             .build());
+        contract.process_proposal(0.into());
 
-        contract.rage_quit(20.into());
+        assert!(contract.proposal_queue.get(0).unwrap().did_pass);
+        assert!(get_logs()
+            .contains(&"Upgrade started! factory: factory.near, code_version: 2".to_string()));
+    }
+
+    #[test]
+    fn on_upgrade_code() {
+        let context = get_context(false);
+        testing_env!(context);
+        let mut contract = MockMoloch::new().build();
+        let code = Base64VecU8::from(vec![0, 97, 115, 109]);
+        testing_env!(
+            get_context(false),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            HashMap::default(),
+            vec![PromiseResult::Successful(
+                near_sdk::serde_json::to_vec(&code).unwrap()
+            )]
+        );
+        assert!(contract.on_upgrade_code("factory.near".to_string(), 2.into()));
         assert_eq!(
-            contract.total_shares, 11,
-            "Total shares have not properly been subtracted"
+            get_logs(),
+            vec!["Code deployed! factory: factory.near, code_version: 2".to_string()]
         );
-        let member = contract.members.get(&robert()).unwrap();
+    }
+
+    #[test]
+    fn on_upgrade_code_failed() {
+        let context = get_context(false);
+        testing_env!(context);
+        let mut contract = MockMoloch::new().build();
+        testing_env!(
+            get_context(false),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            HashMap::default(),
+            vec![PromiseResult::Failed]
+        );
+        assert!(!contract.on_upgrade_code("factory.near".to_string(), 2.into()));
         assert_eq!(
-            member.shares, 10,
-            "Shares have not been properly subtracted by a memeber"
+            get_logs(),
+            vec!["Upgrade failed! factory: factory.near, code_version: 2".to_string()]
         );
     }
 
-    // Highest proposal has not been indexed
     #[test]
-    #[should_panic(
-        expected = r#"Can't rage quit until the highest index proposal member voted YES is processed"#
-    )]
-    fn rage_quit_cant() {
+    fn submit_guild_kick_proposal() {
         let context = get_context(false);
         testing_env!(context);
-        let proposal = MockProposal::new().build();
-        let member = MockMember::new().delegate_key(robert()).shares(30).build();
+        let member = MockMember::new().build();
         let mut contract = MockMoloch::new()
             .add_member(member)
-            .add_proposal(proposal)
+            .add_escrow_deposit(bob(), 101)
+            .register_user(bob(), storage_deposit(), storage_deposit())
             .build();
-        let mut context_builder = get_context_builder(false);
-        testing_env!(context_builder
-            .predecessor_account_id(robert().try_into().unwrap())
-            .build());
+        contract.submit_guild_kick_proposal(robert(), "".to_string());
 
-        contract.rage_quit(20.into());
+        let proposal = contract.proposal_queue.get(0).unwrap();
+        assert_eq!(proposal.proposer, bob());
+        assert_eq!(proposal.applicant, bob());
+        assert_eq!(proposal.shares_requested, 0);
+        assert_eq!(proposal.kind, ProposalKind::GuildKick(robert()));
     }
 
-    // Not a member
     #[test]
-    #[should_panic(expected = r#"Account is not a member"#)]
-    fn rage_quit_not_a_member() {
+    #[should_panic(expected = r#"alice.testnet is not a member with shares"#)]
+    fn submit_guild_kick_proposal_not_a_member() {
         let context = get_context(false);
         testing_env!(context);
-        let proposal = MockProposal::new().build();
-        let mut contract = MockMoloch::new().add_proposal(proposal).build();
-        let mut context_builder = get_context_builder(false);
-        testing_env!(context_builder
-            .predecessor_account_id(robert().try_into().unwrap())
-            .build());
-
-        contract.rage_quit(20.into());
+        let mut contract = MockMoloch::new()
+            .add_escrow_deposit(bob(), 101)
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .build();
+        contract.submit_guild_kick_proposal(alice(), "".to_string());
     }
 
-    // Simple abort
     #[test]
-    fn abort() {
+    fn process_proposal_passed_guild_kick() {
         let context = get_context(false);
         testing_env!(context);
-        let proposal = MockProposal::new().applicant(robert()).build();
+        let member = MockMember::new().build();
+        let proposal = MockProposal::new()
+            .applicant(robert())
+            .shares_requested(0)
+            .kind(ProposalKind::GuildKick(robert()))
+            .yes_vote(&member)
+            .build();
         let mut contract = MockMoloch::new()
             .add_proposal(proposal)
-            .register_user(robert(), storage_deposit(), storage_deposit())
+            .add_member(member)
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .register_user(alice(), storage_deposit(), storage_deposit())
             .build();
+        contract.bank.deposit(110);
+        contract
+            .bank
+            .deposit_nft(Nft::new("nft.testnet".to_string(), "1".to_string()));
         let mut context_builder = get_context_builder(false);
         testing_env!(context_builder
-            .predecessor_account_id(robert().try_into().unwrap())
-            .block_timestamp(contract.summoning_time + contract.period_duration)
+            .block_timestamp(
+                contract.summoning_time
+                    + (contract.period_duration
+                        * (contract.voting_period_length + contract.grace_period_length + 1)),
+            )
+            .predecessor_account_id(alice().try_into().unwrap())
             .build());
-        contract.abort(0.into());
+        contract.process_proposal(0.into());
 
-        let proposal = contract.proposal_queue.get(0).unwrap();
-        assert_eq!(proposal.aborted, true);
-        assert_eq!(proposal.token_tribute, 0);
+        assert!(contract.proposal_queue.get(0).unwrap().did_pass);
+        assert_eq!(contract.members.get(&robert()).unwrap().shares, 0);
+        assert_eq!(contract.total_shares, 1);
+        assert_eq!(
+            u128::from(contract.get_withdrawable_balance(robert(), fdai())),
+            100,
+            "Kicked member was not credited their share of the guild bank"
+        );
+        assert_eq!(u64::from(contract.get_nft_claims(robert())), 0);
     }
 
-    // Proposal does not exist
     #[test]
-    #[should_panic(expected = r#"Proposal does not exist"#)]
-    fn abort_proposal_does_not_exist() {
+    fn process_proposal_passed_guild_kick_refunds_profile_storage() {
         let context = get_context(false);
         testing_env!(context);
-        let mut contract = MockMoloch::new().build();
+        let member = MockMember::new().build();
+        let proposal = MockProposal::new()
+            .applicant(bob())
+            .shares_requested(0)
+            .kind(ProposalKind::GuildKick(robert()))
+            .yes_vote(&member)
+            .build();
+        let mut contract = MockMoloch::new()
+            .add_proposal(proposal)
+            .add_member(member)
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .register_user(robert(), storage_deposit(), storage_deposit())
+            .build();
         let mut context_builder = get_context_builder(false);
         testing_env!(context_builder
             .predecessor_account_id(robert().try_into().unwrap())
-            .block_timestamp(contract.summoning_time + contract.period_duration)
             .build());
-        contract.abort(0.into());
-    }
-
-    // Caller is not applicant
-    #[test]
-    #[should_panic(expected = r#"Calling account is not the proposal applicant"#)]
-    fn abort_proposal_calling_account_is_not_applicant() {
-        let context = get_context(false);
-        testing_env!(context);
-        let proposal = MockProposal::new().applicant(robert()).build();
-        let mut contract = MockMoloch::new().add_proposal(proposal).build();
-        let mut context_builder = get_context_builder(false);
+        contract.set_member_profile("Robert".to_string(), None, vec![]);
+        assert!(
+            contract
+                .user_storage_accounts
+                .get(&robert())
+                .unwrap()
+                .used_bytes
+                > 0
+        );
         testing_env!(context_builder
-            .block_timestamp(contract.summoning_time + contract.period_duration)
+            .block_timestamp(
+                contract.summoning_time
+                    + (contract.period_duration
+                        * (contract.voting_period_length + contract.grace_period_length + 1)),
+            )
+            .predecessor_account_id(bob().try_into().unwrap())
             .build());
-        contract.abort(0.into());
+        let bob_storage = contract.user_storage_accounts.get(&bob()).unwrap();
+        contract.process_proposal(0.into());
+
+        assert_eq!(contract.members.get(&robert()).unwrap().profile, None);
+        let robert_storage = contract.user_storage_accounts.get(&robert()).unwrap();
+        assert_eq!(
+            robert_storage.used_bytes, 0,
+            "Profile bytes were not released"
+        );
+        assert_eq!(robert_storage.available, storage_deposit());
+        assert!(
+            contract
+                .user_storage_accounts
+                .get(&bob())
+                .unwrap()
+                .available
+                <= bob_storage.available,
+            "Proposer was refunded the profile's storage"
+        );
     }
 
-    // Abort window has passed
+    // Getter
     #[test]
-    #[should_panic(expected = r#"Abort window has passed!"#)]
-    fn abort_proposal_abort_window_has_passed() {
+    fn get_current_period() {
         let context = get_context(false);
         testing_env!(context);
-        let proposal = MockProposal::new().applicant(robert()).build();
-        let mut contract = MockMoloch::new().add_proposal(proposal).build();
-        let mut context_builder = get_context_builder(false);
-        testing_env!(context_builder
-            .predecessor_account_id(robert().try_into().unwrap())
-            .block_timestamp(contract.summoning_time + contract.period_duration * 5)
-            .build());
-        contract.abort(0.into());
+        let contract = MockMoloch::new().build();
+        let period = contract.get_current_period();
+        assert_eq!(u64::from(period), 0, "Current period is not 0")
     }
 
-    // Proposal has already been aborted
     #[test]
-    #[should_panic(expected = r#"Proposal has already been aborted"#)]
-    fn abort_proposal_proposal_has_already_been_aborted() {
+    fn get_current_period_after_three_periods() {
         let context = get_context(false);
         testing_env!(context);
-        let proposal = MockProposal::new()
-            .applicant(robert())
-            .aborted(true)
-            .build();
-        let mut contract = MockMoloch::new().add_proposal(proposal).build();
+        let contract = MockMoloch::new().build();
         let mut context_builder = get_context_builder(false);
         testing_env!(context_builder
-            .predecessor_account_id(robert().try_into().unwrap())
-            .block_timestamp(contract.summoning_time + contract.period_duration)
+            .block_timestamp(contract.summoning_time + contract.period_duration * 3)
             .build());
-        contract.abort(0.into());
+        let period = contract.get_current_period();
+        assert_eq!(u64::from(period), 3, "Current period is not 3")
     }
 
     #[test]
-    fn update_delegate_key() {
+    fn get_current_period_block_height_clock() {
         let context = get_context(false);
         testing_env!(context);
-        let mut contract = MockMoloch::new()
-            .register_user(bob(), storage_deposit(), storage_deposit())
+        let contract = MockMoloch::new()
+            .period_clock(PeriodClock::BlockHeight)
+            .period_duration(10)
             .build();
-        contract.update_delegate_key("soda".to_string());
-        let old_key = contract.members_by_delegate_key.get(&bob()).unwrap();
-        assert_eq!(
-            old_key,
-            "".to_string(),
-            "Old has not been updated to an empty string"
-        );
-        let new_key = contract
-            .members_by_delegate_key
-            .get(&"soda".to_string())
-            .unwrap();
-        assert_eq!(new_key, bob().to_string(), "New key has been created");
-        let member = contract.members.get(&bob()).unwrap();
-        assert_eq!(
-            member.delegate_key,
-            "soda".to_string(),
-            "Member delegate key has not been updated"
-        );
+        assert_eq!(contract.get_period_clock(), PeriodClock::BlockHeight);
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder
+            .block_index(contract.summoning_block_height + 25)
+            .block_timestamp(contract.summoning_time)
+            .build());
+        let period = contract.get_current_period();
+        assert_eq!(u64::from(period), 2, "Current period is not 2")
     }
 
     #[test]
-    #[should_panic(expected = r#"Account is not a member"#)]
-    fn update_delegate_key_only_member() {
+    fn get_current_period_block_height_clock_ignores_timestamp() {
         let context = get_context(false);
         testing_env!(context);
-        let mut contract = MockMoloch::new().build();
+        let contract = MockMoloch::new()
+            .period_clock(PeriodClock::BlockHeight)
+            .period_duration(10)
+            .build();
         let mut context_builder = get_context_builder(false);
         testing_env!(context_builder
-            .predecessor_account_id(robert().try_into().unwrap())
+            .block_index(contract.summoning_block_height)
+            .block_timestamp(contract.summoning_time + 10u64.pow(18))
             .build());
-        contract.update_delegate_key("soda".to_string());
+        let period = contract.get_current_period();
+        assert_eq!(u64::from(period), 0, "Current period is not 0")
     }
 
     #[test]
-    #[should_panic(expected = r#"Delegate key cannot be an empty string"#)]
-    fn update_delegate_key_empty_string() {
+    fn submit_vote_block_height_clock() {
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder.block_index(100).build());
+        let mut contract = MockMoloch::new()
+            .period_clock(PeriodClock::BlockHeight)
+            .period_duration(10)
+            .add_proposal(MockProposal::new().build())
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .build();
+        testing_env!(context_builder
+            .block_index(contract.summoning_block_height + 10)
+            .block_timestamp(contract.summoning_time)
+            .build());
+        contract.submit_vote(0.into(), 1);
+
+        let proposal = contract.proposal_queue.get(0).unwrap();
+        assert_eq!(proposal.yes_votes, 1);
+    }
+
+    #[test]
+    fn get_proposal_queue_length() {
         let context = get_context(false);
         testing_env!(context);
-        let mut contract = MockMoloch::new().build();
-        contract.update_delegate_key("".to_string());
+        let contract = MockMoloch::new().build();
+        let period = contract.get_proposal_queue_length();
+        assert_eq!(u64::from(period), 0)
     }
 
     #[test]
-    #[should_panic(expected = r#"Can't overwrite an existing members delegate_key"#)]
-    fn update_delegate_cannot_be_an_existing_member() {
+    fn get_proposal_queue_length_two() {
         let context = get_context(false);
         testing_env!(context);
-        let member = MockMember::new().build();
-        let mut contract = MockMoloch::new().add_member(member).build();
-        contract.update_delegate_key(robert().to_string());
+        let proposal_one = MockProposal::new().build();
+        let proposal_two = MockProposal::new().build();
+        let contract = MockMoloch::new()
+            .add_proposal(proposal_one)
+            .add_proposal(proposal_two)
+            .build();
+        let period = contract.get_proposal_queue_length();
+        assert_eq!(u64::from(period), 2)
+    }
+
+    fn share_event(event: &str, owner_id: &str, amount: &str, memo: &str) -> String {
+        format!(
+            r#"EVENT_JSON:{{"standard":"nep141","version":"1.0.0","event":"{}","data":[{{"owner_id":"{}","amount":"{}","memo":"{}"}}]}}"#,
+            event, owner_id, amount, memo
+        )
     }
 
     #[test]
-    #[should_panic(expected = r#"Can't overwrite existing delegate keys"#)]
-    fn update_delegate_cannot_be_an_existing_member_from_delegate_key() {
+    fn ft_views() {
         let context = get_context(false);
         testing_env!(context);
-        let member = MockMember::new().build();
-        let mut contract = MockMoloch::new()
-            .add_member(member)
-            .register_user(bob(), storage_deposit(), storage_deposit())
-            .build();
-        contract.update_delegate_key("soda".to_string());
-        let mut context_builder = get_context_builder(false);
-        testing_env!(context_builder
-            .predecessor_account_id(robert().try_into().unwrap())
-            .build());
-        contract.update_delegate_key("soda".to_string());
+        let member = MockMember::new().shares(10).build();
+        let contract = MockMoloch::new().add_member(member).build();
+        assert_eq!(u128::from(contract.ft_balance_of(bob())), 1);
+        assert_eq!(u128::from(contract.ft_balance_of(robert())), 10);
+        assert_eq!(u128::from(contract.ft_balance_of(alice())), 0);
+        assert_eq!(u128::from(contract.ft_total_supply()), 11);
+        let metadata = contract.ft_metadata();
+        assert_eq!(metadata.symbol, "SHARES");
+        assert_eq!(metadata.decimals, 0);
+        metadata.assert_valid();
     }
+
     #[test]
-    fn escrow_withdraw() {
+    #[should_panic(expected = r#"Shares are non-transferable"#)]
+    fn ft_transfer() {
         let context = get_context(false);
         testing_env!(context);
-        let member = MockMember::new().build();
-        let mut contract = MockMoloch::new()
-            .add_member(member)
-            .add_escrow_deposit(bob(), 10)
-            .register_user(bob(), storage_deposit(), storage_deposit())
-            .build();
-        contract.escrow_withdraw(bob(), 5.into());
-        let bob_balance = contract.get_escrow_user_balance(bob());
-        assert_eq!(u128::from(bob_balance), 5, "Bob's balance is incorrect");
+        let mut contract = MockMoloch::new().build();
+        contract.ft_transfer(robert(), 1.into(), None);
     }
 
     #[test]
-    #[should_panic(expected = r#"Predecessor account id does not equal withdrawl account id"#)]
-    fn escrow_withdraw_different_account() {
+    #[should_panic(expected = r#"Shares are non-transferable"#)]
+    fn ft_transfer_call() {
         let context = get_context(false);
         testing_env!(context);
-        let member = MockMember::new().build();
-        let mut contract = MockMoloch::new()
-            .add_member(member)
-            .add_escrow_deposit(robert(), 10)
-            .register_user(bob(), storage_deposit(), storage_deposit())
-            .build();
-        contract.escrow_withdraw(robert(), 5.into());
+        let mut contract = MockMoloch::new().build();
+        contract.ft_transfer_call(robert(), 1.into(), None, "".to_string());
     }
 
-    // Getter
     #[test]
-    fn get_current_period() {
+    fn summon_logs_share_mint() {
         let context = get_context(false);
         testing_env!(context);
-        let contract = MockMoloch::new().build();
-        let period = contract.get_current_period();
-        assert_eq!(u64::from(period), 0, "Current period is not 0")
+        MockMoloch::new().build();
+        assert!(get_logs().contains(&share_event("ft_mint", "bob.near", "1", "Summon")));
     }
 
     #[test]
-    fn get_current_period_after_three_periods() {
+    fn process_proposal_logs_share_mint() {
         let context = get_context(false);
         testing_env!(context);
-        let contract = MockMoloch::new().build();
+        let member = MockMember::new().shares(1).delegate_key(bob()).build();
+        let proposal = MockProposal::new()
+            .applicant(alice())
+            .yes_vote(&member)
+            .shares_requested(15)
+            .build();
+        let mut contract = MockMoloch::new()
+            .add_proposal(proposal)
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .build();
         let mut context_builder = get_context_builder(false);
         testing_env!(context_builder
-            .block_timestamp(contract.summoning_time + contract.period_duration * 3)
+            .block_timestamp(
+                contract.summoning_time
+                    + (contract.period_duration
+                        * (contract.voting_period_length + contract.grace_period_length + 1)),
+            )
             .build());
-        let period = contract.get_current_period();
-        assert_eq!(u64::from(period), 3, "Current period is not 3")
+        contract.process_proposal(0.into());
+        assert!(get_logs().contains(&share_event("ft_mint", &alice(), "15", "Proposal 0")));
     }
 
     #[test]
-    fn get_proposal_queue_length() {
+    fn rage_quit_logs_share_burn() {
         let context = get_context(false);
         testing_env!(context);
-        let contract = MockMoloch::new().build();
-        let period = contract.get_proposal_queue_length();
-        assert_eq!(u64::from(period), 0)
+        let mut contract = MockMoloch::new()
+            .add_proposal(MockProposal::new().processed(true).build())
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .build();
+        testing_env!(get_context(false));
+        contract.rage_quit(1.into());
+        assert!(get_logs().contains(&share_event("ft_burn", "bob.near", "1", "Rage quit")));
     }
 
     #[test]
-    fn get_proposal_queue_length_two() {
+    fn get_proposal_status() {
         let context = get_context(false);
         testing_env!(context);
-        let proposal_one = MockProposal::new().build();
-        let proposal_two = MockProposal::new().build();
+        let pending = MockProposal::new().build();
+        let passed = MockProposal::new().processed(true).did_pass(true).build();
         let contract = MockMoloch::new()
-            .add_proposal(proposal_one)
-            .add_proposal(proposal_two)
+            .add_proposal(pending)
+            .add_proposal(passed)
             .build();
-        let period = contract.get_proposal_queue_length();
-        assert_eq!(u64::from(period), 2)
+        assert_eq!(
+            contract.get_proposal_status(0.into()),
+            ProposalStatus {
+                proposer: bob(),
+                processed: false,
+                did_pass: false,
+                aborted: false,
+                starting_period: 1.into(),
+                current_period: 0.into(),
+                details: "".to_string(),
+            }
+        );
+        let status = contract.get_proposal_status(1.into());
+        assert!(status.processed && status.did_pass);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Proposal does not exist"#)]
+    fn get_proposal_status_not_a_proposal() {
+        let context = get_context(false);
+        testing_env!(context);
+        let contract = MockMoloch::new().build();
+        contract.get_proposal_status(0.into());
     }
 
     #[test]
@@ -2461,6 +7682,42 @@ mod tests {
         assert_eq!(vote, Vote::Null, "Bob has not voted yes yet")
     }
 
+    #[test]
+    fn get_member_share_history() {
+        let context = get_context(false);
+        testing_env!(context);
+        let mut member = MockMember::new().shares(10).build();
+        member.shares = 30;
+        member.checkpoint_shares(4);
+        member.shares = 20;
+        member.checkpoint_shares(4);
+        let contract = MockMoloch::new().add_member(member).build();
+        let history = contract.get_member_share_history(robert());
+        assert_eq!(
+            history,
+            vec![
+                (U64::from(0), U128::from(10)),
+                (U64::from(4), U128::from(20))
+            ],
+            "Share history is incorrect"
+        );
+    }
+
+    #[test]
+    fn get_member_voting_shares() {
+        let context = get_context(false);
+        testing_env!(context);
+        let mut member = MockMember::new().shares(10).build();
+        member.shares = 30;
+        member.checkpoint_shares(1);
+        let contract = MockMoloch::new()
+            .add_proposal(MockProposal::new().build())
+            .add_member(member)
+            .build();
+        let shares = contract.get_member_voting_shares(robert(), 0.into());
+        assert_eq!(u128::from(shares), 10, "Voting shares are incorrect");
+    }
+
     // Member does not exist
     #[test]
     #[should_panic(expected = r#"Member does not exist"#)]
@@ -2482,6 +7739,127 @@ mod tests {
         let contract = MockMoloch::new().build();
         contract.get_member_proposal_vote(bob(), 0.into());
     }
+    #[test]
+    fn get_escrow_balances() {
+        let context = get_context(false);
+        testing_env!(context);
+        let contract = MockMoloch::new()
+            .add_escrow_deposit(bob(), 1)
+            .add_escrow_deposit(robert(), 2)
+            .add_escrow_deposit(alice(), 3)
+            .build();
+        let balances = contract.get_escrow_balances(0.into(), 2.into());
+        assert_eq!(balances.len(), 2);
+        let rest = contract.get_escrow_balances(2.into(), 5.into());
+        assert_eq!(rest.len(), 1);
+        let mut all: Vec<(AccountId, u128)> = balances
+            .into_iter()
+            .chain(rest.into_iter())
+            .map(|(account_id, balance)| (account_id, u128::from(balance)))
+            .collect();
+        all.sort();
+        assert_eq!(all, vec![(alice(), 3), (bob(), 1), (robert(), 2)]);
+        assert_eq!(contract.get_escrow_balances(3.into(), 1.into()), vec![]);
+    }
+
+    #[test]
+    fn get_total_escrow_balance() {
+        let context = get_context(false);
+        testing_env!(context);
+        let contract = MockMoloch::new()
+            .add_proposal(MockProposal::new().build())
+            .add_escrow_deposit(alice(), 3)
+            .build();
+        // The proposal locks the proposal deposit and 12 tribute
+        assert_eq!(u128::from(contract.get_total_escrow_balance()), 115);
+    }
+
+    #[test]
+    fn withdraw_balance_statement() {
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder.build());
+        let mut contract = MockMoloch::new()
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .build();
+        contract.ledger.credit(bob(), fdai(), 10);
+        testing_env!(context_builder.attached_deposit(1).build());
+        contract.withdraw_balance(fdai(), 4.into());
+        let statement = contract.get_account_statement(bob());
+        assert_eq!(statement.len(), 1);
+        assert_eq!(statement[0].kind, StatementKind::Withdrawal);
+        assert_eq!(u128::from(statement[0].amount), 4);
+        let bob_storage = contract.user_storage_accounts.get(&bob()).unwrap();
+        assert!(
+            bob_storage.available < storage_deposit(),
+            "Statement entry was not charged"
+        );
+    }
+
+    #[test]
+    fn withdraw_balance_charges_refund_entry_up_front() {
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder.build());
+        let mut contract = MockMoloch::new()
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .build();
+        contract.ledger.credit(bob(), fdai(), 10);
+        let reserved_bytes = contract.statements.entry_bytes(&fdai(), None);
+        testing_env!(context_builder.attached_deposit(1).build());
+        contract.withdraw_balance(fdai(), 4.into());
+        let used_bytes = contract
+            .user_storage_accounts
+            .get(&bob())
+            .unwrap()
+            .used_bytes;
+
+        // A failed payout records the refund entry out of the reserved bytes
+        testing_env!(
+            get_context(false),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            HashMap::default(),
+            vec![PromiseResult::Failed]
+        );
+        contract.on_withdraw_balance(bob(), fdai(), 4.into(), reserved_bytes.into());
+        let statement = contract.get_account_statement(bob());
+        assert_eq!(statement[1].kind, StatementKind::Refund);
+        assert_eq!(
+            contract
+                .user_storage_accounts
+                .get(&bob())
+                .unwrap()
+                .used_bytes,
+            used_bytes,
+            "Refund entry was not paid for by the reservation"
+        );
+
+        // A successful payout gives the reservation back
+        testing_env!(context_builder.attached_deposit(1).build());
+        contract.withdraw_balance(fdai(), 4.into());
+        let used_bytes = contract
+            .user_storage_accounts
+            .get(&bob())
+            .unwrap()
+            .used_bytes;
+        testing_env!(
+            get_context(false),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            HashMap::default(),
+            vec![PromiseResult::Successful(vec![])]
+        );
+        contract.on_withdraw_balance(bob(), fdai(), 4.into(), reserved_bytes.into());
+        assert_eq!(
+            contract
+                .user_storage_accounts
+                .get(&bob())
+                .unwrap()
+                .used_bytes,
+            used_bytes - reserved_bytes,
+            "Reservation was not given back"
+        );
+    }
+
     #[test]
     fn get_user_escrow_balance() {
         let context = get_context(false);
@@ -2545,4 +7923,149 @@ mod tests {
             .build());
         contract.only_member()
     }
+
+    // Deterministic pseudo random numbers for the storage property tests
+    fn next_random(seed: &mut u64) -> u64 {
+        *seed = seed
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        *seed >> 33
+    }
+
+    // Registers account_id the way wallets do, so the account holds the min_balance reserve
+    fn register_with_storage_deposit(contract: &mut Moloch, account_id: &AccountId, amount: u128) {
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder.attached_deposit(amount).build());
+        contract.storage_deposit(Some(account_id.to_string().try_into().unwrap()), None);
+    }
+
+    fn assert_storage_balanced(contract: &Moloch, account_id: &AccountId, total: u128) {
+        let user_storage = contract.user_storage_accounts.get(account_id).unwrap();
+        let min_balance = contract.storage_balance_bounds().min.0;
+        assert_eq!(user_storage.total, total, "Total storage changed");
+        assert_eq!(
+            user_storage.available
+                + env::storage_byte_cost() * u128::from(user_storage.used_bytes)
+                + min_balance,
+            total,
+            "Available storage, used bytes and the min balance don't add up to the total"
+        );
+    }
+
+    #[test]
+    fn update_available_storage_property() {
+        let context = get_context(false);
+        testing_env!(context);
+        let mut contract = MockMoloch::new().build();
+        register_with_storage_deposit(&mut contract, &bob(), storage_deposit());
+        let mut seed = 7;
+        let mut storage_usage: u64 = 10_000;
+        for _ in 0..500 {
+            let user_storage = contract.user_storage_accounts.get(&bob()).unwrap();
+            let affordable_bytes = (user_storage.available / env::storage_byte_cost()) as u64;
+            let delta = next_random(&mut seed) % 100;
+            let next_storage_usage = match next_random(&mut seed) % 2 {
+                0 => storage_usage + min(delta, affordable_bytes),
+                _ => storage_usage - min(delta, user_storage.used_bytes + 20),
+            };
+            contract.update_available_storage(bob(), storage_usage, next_storage_usage);
+            storage_usage = next_storage_usage;
+            assert_storage_balanced(&contract, &bob(), storage_deposit());
+        }
+    }
+
+    #[test]
+    fn update_available_storage_refunds_only_paid_bytes() {
+        let context = get_context(false);
+        testing_env!(context);
+        let mut contract = MockMoloch::new()
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .build();
+        contract.update_available_storage(bob(), 100, 110);
+        contract.update_available_storage(bob(), 110, 80);
+        let user_storage = contract.user_storage_accounts.get(&bob()).unwrap();
+        assert_eq!(user_storage.used_bytes, 0);
+        assert_eq!(user_storage.available, storage_deposit());
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Insufficient deposit to pay for storage bob.near"#)]
+    fn update_available_storage_insufficient() {
+        let context = get_context(false);
+        testing_env!(context);
+        let mut contract = MockMoloch::new().register_user(bob(), 10, 5).build();
+        contract.update_available_storage(bob(), 100, 110);
+    }
+
+    #[test]
+    fn storage_ledger_round_trip_property() {
+        let context = get_context(false);
+        testing_env!(context);
+        let mut contract = MockMoloch::new().build();
+        register_with_storage_deposit(&mut contract, &robert(), storage_deposit());
+        let available = contract
+            .user_storage_accounts
+            .get(&robert())
+            .unwrap()
+            .available;
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder
+            .predecessor_account_id(robert().try_into().unwrap())
+            .build());
+        let proposers: Vec<AccountId> = (0..5).map(|i| format!("p{}.testnet", i)).collect();
+        let mut authorized = vec![false; proposers.len()];
+        let mut seed = 11;
+        for _ in 0..200 {
+            let i = (next_random(&mut seed) % proposers.len() as u64) as usize;
+            if authorized[i] {
+                contract.revoke_tribute_authorization(proposers[i].to_string());
+            } else {
+                let amount = next_random(&mut seed) as u128 + 1;
+                contract.authorize_tribute(proposers[i].to_string(), amount.into(), 0.into());
+            }
+            authorized[i] = !authorized[i];
+            assert_storage_balanced(&contract, &robert(), storage_deposit());
+        }
+        for (i, proposer) in proposers.iter().enumerate() {
+            if authorized[i] {
+                contract.revoke_tribute_authorization(proposer.to_string());
+            }
+        }
+        let user_storage = contract.user_storage_accounts.get(&robert()).unwrap();
+        assert_eq!(user_storage.used_bytes, 0, "Freed bytes were not refunded");
+        assert_eq!(user_storage.available, available);
+    }
+
+    #[test]
+    fn submit_proposal_charges_member_not_delegate_key() {
+        let context = get_context(false);
+        testing_env!(context);
+        let mut contract = MockMoloch::new()
+            .add_escrow_deposit(bob(), 113)
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .build();
+        contract.update_delegate_key(alice());
+        let used_bytes = contract
+            .user_storage_accounts
+            .get(&bob())
+            .unwrap()
+            .used_bytes;
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder
+            .predecessor_account_id(alice().try_into().unwrap())
+            .build());
+        contract.submit_proposal(
+            bob(),
+            12.into(),
+            0.into(),
+            vec![],
+            10.into(),
+            "".to_string(),
+        );
+        let user_storage = contract.user_storage_accounts.get(&bob()).unwrap();
+        assert!(
+            user_storage.used_bytes > used_bytes,
+            "Member was not charged"
+        );
+    }
 }
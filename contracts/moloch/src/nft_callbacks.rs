@@ -0,0 +1,361 @@
+use crate::*;
+
+use near_sdk::{env, ext_contract, near_bindgen, PromiseOrValue};
+
+#[ext_contract(ext_non_fungible_token)]
+pub trait NonFungibleTokenCore {
+    fn nft_transfer(
+        &mut self,
+        receiver_id: AccountId,
+        token_id: String,
+        approval_id: Option<u64>,
+        memo: Option<String>,
+    );
+}
+
+#[near_bindgen]
+impl Moloch {
+    /// NEP-171 receiver that deposits an NFT into the proposal escrow so it
+    /// can be offered as tribute
+    ///
+    /// An empty msg credits the sender's escrow. Otherwise msg is the account
+    /// id of a beneficiary, who must have registered storage with moloch. The
+    /// sender pays for the escrow entry out of their storage balance.
+    ///
+    /// Returns true when the NFT should be returned to the previous owner.
+    pub fn nft_on_transfer(
+        &mut self,
+        sender_id: AccountId,
+        previous_owner_id: AccountId,
+        token_id: String,
+        msg: String,
+    ) -> PromiseOrValue<bool> {
+        let initial_storage_usage = env::storage_usage();
+        self.assert_active();
+        self.assert_not_paused();
+        let nft = Nft::new(env::predecessor_account_id(), token_id);
+        if !self.user_storage_accounts.contains_key(&sender_id) {
+            env::log(
+                format!(
+                    "Sender {} has not registered storage, returning the NFT",
+                    sender_id
+                )
+                .as_bytes(),
+            );
+            return PromiseOrValue::Value(true);
+        };
+        let beneficiary_id = match msg.is_empty() {
+            true => sender_id.to_string(),
+            false => msg,
+        };
+        if !env::is_valid_account_id(beneficiary_id.as_bytes())
+            || !self.user_storage_accounts.contains_key(&beneficiary_id)
+        {
+            env::log(
+                format!(
+                    "Beneficiary {} has not registered storage, returning the NFT",
+                    beneficiary_id
+                )
+                .as_bytes(),
+            );
+            return PromiseOrValue::Value(true);
+        };
+        env::log(
+            format!(
+                "Escrow NFT deposit! sender: {}, previous_owner: {}, beneficiary: {}, nft_contract: {}, token_id: {}",
+                sender_id, previous_owner_id, beneficiary_id, nft.contract_id, nft.token_id
+            )
+            .as_bytes(),
+        );
+        self.escrow.deposit_nft(beneficiary_id.to_string(), nft);
+        // Panics if the sender can't cover the entry, which returns the NFT
+        self.sponsor_storage(sender_id, &beneficiary_id, initial_storage_usage);
+        PromiseOrValue::Value(false)
+    }
+
+    /// Withdraw an NFT the caller holds in escrow. If the transfer fails the NFT is put back in
+    /// escrow. The storage the escrow entry frees is given back once the transfer resolves.
+    pub fn escrow_withdraw_nft(&mut self, nft_contract_id: AccountId, token_id: String) -> Promise {
+        let initial_storage_usage = env::storage_usage();
+        let account_id = env::predecessor_account_id();
+        let nft = Nft::new(nft_contract_id, token_id);
+        self.escrow.withdraw_nft(account_id.to_string(), &nft);
+        let freed_bytes = initial_storage_usage.saturating_sub(env::storage_usage());
+        nft.transfer(account_id.to_string(), "Withdrawing NFT from Moloch escrow")
+            .then(ext_self::on_escrow_withdraw_nft(
+                account_id,
+                nft,
+                freed_bytes.into(),
+                &env::current_account_id(),
+                0,
+                env::prepaid_gas() / 4,
+            ))
+    }
+
+    /// Puts a NFT back in escrow if its escrow_withdraw_nft transfer failed, and refunds the
+    /// storage the account no longer uses. Returns whether the NFT was transferred.
+    #[private]
+    pub fn on_escrow_withdraw_nft(
+        &mut self,
+        account_id: AccountId,
+        nft: Nft,
+        freed_bytes: U64,
+    ) -> bool {
+        let initial_storage_usage = env::storage_usage() + u64::from(freed_bytes);
+        let transferred = nft_transferred(&account_id, &nft);
+        if !transferred {
+            self.escrow.deposit_nft(account_id.to_string(), nft);
+        };
+        if self.user_storage_accounts.contains_key(&account_id) {
+            self.update_available_storage(account_id, initial_storage_usage, env::storage_usage());
+        };
+        transferred
+    }
+
+    /// Uses one of the caller's NFT claims, granted when they rage quit or were kicked, to take
+    /// the vault NFT at index. See get_bank_nfts for the indexes. If the transfer fails the NFT
+    /// and the claim are put back.
+    pub fn claim_nft(&mut self, index: U64) -> Promise {
+        let initial_storage_usage = env::storage_usage();
+        let account_id = env::predecessor_account_id();
+        let nft = self.bank.claim_nft(&account_id, index.into());
+        env::log(
+            format!(
+                "NFT claimed! account: {}, nft_contract: {}, token_id: {}",
+                account_id, nft.contract_id, nft.token_id
+            )
+            .as_bytes(),
+        );
+        let freed_bytes = initial_storage_usage.saturating_sub(env::storage_usage());
+        nft.transfer(
+            account_id.to_string(),
+            "Claiming NFT from the guild bank vault",
+        )
+        .then(ext_self::on_claim_nft(
+            account_id,
+            nft,
+            freed_bytes.into(),
+            &env::current_account_id(),
+            0,
+            env::prepaid_gas() / 4,
+        ))
+    }
+
+    /// Puts a NFT and its claim back in the vault if the claim_nft transfer failed, and refunds
+    /// the storage the claim no longer uses. Returns whether the NFT was transferred.
+    #[private]
+    pub fn on_claim_nft(&mut self, account_id: AccountId, nft: Nft, freed_bytes: U64) -> bool {
+        let initial_storage_usage = env::storage_usage() + u64::from(freed_bytes);
+        let transferred = nft_transferred(&account_id, &nft);
+        if !transferred {
+            self.bank.return_nft_claim(&account_id, nft);
+        };
+        if self.user_storage_accounts.contains_key(&account_id) {
+            self.update_available_storage(account_id, initial_storage_usage, env::storage_usage());
+        };
+        transferred
+    }
+}
+
+/// Returns whether the nft_transfer a callback follows succeeded, and logs a failure
+fn nft_transferred(account_id: &AccountId, nft: &Nft) -> bool {
+    match env::promise_result(0) {
+        PromiseResult::Successful(_) => true,
+        _ => {
+            env::log(
+                format!(
+                    "NFT transfer failed, returned! account: {}, nft_contract: {}, token_id: {}",
+                    account_id, nft.contract_id, nft.token_id
+                )
+                .as_bytes(),
+            );
+            false
+        }
+    }
+}
+
+impl Nft {
+    /// Sends this NFT from moloch to the receiver
+    pub fn transfer(&self, receiver_id: AccountId, memo: &str) -> Promise {
+        let prepaid_gas = env::prepaid_gas();
+        ext_non_fungible_token::nft_transfer(
+            receiver_id,
+            self.token_id.to_string(),
+            None,
+            Some(memo.to_string()),
+            &self.contract_id,
+            1,
+            prepaid_gas / 4,
+        )
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mocks::{bob, get_context_builder, robert, storage_deposit, MockMoloch};
+    use near_sdk::{testing_env, MockedBlockchain, RuntimeFeesConfig, VMConfig};
+    use std::collections::HashMap;
+    use std::convert::TryInto;
+
+    fn nft_contract() -> AccountId {
+        "nft.testnet".to_string()
+    }
+
+    fn with_result(result: PromiseResult) {
+        testing_env!(
+            get_context_builder(false).build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            HashMap::default(),
+            vec![result]
+        );
+    }
+
+    fn returned(promise: PromiseOrValue<bool>) -> bool {
+        match promise {
+            PromiseOrValue::Promise(_) => false,
+            PromiseOrValue::Value(returned) => returned,
+        }
+    }
+
+    #[test]
+    fn nft_on_transfer_beneficiary() {
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder.build());
+        let mut contract = MockMoloch::new()
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .register_user(robert(), storage_deposit(), storage_deposit())
+            .build();
+        testing_env!(context_builder
+            .predecessor_account_id(nft_contract().try_into().unwrap())
+            .build());
+
+        let promise = contract.nft_on_transfer(bob(), bob(), "1".to_string(), robert());
+
+        assert_eq!(returned(promise), false, "NFT was returned");
+        assert_eq!(
+            contract.escrow.user_nfts(robert()),
+            vec![Nft::new(nft_contract(), "1".to_string())],
+            "Beneficiary escrow does not hold the NFT"
+        );
+        let bob_storage = contract.user_storage_accounts.get(&bob()).unwrap();
+        assert!(
+            bob_storage.available < storage_deposit(),
+            "Sender was not charged for the escrow entry"
+        );
+    }
+
+    #[test]
+    fn nft_on_transfer_sender_not_registered() {
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder.build());
+        let mut contract = MockMoloch::new().build();
+        testing_env!(context_builder
+            .predecessor_account_id(nft_contract().try_into().unwrap())
+            .build());
+
+        let promise = contract.nft_on_transfer(bob(), bob(), "1".to_string(), "".to_string());
+
+        assert_eq!(returned(promise), true, "NFT was not returned");
+        assert_eq!(contract.escrow.user_nfts(bob()), vec![]);
+    }
+
+    #[test]
+    fn escrow_withdraw_nft() {
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder.build());
+        let mut contract = MockMoloch::new().build();
+        contract
+            .escrow
+            .deposit_nft(bob(), Nft::new(nft_contract(), "1".to_string()));
+        testing_env!(context_builder.attached_deposit(1).build());
+
+        contract.escrow_withdraw_nft(nft_contract(), "1".to_string());
+
+        assert_eq!(contract.escrow.user_nfts(bob()), vec![]);
+    }
+
+    #[test]
+    fn escrow_withdraw_nft_while_paused() {
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder.build());
+        let mut contract = MockMoloch::new().build();
+        contract
+            .escrow
+            .deposit_nft(bob(), Nft::new(nft_contract(), "1".to_string()));
+        contract.paused = true;
+
+        contract.escrow_withdraw_nft(nft_contract(), "1".to_string());
+
+        assert_eq!(contract.escrow.user_nfts(bob()), vec![]);
+    }
+
+    #[test]
+    fn escrow_withdraw_nft_refunds_storage() {
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder.build());
+        let mut contract = MockMoloch::new()
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .build();
+        testing_env!(context_builder
+            .predecessor_account_id(nft_contract().try_into().unwrap())
+            .build());
+        contract.nft_on_transfer(bob(), bob(), "1".to_string(), "".to_string());
+        testing_env!(context_builder
+            .predecessor_account_id(bob().try_into().unwrap())
+            .build());
+
+        contract.escrow_withdraw_nft(nft_contract(), "1".to_string());
+        let bob_storage = contract.user_storage_accounts.get(&bob()).unwrap();
+        assert!(bob_storage.used_bytes > 0, "Storage was refunded early");
+        let nft = Nft::new(nft_contract(), "1".to_string());
+        let freed_bytes = bob_storage.used_bytes;
+        with_result(PromiseResult::Successful(vec![]));
+        assert!(contract.on_escrow_withdraw_nft(bob(), nft, freed_bytes.into()));
+
+        let bob_storage = contract.user_storage_accounts.get(&bob()).unwrap();
+        assert_eq!(bob_storage.used_bytes, 0, "Freed storage was not refunded");
+        assert_eq!(bob_storage.available, storage_deposit());
+    }
+
+    #[test]
+    fn on_escrow_withdraw_nft_failed_returns_nft() {
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder.build());
+        let mut contract = MockMoloch::new()
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .build();
+        let nft = Nft::new(nft_contract(), "1".to_string());
+        contract.escrow.deposit_nft(bob(), nft.clone());
+        contract.escrow_withdraw_nft(nft_contract(), "1".to_string());
+
+        with_result(PromiseResult::Failed);
+        assert!(!contract.on_escrow_withdraw_nft(bob(), nft.clone(), 0.into()));
+
+        assert_eq!(contract.escrow.user_nfts(bob()), vec![nft]);
+    }
+
+    #[test]
+    fn on_claim_nft_failed_returns_nft_and_claim() {
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder.build());
+        let mut contract = MockMoloch::new().build();
+        let nft = Nft::new(nft_contract(), "1".to_string());
+        contract.bank.deposit_nft(nft.clone());
+        contract.bank.grant_nft_claims(bob(), 1, 1);
+        contract.claim_nft(0.into());
+        assert_eq!(contract.bank.nft_claims(&bob()), 0);
+
+        with_result(PromiseResult::Failed);
+        assert!(!contract.on_claim_nft(bob(), nft.clone(), 0.into()));
+
+        assert_eq!(
+            contract.bank.nft_claims(&bob()),
+            1,
+            "Claim was not returned"
+        );
+        assert_eq!(contract.bank.get_nfts(0, 10), vec![nft]);
+    }
+}
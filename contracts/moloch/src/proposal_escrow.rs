@@ -1,59 +1,238 @@
 extern crate near_sdk;
 
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::collections::UnorderedMap;
+use near_sdk::collections::{LookupMap, UnorderedMap};
 use near_sdk::{near_bindgen, AccountId, PanicOnDefault};
 
+use std::cmp::min;
+
+use crate::{EscrowLock, LockPurpose, Nft};
+
 #[near_bindgen]
 #[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
 pub struct ProposalEscrow {
     user_balances: UnorderedMap<AccountId, u128>,
+    /// Sum of every account's token balance
+    total_balance: u128,
+    /// Native NEAR held for proposals, kept apart from storage deposits
+    user_near_balances: UnorderedMap<AccountId, u128>,
+    /// NFTs held for proposals
+    user_nfts: UnorderedMap<AccountId, Vec<Nft>>,
+    /// Funds locked for proposals, keyed by proposal index and purpose
+    locks: LookupMap<(u64, LockPurpose), EscrowLock>,
+    /// The locks each account has funds in
+    user_locks: LookupMap<AccountId, Vec<(u64, LockPurpose)>>,
+    /// Sum of the token amounts in every lock
+    total_locked: u128,
 }
 
 impl ProposalEscrow {
     pub fn new() -> Self {
         Self {
             user_balances: UnorderedMap::new(b"user_balances".to_vec()),
+            total_balance: 0,
+            user_near_balances: UnorderedMap::new(b"user_near_balances".to_vec()),
+            user_nfts: UnorderedMap::new(b"user_nfts".to_vec()),
+            locks: LookupMap::new(b"escrow_locks".to_vec()),
+            user_locks: LookupMap::new(b"escrow_user_locks".to_vec()),
+            total_locked: 0,
         }
     }
 
     pub fn deposit(&mut self, account_id: AccountId, amount: u128) -> u128 {
-        let balance = match self.user_balances.get(&account_id) {
+        self.total_balance += amount;
+        deposit_into(&mut self.user_balances, account_id, amount)
+    }
+
+    pub fn withdraw(&mut self, account_id: AccountId, amount: u128) -> u128 {
+        let updated_balance = withdraw_from(&mut self.user_balances, account_id, amount);
+        self.total_balance -= amount;
+        updated_balance
+    }
+
+    /// Token balances from the from_index'th account, at most limit of them
+    pub fn balances(&self, from_index: u64, limit: u64) -> Vec<(AccountId, u128)> {
+        let keys = self.user_balances.keys_as_vector();
+        let values = self.user_balances.values_as_vector();
+        (from_index..min(from_index.saturating_add(limit), keys.len()))
+            .map(|index| (keys.get(index).unwrap(), values.get(index).unwrap()))
+            .collect()
+    }
+
+    pub fn total_balance(&self) -> u128 {
+        self.total_balance
+    }
+
+    pub fn user_balance(&self, account_id: AccountId) -> u128 {
+        return match self.user_balances.get(&account_id) {
             Some(balance) => balance,
             None => 0,
         };
-        let updated_balance = balance + amount;
-        self.user_balances.insert(&account_id, &updated_balance);
-        updated_balance
     }
 
-    pub fn withdraw(&mut self, account_id: AccountId, amount: u128) -> u128 {
-        let balance = match self.user_balances.get(&account_id) {
+    pub fn deposit_near(&mut self, account_id: AccountId, amount: u128) -> u128 {
+        deposit_into(&mut self.user_near_balances, account_id, amount)
+    }
+
+    pub fn withdraw_near(&mut self, account_id: AccountId, amount: u128) -> u128 {
+        withdraw_from(&mut self.user_near_balances, account_id, amount)
+    }
+
+    pub fn user_near_balance(&self, account_id: AccountId) -> u128 {
+        return match self.user_near_balances.get(&account_id) {
             Some(balance) => balance,
-            None => 0u128,
+            None => 0,
         };
-        let updated_balance = match balance.checked_sub(amount) {
-            Some(balance) => balance,
+    }
+
+    /// Moves an account's token and NEAR balances into a lock for a proposal. Nothing is locked
+    /// when both amounts are 0.
+    pub fn lock(
+        &mut self,
+        proposal_index: u64,
+        account_id: AccountId,
+        amount: u128,
+        near_amount: u128,
+        purpose: LockPurpose,
+    ) {
+        let key = (proposal_index, purpose);
+        assert!(
+            self.locks.get(&key).is_none(),
+            "Proposal {} already has a {:?} lock",
+            proposal_index,
+            purpose
+        );
+        if amount == 0 && near_amount == 0 {
+            return;
+        };
+        self.withdraw(account_id.to_string(), amount);
+        self.withdraw_near(account_id.to_string(), near_amount);
+        self.total_locked += amount;
+        self.locks.insert(
+            &key,
+            &EscrowLock {
+                proposal_index: proposal_index,
+                owner: account_id.to_string(),
+                amount: amount,
+                near_amount: near_amount,
+                purpose: purpose,
+            },
+        );
+        let mut user_locks = self.user_locks.get(&account_id).unwrap_or_default();
+        user_locks.push(key);
+        self.user_locks.insert(&account_id, &user_locks);
+    }
+
+    /// Removes a proposal's lock, the funds leave escrow with the returned lock. Returns None if
+    /// nothing was locked.
+    pub fn release(&mut self, proposal_index: u64, purpose: LockPurpose) -> Option<EscrowLock> {
+        let key = (proposal_index, purpose);
+        let lock = self.locks.remove(&key)?;
+        self.total_locked -= lock.amount;
+        let mut user_locks = self.user_locks.get(&lock.owner).unwrap();
+        user_locks.retain(|user_lock| *user_lock != key);
+        if user_locks.is_empty() {
+            self.user_locks.remove(&lock.owner);
+        } else {
+            self.user_locks.insert(&lock.owner, &user_locks);
+        };
+        Some(lock)
+    }
+
+    /// Removes a proposal's lock and returns its funds to the owner's balances
+    pub fn refund(&mut self, proposal_index: u64, purpose: LockPurpose) -> Option<EscrowLock> {
+        let lock = self.release(proposal_index, purpose)?;
+        self.deposit(lock.owner.to_string(), lock.amount);
+        self.deposit_near(lock.owner.to_string(), lock.near_amount);
+        Some(lock)
+    }
+
+    pub fn get_lock(&self, proposal_index: u64, purpose: LockPurpose) -> Option<EscrowLock> {
+        self.locks.get(&(proposal_index, purpose))
+    }
+
+    /// The account's active locks, oldest first
+    pub fn user_locks(&self, account_id: AccountId) -> Vec<EscrowLock> {
+        self.user_locks
+            .get(&account_id)
+            .unwrap_or_default()
+            .iter()
+            .map(|key| self.locks.get(key).unwrap())
+            .collect()
+    }
+
+    pub fn total_locked(&self) -> u128 {
+        self.total_locked
+    }
+
+    pub fn deposit_nft(&mut self, account_id: AccountId, nft: Nft) {
+        let mut nfts = self.user_nfts(account_id.to_string());
+        nfts.push(nft);
+        self.user_nfts.insert(&account_id, &nfts);
+    }
+
+    pub fn withdraw_nft(&mut self, account_id: AccountId, nft: &Nft) {
+        let mut nfts = self.user_nfts(account_id.to_string());
+        let position = match nfts.iter().position(|held| held == nft) {
+            Some(position) => position,
             None => panic!(
-                "Insuffcient balance to withdraw requested amount for {}!",
-                account_id
+                "NFT {} from {} is not held in escrow for {}!",
+                nft.token_id, nft.contract_id, account_id
             ),
         };
-        self.user_balances.insert(&account_id, &updated_balance);
-        if updated_balance == 0 {
-            self.user_balances.remove(&account_id);
+        nfts.swap_remove(position);
+        if nfts.is_empty() {
+            self.user_nfts.remove(&account_id);
+        } else {
+            self.user_nfts.insert(&account_id, &nfts);
         };
-        updated_balance
     }
 
-    pub fn user_balance(&self, account_id: AccountId) -> u128 {
-        return match self.user_balances.get(&account_id) {
-            Some(balance) => balance,
-            None => 0,
+    pub fn user_nfts(&self, account_id: AccountId) -> Vec<Nft> {
+        return match self.user_nfts.get(&account_id) {
+            Some(nfts) => nfts,
+            None => vec![],
         };
     }
 }
 
+fn deposit_into(
+    balances: &mut UnorderedMap<AccountId, u128>,
+    account_id: AccountId,
+    amount: u128,
+) -> u128 {
+    let balance = match balances.get(&account_id) {
+        Some(balance) => balance,
+        None => 0,
+    };
+    let updated_balance = balance + amount;
+    balances.insert(&account_id, &updated_balance);
+    updated_balance
+}
+
+fn withdraw_from(
+    balances: &mut UnorderedMap<AccountId, u128>,
+    account_id: AccountId,
+    amount: u128,
+) -> u128 {
+    let balance = match balances.get(&account_id) {
+        Some(balance) => balance,
+        None => 0u128,
+    };
+    let updated_balance = match balance.checked_sub(amount) {
+        Some(balance) => balance,
+        None => panic!(
+            "Insuffcient balance to withdraw requested amount for {}!",
+            account_id
+        ),
+    };
+    balances.insert(&account_id, &updated_balance);
+    if updated_balance == 0 {
+        balances.remove(&account_id);
+    };
+    updated_balance
+}
+
 #[cfg(not(target_arch = "wasm32"))]
 #[cfg(test)]
 mod tests {
@@ -84,7 +263,7 @@ mod tests {
         testing_env!(context);
 
         let mut proposal_escrow = ProposalEscrow::new();
-        proposal_escrow.user_balances.insert(&bob(), &20);
+        proposal_escrow.deposit(bob(), 20);
         let balance = proposal_escrow.deposit(bob(), 100);
 
         assert_eq!(balance, 120, "Balance does not equal 120");
@@ -99,12 +278,17 @@ mod tests {
         testing_env!(context);
 
         let mut proposal_escrow = ProposalEscrow::new();
-        proposal_escrow.user_balances.insert(&bob(), &20);
+        proposal_escrow.deposit(bob(), 20);
         let balance = proposal_escrow.withdraw(bob(), 17);
 
         assert_eq!(balance, 3, "Balance does not equal 3");
         let balance = proposal_escrow.user_balances.get(&bob()).unwrap();
-        assert_eq!(balance, 3, "Saved balance does not equal 3")
+        assert_eq!(balance, 3, "Saved balance does not equal 3");
+        assert_eq!(
+            proposal_escrow.total_balance(),
+            3,
+            "Total balance does not equal 3"
+        )
     }
 
     // Withdraw without a previous balance
@@ -115,7 +299,131 @@ mod tests {
         testing_env!(context);
 
         let mut proposal_escrow = ProposalEscrow::new();
-        proposal_escrow.user_balances.insert(&bob(), &20);
+        proposal_escrow.deposit(bob(), 20);
         proposal_escrow.withdraw(bob(), 21);
     }
+
+    // Native NEAR is tracked apart from the approved token
+    #[test]
+    fn deposit_near_separate_from_token() {
+        let context = get_context(false);
+        testing_env!(context);
+
+        let mut proposal_escrow = ProposalEscrow::new();
+        proposal_escrow.deposit(bob(), 20);
+        let balance = proposal_escrow.deposit_near(bob(), 100);
+
+        assert_eq!(balance, 100, "NEAR balance does not equal 100");
+        assert_eq!(
+            proposal_escrow.user_balance(bob()),
+            20,
+            "Token balance does not equal 20"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Insuffcient balance to withdraw requested amount for bob.near"#)]
+    fn withdraw_near_with_no_balance() {
+        let context = get_context(false);
+        testing_env!(context);
+
+        let mut proposal_escrow = ProposalEscrow::new();
+        proposal_escrow.deposit(bob(), 20);
+        proposal_escrow.withdraw_near(bob(), 1);
+    }
+
+    #[test]
+    fn deposit_and_withdraw_nft() {
+        let context = get_context(false);
+        testing_env!(context);
+
+        let mut proposal_escrow = ProposalEscrow::new();
+        let nft = Nft::new("nft.near".to_string(), "1".to_string());
+        proposal_escrow.deposit_nft(bob(), nft.clone());
+        assert_eq!(proposal_escrow.user_nfts(bob()), vec![nft.clone()]);
+
+        proposal_escrow.withdraw_nft(bob(), &nft);
+        assert_eq!(proposal_escrow.user_nfts(bob()), vec![]);
+        assert!(proposal_escrow.user_nfts.get(&bob()).is_none());
+    }
+
+    #[test]
+    fn lock_and_refund() {
+        let context = get_context(false);
+        testing_env!(context);
+
+        let mut proposal_escrow = ProposalEscrow::new();
+        proposal_escrow.deposit(bob(), 20);
+        proposal_escrow.deposit_near(bob(), 5);
+        proposal_escrow.lock(3, bob(), 12, 5, LockPurpose::Tribute);
+        assert_eq!(proposal_escrow.user_balance(bob()), 8);
+        assert_eq!(proposal_escrow.user_near_balance(bob()), 0);
+        assert_eq!(proposal_escrow.total_balance(), 8);
+        assert_eq!(proposal_escrow.total_locked(), 12);
+        let lock = EscrowLock {
+            proposal_index: 3,
+            owner: bob(),
+            amount: 12,
+            near_amount: 5,
+            purpose: LockPurpose::Tribute,
+        };
+        assert_eq!(proposal_escrow.user_locks(bob()), vec![lock.clone()]);
+
+        assert_eq!(proposal_escrow.refund(3, LockPurpose::Tribute), Some(lock));
+        assert_eq!(proposal_escrow.user_balance(bob()), 20);
+        assert_eq!(proposal_escrow.user_near_balance(bob()), 5);
+        assert_eq!(proposal_escrow.total_locked(), 0);
+        assert_eq!(proposal_escrow.user_locks(bob()), vec![]);
+        assert!(proposal_escrow.user_locks.get(&bob()).is_none());
+    }
+
+    #[test]
+    fn release_removes_funds() {
+        let context = get_context(false);
+        testing_env!(context);
+
+        let mut proposal_escrow = ProposalEscrow::new();
+        proposal_escrow.deposit(bob(), 20);
+        proposal_escrow.lock(0, bob(), 10, 0, LockPurpose::Deposit);
+        proposal_escrow.lock(1, bob(), 10, 0, LockPurpose::Deposit);
+        let lock = proposal_escrow.release(0, LockPurpose::Deposit).unwrap();
+        assert_eq!(lock.amount, 10);
+        assert_eq!(proposal_escrow.user_balance(bob()), 0);
+        assert_eq!(proposal_escrow.total_locked(), 10);
+        assert_eq!(proposal_escrow.user_locks(bob()).len(), 1);
+        assert_eq!(proposal_escrow.release(0, LockPurpose::Deposit), None);
+    }
+
+    #[test]
+    fn lock_nothing() {
+        let context = get_context(false);
+        testing_env!(context);
+
+        let mut proposal_escrow = ProposalEscrow::new();
+        proposal_escrow.lock(0, bob(), 0, 0, LockPurpose::Tribute);
+        assert_eq!(proposal_escrow.get_lock(0, LockPurpose::Tribute), None);
+        assert_eq!(proposal_escrow.refund(0, LockPurpose::Tribute), None);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Insuffcient balance to withdraw requested amount for bob.near"#)]
+    fn lock_insufficient_balance() {
+        let context = get_context(false);
+        testing_env!(context);
+
+        let mut proposal_escrow = ProposalEscrow::new();
+        proposal_escrow.deposit(bob(), 5);
+        proposal_escrow.lock(0, bob(), 6, 0, LockPurpose::Deposit);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"NFT 1 from nft.near is not held in escrow for bob.near"#)]
+    fn withdraw_nft_not_held() {
+        let context = get_context(false);
+        testing_env!(context);
+
+        let mut proposal_escrow = ProposalEscrow::new();
+        let nft = Nft::new("nft.near".to_string(), "1".to_string());
+        proposal_escrow.withdraw_nft(bob(), &nft);
+    }
 }
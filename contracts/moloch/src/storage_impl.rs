@@ -2,7 +2,8 @@ use crate::*;
 use near_contract_standards::storage_management::{
     StorageBalance, StorageBalanceBounds, StorageManagement,
 };
-use near_sdk::json_types::{ValidAccountId, U128};
+use near_sdk::json_types::{ValidAccountId, U128, U64};
+use std::cmp::min;
 use std::convert::TryFrom;
 
 use near_sdk::{assert_one_yocto, env, Balance, Promise};
@@ -37,6 +38,7 @@ impl StorageManagement for Moloch {
         let mut user_storage = UserStorageBalance {
             total: 0,
             available: 0,
+            used_bytes: 0,
         };
         let min_balance = self.storage_balance_bounds().min.0;
         let user_storage_opt = self.user_storage_accounts.get(&account_id);
@@ -58,6 +60,7 @@ impl StorageManagement for Moloch {
                 &UserStorageBalance {
                     total: user_storage.total + amount,
                     available: available,
+                    used_bytes: user_storage.used_bytes,
                 },
             );
             return self.storage_balance_of(valid_account_id).unwrap();
@@ -78,6 +81,7 @@ impl StorageManagement for Moloch {
             &UserStorageBalance {
                 total: min_balance,
                 available: 0,
+                used_bytes: 0,
             },
         );
 
@@ -111,6 +115,7 @@ impl StorageManagement for Moloch {
             let new_storage_balance = UserStorageBalance {
                 total: total,
                 available: 0,
+                used_bytes: storage_account.used_bytes,
             };
             self.user_storage_accounts
                 .insert(&predecessor_account_id, &new_storage_balance);
@@ -139,6 +144,7 @@ impl StorageManagement for Moloch {
             &UserStorageBalance {
                 total: new_storage_balance.total.into(),
                 available: new_storage_balance.available.into(),
+                used_bytes: storage_account.used_bytes,
             },
         );
         new_storage_balance
@@ -152,9 +158,23 @@ impl StorageManagement for Moloch {
         let account_id = env::predecessor_account_id();
         let force = force.unwrap_or(false);
         if let Some(balance) = self.user_storage_accounts.get(&account_id) {
+            // Bytes the account paid for are still in state and would be left unpaid
+            assert!(
+                balance.used_bytes == 0,
+                "Can't unregister the account while it pays for {} bytes of storage",
+                balance.used_bytes
+            );
+            // Sponsors could no longer be refunded once the account is gone
+            let sponsored_bytes = self.sponsored_bytes(&account_id);
+            assert!(
+                sponsored_bytes == 0,
+                "Can't unregister the account while others pay for {} bytes of its storage",
+                sponsored_bytes
+            );
             if balance.available == 0 || force {
                 self.user_storage_accounts.remove(&account_id);
-                Promise::new(account_id.clone()).transfer(balance.available + 1);
+                // The account gets back its min_balance reserve with the available balance
+                Promise::new(account_id.clone()).transfer(balance.total + 1);
                 true
             } else {
                 env::panic(b"Can't unregister the account with a positive balance without a force")
@@ -188,11 +208,104 @@ impl StorageManagement for Moloch {
     }
 }
 
+#[near_bindgen]
+impl Moloch {
+    /// Adds the attached deposit to the DAO storage pool. The pool pays for processing proposals
+    /// when neither the proposer nor the applicant can. The funder can take back what the pool
+    /// hasn't spent with withdraw_storage_pool, less the storage of their funder entry.
+    #[payable]
+    pub fn fund_storage_pool(&mut self) -> U128 {
+        let initial_storage_usage = env::storage_usage();
+        let amount = env::attached_deposit();
+        assert!(amount > 0, "Attached deposit must be greater than 0");
+        let funder_id = env::predecessor_account_id();
+        let funded = self.storage_pool_funders.get(&funder_id).unwrap_or(0);
+        self.storage_pool_funders
+            .insert(&funder_id, &(funded + amount));
+        let storage_cost = env::storage_byte_cost()
+            * Balance::from(env::storage_usage().saturating_sub(initial_storage_usage));
+        assert!(
+            amount > storage_cost,
+            "Attached deposit must be greater than the storage cost {}",
+            storage_cost
+        );
+        let amount = amount - storage_cost;
+        self.storage_pool_funders
+            .insert(&funder_id, &(funded + amount));
+        self.storage_pool += amount;
+        env::log(
+            format!(
+                "Storage pool funded! sender: {}, amount: {}",
+                funder_id, amount
+            )
+            .as_bytes(),
+        );
+        self.storage_pool.into()
+    }
+
+    /// A funder takes NEAR they put into the storage pool back out, all of it when amount is
+    /// omitted. Only what the pool hasn't spent can be withdrawn.
+    #[payable]
+    pub fn withdraw_storage_pool(&mut self, amount: Option<U128>) -> U128 {
+        assert_one_yocto();
+        let funder_id = env::predecessor_account_id();
+        let funded = self.storage_pool_funders.get(&funder_id).unwrap_or(0);
+        let amount = match amount {
+            Some(amount) => amount.into(),
+            None => min(funded, self.storage_pool),
+        };
+        assert!(amount > 0, "Nothing to withdraw from the storage pool");
+        assert!(
+            amount <= funded,
+            "Requested amount is greater than what {} funded",
+            funder_id
+        );
+        assert!(
+            amount <= self.storage_pool,
+            "Requested amount is greater than the storage pool"
+        );
+        self.storage_pool -= amount;
+        self.storage_pool_funders
+            .insert(&funder_id, &(funded - amount));
+        env::log(
+            format!(
+                "Storage pool withdrawn! account: {}, amount: {}",
+                funder_id, amount
+            )
+            .as_bytes(),
+        );
+        Promise::new(funder_id).transfer(amount);
+        self.storage_pool.into()
+    }
+
+    /// Returns the NEAR account_id can still withdraw from the storage pool
+    pub fn get_storage_pool_funding(&self, account_id: ValidAccountId) -> U128 {
+        let funded = self
+            .storage_pool_funders
+            .get(&account_id.to_string())
+            .unwrap_or(0);
+        min(funded, self.storage_pool).into()
+    }
+
+    /// Returns the NEAR left in the DAO storage pool
+    pub fn get_storage_pool(&self) -> U128 {
+        self.storage_pool.into()
+    }
+
+    /// Returns the bytes an account has paid for out of its storage balance
+    pub fn get_storage_used_bytes(&self, account_id: ValidAccountId) -> U64 {
+        match self.user_storage_accounts.get(&account_id.to_string()) {
+            Some(user_storage) => user_storage.used_bytes.into(),
+            None => 0.into(),
+        }
+    }
+}
+
 #[cfg(not(target_arch = "wasm32"))]
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::mocks::{bob, get_context, get_context_builder, MockMoloch};
+    use crate::mocks::{alice, bob, get_context, get_context_builder, storage_deposit, MockMoloch};
     use near_sdk::{testing_env, MockedBlockchain};
     use std::convert::TryInto;
 
@@ -327,6 +440,61 @@ mod tests {
         );
     }
 
+    #[test]
+    fn fund_storage_pool() {
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder.build());
+        let mut contract = MockMoloch::new().build();
+        let deposit = storage_deposit();
+        testing_env!(context_builder.attached_deposit(deposit).build());
+        let first = u128::from(contract.fund_storage_pool());
+        assert!(first < deposit, "Funder entry storage was not charged");
+        let pool = contract.fund_storage_pool();
+        assert_eq!(
+            u128::from(pool),
+            first + deposit,
+            "Storage pool is incorrect"
+        );
+        let funding = contract.get_storage_pool_funding(bob().try_into().unwrap());
+        assert_eq!(funding, pool, "Funding is incorrect");
+    }
+
+    #[test]
+    fn withdraw_storage_pool() {
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder.build());
+        let mut contract = MockMoloch::new().build();
+        testing_env!(context_builder.attached_deposit(storage_deposit()).build());
+        let funded = u128::from(contract.fund_storage_pool());
+        // The pool spent some of the funding on proposals
+        contract.storage_pool -= 10;
+        let funding = contract.get_storage_pool_funding(bob().try_into().unwrap());
+        assert_eq!(u128::from(funding), funded - 10, "Funding is incorrect");
+        testing_env!(context_builder.attached_deposit(1).build());
+        let pool = contract.withdraw_storage_pool(None);
+        assert_eq!(u128::from(pool), 0, "Storage pool is incorrect");
+        assert_eq!(
+            contract.storage_pool_funders.get(&bob()),
+            Some(10),
+            "Funding is incorrect"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Requested amount is greater than what alice.testnet funded"#)]
+    fn withdraw_storage_pool_not_funder() {
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder.build());
+        let mut contract = MockMoloch::new().build();
+        testing_env!(context_builder.attached_deposit(storage_deposit()).build());
+        contract.fund_storage_pool();
+        testing_env!(context_builder
+            .predecessor_account_id(alice().try_into().unwrap())
+            .attached_deposit(1)
+            .build());
+        contract.withdraw_storage_pool(Some(1.into()));
+    }
+
     // storage_withdraw
     //
     // If no amount than the full amount is refunded
@@ -470,6 +638,120 @@ mod tests {
         contract.storage_unregister(Some(false));
     }
 
+    #[test]
+    #[should_panic(
+        expected = r#"Can't unregister the account while it pays for 10 bytes of storage"#
+    )]
+    fn storage_unregister_with_used_bytes() {
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder.build());
+        let mut contract = MockMoloch::new().build();
+        testing_env!(context_builder.attached_deposit(storage_deposit()).build());
+        contract.storage_deposit(None, None);
+        contract.update_available_storage(bob(), 100, 110);
+        testing_env!(context_builder.attached_deposit(1).build());
+        contract.storage_unregister(Some(true));
+    }
+
+    #[test]
+    fn storage_unregister_sponsor_after_beneficiary_withdraws() {
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder.build());
+        let mut contract = MockMoloch::new()
+            .register_user(alice(), storage_deposit(), storage_deposit())
+            .register_user(bob(), storage_deposit(), storage_deposit())
+            .build();
+        testing_env!(context_builder
+            .predecessor_account_id(alice().try_into().unwrap())
+            .attached_deposit(100)
+            .build());
+        contract.escrow_deposit_near(Some(bob()));
+        let alice_storage = contract.user_storage_accounts.get(&alice()).unwrap();
+        assert!(
+            alice_storage.used_bytes > 0,
+            "Sponsor was not charged for the escrow entry"
+        );
+
+        testing_env!(context_builder
+            .predecessor_account_id(bob().try_into().unwrap())
+            .attached_deposit(0)
+            .build());
+        contract.escrow_withdraw_near(100.into());
+        let alice_storage = contract.user_storage_accounts.get(&alice()).unwrap();
+        assert_eq!(
+            alice_storage.used_bytes, 0,
+            "Sponsor still pays for the beneficiary's storage"
+        );
+        assert_eq!(
+            alice_storage.available,
+            storage_deposit(),
+            "Sponsor was not refunded"
+        );
+
+        testing_env!(context_builder
+            .predecessor_account_id(alice().try_into().unwrap())
+            .attached_deposit(1)
+            .build());
+        let unregistered = contract.storage_unregister(Some(true));
+        assert_eq!(unregistered, true, "Sponsor was not unregistered");
+    }
+
+    #[test]
+    fn storage_sponsor_refunded_for_bytes_beneficiary_frees() {
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder.build());
+        let mut contract = MockMoloch::new()
+            .register_user(alice(), storage_deposit(), storage_deposit())
+            .register_user(bob(), storage_deposit(), 0)
+            .build();
+        testing_env!(context_builder
+            .predecessor_account_id(alice().try_into().unwrap())
+            .attached_deposit(100)
+            .build());
+        contract.escrow_deposit_near(Some(bob()));
+        let used_bytes = contract
+            .user_storage_accounts
+            .get(&alice())
+            .unwrap()
+            .used_bytes;
+
+        testing_env!(context_builder.attached_deposit(0).build());
+        contract.update_available_storage(bob(), 100, 90);
+
+        let alice_storage = contract.user_storage_accounts.get(&alice()).unwrap();
+        assert_eq!(
+            alice_storage.used_bytes,
+            used_bytes - 10,
+            "Sponsor was not refunded the freed bytes"
+        );
+        let bob_storage = contract.user_storage_accounts.get(&bob()).unwrap();
+        assert_eq!(
+            bob_storage.available, 0,
+            "Beneficiary was refunded bytes it didn't pay for"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Can't unregister the account while others pay for"#)]
+    fn storage_unregister_with_sponsored_bytes() {
+        let mut context_builder = get_context_builder(false);
+        testing_env!(context_builder.build());
+        let mut contract = MockMoloch::new()
+            .register_user(alice(), storage_deposit(), storage_deposit())
+            .register_user(bob(), storage_deposit(), 0)
+            .build();
+        testing_env!(context_builder
+            .predecessor_account_id(alice().try_into().unwrap())
+            .attached_deposit(100)
+            .build());
+        contract.escrow_deposit_near(Some(bob()));
+        testing_env!(context_builder
+            .predecessor_account_id(bob().try_into().unwrap())
+            .attached_deposit(1)
+            .build());
+        contract.storage_unregister(Some(true));
+    }
+
     // storage_unregister
 
     // Call and get back expected balance
@@ -0,0 +1,142 @@
+extern crate near_sdk;
+
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::collections::LookupMap;
+use near_sdk::{near_bindgen, AccountId, PanicOnDefault};
+
+// Token ledger
+//
+// Holds what each account can withdraw from moloch per token. Payouts are
+// credited here and paid out later with withdraw_balance so accounting never
+// depends on a cross contract transfer succeeding.
+#[near_bindgen]
+#[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
+pub struct TokenLedger {
+    balances: LookupMap<(AccountId, AccountId), u128>,
+    /// Approved token whose withdrawable balances are totalled
+    token_id: AccountId,
+    /// Sum of every account's withdrawable balance of the approved token
+    token_total: u128,
+}
+
+impl TokenLedger {
+    pub fn new(approved_token: AccountId) -> Self {
+        Self {
+            balances: LookupMap::new(b"token_ledger".to_vec()),
+            token_id: approved_token,
+            token_total: 0,
+        }
+    }
+
+    pub fn credit(&mut self, account_id: AccountId, token_id: AccountId, amount: u128) -> u128 {
+        let key = (account_id, token_id);
+        let balance = match self.balances.get(&key) {
+            Some(balance) => balance,
+            None => 0,
+        };
+        if amount == 0 {
+            return balance;
+        };
+        if key.1 == self.token_id {
+            self.token_total += amount;
+        };
+        let updated_balance = balance + amount;
+        self.balances.insert(&key, &updated_balance);
+        updated_balance
+    }
+
+    pub fn debit(&mut self, account_id: AccountId, token_id: AccountId, amount: u128) -> u128 {
+        let key = (account_id, token_id);
+        let balance = match self.balances.get(&key) {
+            Some(balance) => balance,
+            None => 0,
+        };
+        let updated_balance = match balance.checked_sub(amount) {
+            Some(balance) => balance,
+            None => panic!(
+                "Insufficient withdrawable balance of {} for {}!",
+                key.1, key.0
+            ),
+        };
+        if key.1 == self.token_id {
+            self.token_total -= amount;
+        };
+        if updated_balance == 0 {
+            self.balances.remove(&key);
+        } else {
+            self.balances.insert(&key, &updated_balance);
+        };
+        updated_balance
+    }
+
+    pub fn balance_of(&self, account_id: AccountId, token_id: AccountId) -> u128 {
+        return match self.balances.get(&(account_id, token_id)) {
+            Some(balance) => balance,
+            None => 0,
+        };
+    }
+
+    pub fn token_total(&self) -> u128 {
+        self.token_total
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mocks::{bob, fdai, get_context};
+    use near_sdk::{testing_env, MockedBlockchain};
+
+    #[test]
+    fn credit_and_debit() {
+        let context = get_context(false);
+        testing_env!(context);
+
+        let mut ledger = TokenLedger::new(fdai());
+        assert_eq!(ledger.credit(bob(), fdai(), 10), 10);
+        assert_eq!(ledger.credit(bob(), fdai(), 5), 15);
+        assert_eq!(ledger.balance_of(bob(), "NEAR".to_string()), 0);
+        assert_eq!(ledger.debit(bob(), fdai(), 15), 0);
+        assert!(
+            ledger.balances.get(&(bob(), fdai())).is_none(),
+            "Empty balance was not removed"
+        );
+    }
+
+    #[test]
+    fn token_total() {
+        let context = get_context(false);
+        testing_env!(context);
+
+        let mut ledger = TokenLedger::new(fdai());
+        ledger.credit(bob(), fdai(), 10);
+        ledger.credit(fdai(), fdai(), 5);
+        ledger.credit(bob(), "NEAR".to_string(), 7);
+        assert_eq!(ledger.token_total(), 15);
+        ledger.debit(bob(), fdai(), 4);
+        ledger.debit(bob(), "NEAR".to_string(), 7);
+        assert_eq!(ledger.token_total(), 11);
+    }
+
+    #[test]
+    fn credit_zero_does_not_add_entry() {
+        let context = get_context(false);
+        testing_env!(context);
+
+        let mut ledger = TokenLedger::new(fdai());
+        ledger.credit(bob(), fdai(), 0);
+        assert!(ledger.balances.get(&(bob(), fdai())).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Insufficient withdrawable balance of fdai.testnet for bob.near"#)]
+    fn debit_insufficient_balance() {
+        let context = get_context(false);
+        testing_env!(context);
+
+        let mut ledger = TokenLedger::new(fdai());
+        ledger.credit(bob(), fdai(), 10);
+        ledger.debit(bob(), fdai(), 11);
+    }
+}
@@ -37,12 +37,47 @@ fn simulate_submit_proposal() {
         moloch.submit_proposal(
             alice.valid_account_id().to_string(),
             to_yocto("2").into(),
+            0.into(),
+            vec![],
             15.into(),
             "A random proposal".to_string()
         ),
-        1,
+        0,
         near_sdk_sim::DEFAULT_GAS
     );
     let moloch_balance: U128 = view!(fdai.ft_balance_of(moloch.valid_account_id())).unwrap_json();
     assert_eq!(to_yocto("4"), moloch_balance.0);
 }
+
+#[test]
+fn simulate_summon_confirms_approved_token() {
+    let (_root, moloch, _fdai, _alice, _bob, _deposit_amount) = init_moloch();
+    let is_active: bool = view!(moloch.is_active()).unwrap_json();
+    assert!(is_active, "Approved token was not confirmed at summon");
+}
+
+#[test]
+fn simulate_reconcile_sweeps_plain_transfer() {
+    let (root, moloch, fdai, _alice, bob, _deposit_amount) = init_moloch();
+    register_user_moloch(&bob, &moloch);
+    call!(
+        bob,
+        fdai.ft_transfer(moloch.user_account.valid_account_id(), to_yocto("3").into(), None),
+        deposit = 1
+    )
+    .assert_success();
+
+    // The first reconcile only records the surplus
+    call!(bob, moloch.reconcile(true), 0, near_sdk_sim::DEFAULT_GAS).assert_success();
+    let bank_balance: U128 = view!(moloch.get_bank_balance()).unwrap_json();
+    assert_eq!(0, bank_balance.0);
+
+    // Periods are a second and each block a second, so this passes SURPLUS_SWEEP_DELAY periods
+    root.borrow_runtime_mut().produce_blocks(2).unwrap();
+    call!(bob, moloch.reconcile(true), 0, near_sdk_sim::DEFAULT_GAS).assert_success();
+    let bank_balance: U128 = view!(moloch.get_bank_balance()).unwrap_json();
+    assert_eq!(to_yocto("3"), bank_balance.0);
+    let liabilities: U128 = view!(moloch.get_token_liabilities()).unwrap_json();
+    let moloch_balance: U128 = view!(fdai.ft_balance_of(moloch.valid_account_id())).unwrap_json();
+    assert_eq!(moloch_balance.0, liabilities.0);
+}
@@ -1,4 +1,4 @@
-use moloch::MolochContract;
+use moloch::{Founder, MolochContract, PeriodClock};
 use near_sdk::serde_json::json;
 use near_sdk_sim::{call, deploy, init_simulator, to_yocto, ContractAccount, UserAccount};
 use test_fungible_token::ContractContract as FdaiContract;
@@ -72,16 +72,19 @@ pub fn init_moloch() -> (
         bytes: &MOLOCH_WASM_BYTES,
         signer_account: root,
         init_method: new(
-            bob.valid_account_id().to_string(),
+            vec![Founder::new(bob.valid_account_id().to_string(), 1u128.into(), None)],
             fdai.user_account.account_id.to_string(),
             // nanoseconds
              10u64.pow(9).into(),
+             PeriodClock::Timestamp,
              3u64.into(),
              1u64.into(),
              2u64.into(),
              deposit_amount.into(),
              2u128.into(),
-             1u128.into()
+             1u128.into(),
+             0u128.into(),
+             None
              )
     );
 
@@ -164,12 +164,24 @@ describe("Moloch test", () => {
 
   // Create a proposal for bob
   test("Create a proposal for the first member", async () => {
+    // Alice consents to the tribute the proposal takes from her escrow
+    await alice.functionCall({
+      contractId: contractAccountId,
+      methodName: "authorize_tribute",
+      args: {
+        proposer: masterContractId,
+        token_tribute: "10",
+        near_tribute: "0"
+      }
+    });
     await masterAccount.functionCall({
       contractId: contractAccountId,
       methodName: "submit_proposal",
       args: {
         applicant: aliceId,
         token_tribute: "10",
+        near_tribute: "0",
+        nft_tribute: [],
         shares_requested: "10",
         details: "Let's add a second member"
       },
@@ -255,13 +267,24 @@ describe("Moloch test", () => {
       args: {
         proposal_index: "0"
       },
-      attachedDeposit: "1",
       gas: 300000000000000
     });
 
     const bankBalance = await getBankBalance(masterAccount, contractAccountId);
     expect(bankBalance).toEqual("10");
 
+    // Withdraw processing reward
+    await bob.functionCall({
+      contractId: contractAccountId,
+      methodName: "withdraw_balance",
+      args: {
+        token_id: ftAccountId,
+        amount: "1"
+      },
+      attachedDeposit: "1",
+      gas: 300000000000000
+    });
+
     const aliceBalance = await balanceOfFdai(
       masterAccount,
       aliceId,
@@ -299,7 +322,6 @@ describe("Moloch test", () => {
       args: {
         shares_to_burn: "5" // 5 of 11 total
       },
-      attachedDeposit: "1",
       gas: 300000000000000
     });
 
@@ -31,7 +31,11 @@ const config = {
       "process_proposal",
       "rage_quit",
       "abort",
-      "update_delegate_key"
+      "update_delegate_key",
+      "withdraw_balance",
+      "authorize_tribute",
+      "pledge_tribute",
+      "cancel_pending_proposal"
     ],
     viewMethods: [
       "get_current_period",